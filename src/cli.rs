@@ -1,5 +1,5 @@
 // std
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 // crates.io
 use bitcoin::Network;
 use clap::{
@@ -10,7 +10,11 @@ use clap::{
 	ArgGroup, Parser, ValueEnum,
 };
 // atomicalsir
-use crate::{engine::*, prelude::*};
+use crate::{
+	electrumx::{self, r#type::FeeRate},
+	engine::*,
+	prelude::*,
+};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -38,8 +42,10 @@ pub struct Cli {
 	#[arg(long, value_name = "PATH", group = "engine")]
 	js_engine: Option<PathBuf>,
 	/// Network type.
-	#[arg(value_enum, long, value_name = "NETWORK", default_value_t = Network_::Mainnet)]
-	network: Network_,
+	///
+	/// Defaults to the network of the electrumx config, or to mainnet.
+	#[arg(value_enum, long, value_name = "NETWORK")]
+	network: Option<Network_>,
 	/// Maximum acceptable fee.
 	///
 	/// This value will be passed to atomicals-js's `--satsbyte` flag if the current network's
@@ -48,32 +54,508 @@ pub struct Cli {
 	max_fee: u64,
 	/// Specify the URI of the electrumx.
 	///
+	/// Defaults to the public proxy of the network.
 	/// Example:
 	/// - https://ep.atomicals.xyz/proxy
+	#[arg(verbatim_doc_comment, long, value_name = "URI")]
+	electrumx: Option<String>,
+	/// Read the electrumx client settings from the JSON file at PATH instead.
+	///
+	/// Holds the base URIs, timeouts, retries, proxy and concurrency, see `ClientConfig`.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "PATH", conflicts_with = "electrumx")]
+	electrumx_config: Option<PathBuf>,
+	/// Ticker of the network to mine on.
+	#[arg(long, value_name = "NAME", required_unless_present_any = ["file", "snapshot", "benchmark", "split_funding", "broadcast_psbt", "tickers_status", "replay", "watch_funding"])]
+	ticker: Option<String>,
+	/// Mint the file at PATH as an NFT instead of mining a ticker.
+	///
+	/// The content type is guessed from the extension, e.g. `image/png` or `application/json`.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "PATH", conflicts_with = "ticker")]
+	file: Option<PathBuf>,
+	/// Bitwork the commit of the NFT mint has to satisfy.
+	#[arg(long, value_name = "PREFIX", requires = "file")]
+	bitworkc: Option<String>,
+	/// Report what a mint with each wallet would cost and pay, without spending anything.
+	///
+	/// Checks the ticker, estimates the fees and looks for a funding UTXO.
+	/// Only works with the Rust engine.
+	#[arg(long, conflicts_with = "file")]
+	simulate: bool,
+	/// Report whether the ticker is still open and how many mints are left, along with the
+	/// balance and the pending transactions of each wallet, without spending anything.
+	///
+	/// Only works with the Rust engine.
+	#[arg(long, conflicts_with_all = ["file", "simulate"])]
+	status: bool,
+	/// Log the commit of a mint with each wallet as an unsigned PSBT, in hex, instead of signing
+	/// it.
+	///
+	/// For an external wallet to sign, e.g. a hardware wallet, then `--broadcast-psbt` it. The
+	/// reveal is not part of it.
+	/// Only works with the Rust engine.
+	#[arg(long, conflicts_with_all = ["file", "simulate", "status"])]
+	export_psbt: bool,
+	/// Broadcast the HEX PSBTs once signed by an external wallet, e.g. the ones of
+	/// `--export-psbt`.
+	///
+	/// All of them go out, even if some fail.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "HEX", num_args = 1.., conflicts_with_all = ["ticker", "file"])]
+	broadcast_psbt: Vec<String>,
+	/// Audit every mint of the ticker, reporting the ones which went to the wallets.
+	///
+	/// Walks the whole history of the ticker, so it's slow for tickers with many mints.
+	/// Only works with the Rust engine.
+	#[arg(long, conflicts_with_all = ["file", "simulate", "status"])]
+	mint_history: bool,
+	/// Report whether each of the TICKERS is still open and how many mints are left, e.g.
+	/// `atom,quark`.
+	///
+	/// A ticker which can't be fetched is left out, no wallet is involved.
+	/// Only works with the Rust engine.
 	#[arg(
-		verbatim_doc_comment,
 		long,
-		value_name = "URI",
-		default_value_t = String::from("https://ep.atomicals.xyz/proxy")
+		value_name = "TICKERS",
+		value_delimiter = ',',
+		conflicts_with_all = ["ticker", "file", "simulate", "status"]
 	)]
-	electrumx: String,
-	/// Ticker of the network to mine on.
-	#[arg(long, value_name = "NAME")]
-	ticker: String,
+	tickers_status: Vec<String>,
+	/// Write a snapshot of the atomicals held by the wallets to PATH, as JSON.
+	///
+	/// Lists the ticker, amount and atomical id of each, sorted so that snapshots diff cleanly.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "PATH", conflicts_with_all = ["ticker", "file"])]
+	snapshot: Option<PathBuf>,
+	/// Benchmark the mining against the synthetic BITWORK, e.g. `0000` or `000.8`.
+	///
+	/// Reports the hash rate and the solutions found, nothing is spent nor broadcast.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "BITWORK", conflicts_with_all = ["ticker", "file", "snapshot"])]
+	benchmark: Option<String>,
+	/// Split the clean funding UTXOs of each wallet into COUNT outputs, to fund several mints at
+	/// once.
+	///
+	/// Waits for the funding confirmations of the client before reporting the new UTXOs.
+	/// Only works with the Rust engine.
+	#[arg(
+		long,
+		value_name = "COUNT",
+		requires = "split_value",
+		conflicts_with_all = ["ticker", "file", "snapshot", "benchmark"]
+	)]
+	split_funding: Option<usize>,
+	/// Value of each output of the split, in sats.
+	#[arg(long, value_name = "SATS", requires = "split_funding")]
+	split_value: Option<u64>,
+	/// Watch the funding address of each wallet, reporting every new clean UTXO of at least SATS
+	/// as it comes in.
+	///
+	/// Runs until interrupted.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SATS", conflicts_with_all = ["ticker", "file", "split_funding"])]
+	watch_funding: Option<u64>,
+	/// Run the benchmark for SECONDS.
+	#[arg(long, value_name = "SECONDS", default_value_t = 10, requires = "benchmark")]
+	benchmark_time: u64,
 	/// Maximum number of mints.
 	#[arg(long, value_name = "VALUE", default_value_t = 1)]
 	max_mints: usize,
+	/// Sweep the funding address's leftover change into one UTXO after each mint.
+	///
+	/// Kicks in once there are more than COUNT UTXOs below 10,000 sats.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "COUNT")]
+	consolidate_threshold: Option<usize>,
+	/// Fee rate used by the consolidation, in sat/vB.
+	#[arg(long, value_name = "VALUE", default_value_t = 2.)]
+	consolidate_fee: f64,
+	/// Wait for the commit to have COUNT confirmations before broadcasting the reveal.
+	///
+	/// A mint can be revealed right away, in the same block as its commit. The bitworkr of the
+	/// reveal is mined meanwhile, unless the state is persisted.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "COUNT", default_value_t = 0)]
+	commit_confirmations: u64,
+	/// Keep the state of the mint in progress in `<FUNDING_ADDRESS>.pending.json`.
+	///
+	/// After a crash between the commit and the reveal, the next run resumes the pending mint
+	/// instead of leaving the committed funds behind.
+	/// Only works with the Rust engine.
+	#[arg(long)]
+	persist_state: bool,
+	/// Re-estimate the fees on each poll while waiting for the funding UTXO.
+	///
+	/// If fees rise meanwhile, the required amount is revised upward, so the mint still gets in.
+	/// Only works with the Rust engine.
+	#[arg(long)]
+	reprice_funding: bool,
+	/// Make sure the commit output is unspent right before broadcasting the reveal.
+	///
+	/// Catches a commit which silently failed, instead of sending an unspendable reveal.
+	/// Only works with the Rust engine.
+	#[arg(long)]
+	verify_commit: bool,
+	/// Mine the bitwork with COUNT threads, each searching its own range of sequences.
+	///
+	/// All the CPUs by default.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "COUNT")]
+	workers: Option<usize>,
+	/// Report the hash rate, the hashes and the elapsed time every SECONDS while mining.
+	///
+	/// `0` only reports once a stage is done.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SECONDS", default_value_t = 30)]
+	hash_rate_interval: u64,
+	/// Save the progress of the commit mining every SECONDS, to resume it after a crash.
+	///
+	/// The search then skips the sequences which were already tried.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SECONDS")]
+	checkpoint_interval: Option<u64>,
+	/// Pin the mining workers to the CPU cores IDS, in turn, e.g. `0,2,4`.
+	///
+	/// The OS schedules them freely by default.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "IDS", value_delimiter = ',')]
+	worker_cores: Vec<usize>,
+	/// Run the mining workers at the nice LEVEL, up to `19` for the lowest priority.
+	///
+	/// Only supported on Unix, where going below `0` takes privileges.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "LEVEL", allow_negative_numbers = true)]
+	worker_nice: Option<i32>,
+	/// Give up a mining stage which is still searching after SECONDS.
+	///
+	/// A first Ctrl-C gives it up too.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SECONDS")]
+	max_mining_time: Option<u64>,
+	/// Check every SECONDS while mining that the ticker has mints left, giving up once it has
+	/// none.
+	///
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SECONDS")]
+	mint_check_interval: Option<u64>,
+	/// Refuse to broadcast once the session would spend more than SATS in total, fees included.
+	///
+	/// The change paid back to the wallet doesn't count.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SATS")]
+	spend_cap: Option<u64>,
+	/// Wait up to SECONDS after each broadcast for another base URI to see the transaction,
+	/// failing otherwise.
+	///
+	/// Catches a proxy which accepted the transaction without relaying it.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "SECONDS")]
+	verify_broadcast: Option<u64>,
+	/// Probe the base URIs of the electrumx before mining, refusing to start unless at least
+	/// COUNT of them are healthy.
+	///
+	/// `0` skips the probe.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "COUNT", default_value_t = 0)]
+	require_healthy: usize,
+	/// Fund at most COUNT mints from the funding address of each wallet.
+	///
+	/// The mints then draw from the wallets in turn, each mint going to the next wallet which
+	/// holds a funding UTXO, until all of them reached their quota.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "COUNT")]
+	funding_quota: Option<usize>,
+	/// Save the configuration, the electrumx traffic and the outcome of the run to PATH.
+	///
+	/// Attach it to a bug report, so the run can be replayed offline.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "PATH")]
+	scenario: Option<PathBuf>,
+	/// Replay the scenario at PATH offline, reporting each electrumx response and the outcome of
+	/// the recorded run.
+	///
+	/// Nothing is sent to the network.
+	/// Only works with the Rust engine.
+	#[arg(long, value_name = "PATH", conflicts_with_all = ["ticker", "file", "scenario"])]
+	replay: Option<PathBuf>,
+	/// Log format.
+	///
+	/// `json` emits one object per event, with the uri, method, attempt and latency_ms of the
+	/// electrumx requests as fields.
+	#[arg(value_enum, long, value_name = "FORMAT", default_value_t = LogFormat::Text)]
+	log_format: LogFormat,
 }
 impl Cli {
+	pub fn init_tracing(&self) {
+		match self.log_format {
+			LogFormat::Text => tracing_subscriber::fmt::init(),
+			LogFormat::Json => tracing_subscriber::fmt().json().init(),
+		}
+	}
+
 	pub async fn run(self) -> Result<()> {
-		let Cli { rust_engine, js_engine, network, max_fee, electrumx, ticker, max_mints } = self;
-		let ticker = ticker.to_lowercase();
+		let Cli {
+			rust_engine,
+			js_engine,
+			network,
+			max_fee,
+			electrumx,
+			electrumx_config,
+			ticker,
+			file,
+			bitworkc,
+			simulate,
+			status,
+			mint_history,
+			tickers_status,
+			export_psbt,
+			broadcast_psbt,
+			snapshot,
+			benchmark,
+			benchmark_time,
+			split_funding,
+			split_value,
+			watch_funding,
+			max_mints,
+			consolidate_threshold,
+			consolidate_fee,
+			commit_confirmations,
+			persist_state,
+			reprice_funding,
+			verify_commit,
+			workers,
+			hash_rate_interval,
+			checkpoint_interval,
+			worker_cores,
+			worker_nice,
+			max_mining_time,
+			mint_check_interval,
+			spend_cap,
+			verify_broadcast,
+			require_healthy,
+			funding_quota,
+			scenario,
+			replay,
+			log_format: _,
+		} = self;
+		// Either is required by clap.
+		let ticker = ticker.unwrap_or_default().to_lowercase();
+		let hash_rate_interval =
+			(hash_rate_interval > 0).then(|| Duration::from_secs(hash_rate_interval));
+		let checkpoint_interval = checkpoint_interval.filter(|&i| i > 0).map(Duration::from_secs);
+		let verify_broadcast = verify_broadcast.map(Duration::from_secs);
+		let priority = rust::WorkerPriority { cores: worker_cores, nice: worker_nice };
+		let stop = rust::StopCondition {
+			cancel: Default::default(),
+			max_duration: max_mining_time.map(Duration::from_secs),
+			mint_check_interval: mint_check_interval.map(Duration::from_secs),
+		};
+
+		if let Some(b) = benchmark {
+			if rust_engine.is_none() {
+				Err(anyhow::anyhow!("benchmarking only works with the Rust engine"))?;
+			}
+
+			rust::benchmark(&b, Duration::from_secs(benchmark_time), workers, hash_rate_interval)?;
+		} else if let Some(p) = replay {
+			if rust_engine.is_none() {
+				Err(anyhow::anyhow!("replaying a scenario only works with the Rust engine"))?;
+			}
+
+			rust::replay(&p).await?;
+		} else if let Some(c) = split_funding {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("splitting the funding only works with the Rust engine"))?
+			};
+
+			rust::split_funding(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				c,
+				// Required by clap.
+				split_value.unwrap_or_default(),
+			)
+			.await?;
+		} else if let Some(v) = watch_funding {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("watching the funding only works with the Rust engine"))?
+			};
+
+			rust::watch_funding(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				v,
+			)
+			.await?;
+		} else if let Some(f) = file {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("minting a file only works with the Rust engine"))?
+			};
+
+			stop.cancel_on_ctrl_c();
+
+			rust::mint_file(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&f,
+				bitworkc.as_deref(),
+				max_fee,
+				commit_confirmations,
+				persist_state,
+				reprice_funding,
+				verify_commit,
+				workers,
+				hash_rate_interval,
+				checkpoint_interval,
+				priority,
+				stop,
+				spend_cap,
+				verify_broadcast,
+				require_healthy,
+			)
+			.await?;
+		} else if !broadcast_psbt.is_empty() {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("broadcasting PSBTs only works with the Rust engine"))?
+			};
+
+			rust::broadcast_psbts(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&broadcast_psbt,
+			)
+			.await?;
+		} else if export_psbt {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("exporting a PSBT only works with the Rust engine"))?
+			};
+
+			rust::export_psbt(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&ticker,
+				max_fee,
+			)
+			.await?;
+		} else if let Some(p) = snapshot {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("taking a snapshot only works with the Rust engine"))?
+			};
+
+			rust::snapshot(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&p,
+			)
+			.await?;
+		} else if status {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("reporting the status only works with the Rust engine"))?
+			};
+
+			rust::status(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&ticker,
+			)
+			.await?;
+		} else if mint_history {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("auditing the mints only works with the Rust engine"))?
+			};
+
+			rust::mint_history(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&ticker,
+			)
+			.await?;
+		} else if !tickers_status.is_empty() {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("reporting the tickers only works with the Rust engine"))?
+			};
+
+			rust::tickers_status(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&tickers_status,
+			)
+			.await?;
+		} else if simulate {
+			let Some(d) = rust_engine else {
+				Err(anyhow::anyhow!("simulating a mint only works with the Rust engine"))?
+			};
+
+			rust::simulate(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&ticker,
+				max_fee,
+			)
+			.await?;
+		} else if let Some(d) = js_engine {
+			let network = network.unwrap_or(Network_::Mainnet);
+			let electrumx = match electrumx {
+				Some(u) => u,
+				None => electrumx::default_base_uris_of(network.clone().into())?.remove(0),
+			};
 
-		if let Some(d) = js_engine {
 			js::run(network.as_atomical_js_network(), &electrumx, &d, &ticker, max_fee, max_mints)
 				.await?;
 		} else if let Some(d) = rust_engine {
-			rust::run(network.into(), &electrumx, &d, &ticker, max_fee, max_mints).await?;
+			stop.cancel_on_ctrl_c();
+
+			let consolidation = consolidate_threshold.map(|threshold| rust::Consolidation {
+				threshold,
+				fee_rate: FeeRate::from_sat_per_vb(consolidate_fee),
+			});
+
+			rust::run(
+				network.map(Into::into),
+				electrumx.as_deref(),
+				electrumx_config.as_deref(),
+				&d,
+				&ticker,
+				max_fee,
+				max_mints,
+				consolidation,
+				commit_confirmations,
+				persist_state,
+				reprice_funding,
+				verify_commit,
+				workers,
+				hash_rate_interval,
+				checkpoint_interval,
+				priority,
+				stop,
+				spend_cap,
+				verify_broadcast,
+				require_healthy,
+				funding_quota,
+				scenario.as_deref(),
+			)
+			.await?;
 		}
 
 		Ok(())
@@ -102,6 +584,12 @@ impl From<Network_> for Network {
 	}
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+enum LogFormat {
+	Text,
+	Json,
+}
+
 fn styles() -> Styles {
 	Styles::styled()
 		.header(AnsiColor::Red.on_default() | Effects::BOLD)
@@ -1,22 +1,161 @@
 #[cfg(test)] mod test;
 
+pub mod backoff;
+use backoff::BackoffPolicy;
+
+pub mod clock;
+use clock::{Clock, RealClock};
+
+pub mod config;
+
+pub mod error;
+use error::{AtomicalsError, Error as ElectrumXError, Exhaustion};
+
+pub mod funding;
+
+pub mod registry;
+
+pub mod scenario;
+use scenario::{Exchange, Scenario};
+
+pub mod selection;
+use selection::CoinSelector;
+
+pub mod spend;
+use spend::SpendCap;
+
 pub mod r#type;
 use r#type::*;
 
 // std
 // std
-use std::{str::FromStr, sync::Mutex, time::Duration};
+use std::{
+	collections::HashMap,
+	error::Error as StdError,
+	fmt::{Debug, Formatter, Result as FmtResult},
+	future::Future,
+	io::{self, ErrorKind},
+	pin::pin,
+	str::FromStr,
+	sync::{Arc, Mutex, RwLock},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 // crates.io
-use bitcoin::{Address, Network};
-use reqwest::{Client as ReqwestClient, ClientBuilder as ReqwestClientBuilder};
-use serde::{de::DeserializeOwned, Serialize};
-use tokio::time;
+use bitcoin::{
+	consensus::encode, Address, Amount, Network, OutPoint, Psbt, ScriptBuf, Transaction, TxOut,
+	Txid, Witness,
+};
+use futures::{future, stream, stream::FuturesUnordered, Stream, StreamExt, TryStreamExt};
+use rand::Rng;
+use reqwest::{
+	header::{HeaderMap, LOCATION},
+	redirect::Policy,
+	Client as ReqwestClient, ClientBuilder as ReqwestClientBuilder, Proxy, RequestBuilder,
+	Response as ReqwestResponse, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{Semaphore, SemaphorePermit};
 // atomicalsir
 use crate::{prelude::*, util};
 
+// Rebuild the HTTP client of a base URI after this many consecutive connection failures on it.
+const MAX_CONNECT_FAILURES: usize = 2;
+// Most proxies sit behind nginx, whose default `client_max_body_size` is 1 MiB.
+const DEFAULT_MAX_BROADCAST_SIZE: usize = 1024 * 1024;
+// Enough for an HTTP to HTTPS upgrade plus a moved proxy.
+const DEFAULT_MAX_REDIRECTS: usize = 3;
+// A base URI which returned junk joins the next races this much later, per junk response.
+const RACE_PENALTY: Duration = Duration::from_millis(500);
+const MAX_RACE_PENALTY: u32 = 4;
+// Transactions per page while walking the history of an atomical.
+const HISTORY_PAGE_SIZE: u64 = 100;
+// Resolving the atomicals of a wallet could otherwise flood the proxy.
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+// Lookups against an index lagging the chain tip by more than this are worth a warning.
+pub const MAX_INDEX_LAG: u64 = 2;
+// Between the polls of the waits, e.g. for a UTXO or a confirmation.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Far above any sane bid, a transaction paying more is most likely a bug.
+const DEFAULT_MAX_FEE_RATE: f64 = 1_000.;
+// Don't let a proxy park the client for too long with its `X-RateLimit-Reset`.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// The public proxies of the given network, used when no base URI is set explicitly.
+pub fn default_base_uris_of(network: Network) -> Result<Vec<String>> {
+	match network {
+		Network::Bitcoin => Ok(vec!["https://ep.atomicals.xyz/proxy".into()]),
+		Network::Testnet => Ok(vec!["https://eptestnet.atomicals.xyz/proxy".into()]),
+		n => Err(ElectrumXError::NoDefaultBaseUri(n))?,
+	}
+}
+
 pub trait Config {
 	fn network(&self) -> &Network;
 	fn base_uris(&self) -> &[String];
+
+	/// The base URIs to try, in order, for the given method.
+	///
+	/// Override this to route specific methods to specific backends, e.g. broadcasts to a private
+	/// node and queries to the public proxies.
+	fn base_uri_for(&self, _method: &str) -> &[String] {
+		self.base_uris()
+	}
+
+	fn clock(&self) -> &dyn Clock {
+		&RealClock
+	}
+
+	/// How long the waits sleep between their polls.
+	fn poll_interval(&self) -> Duration {
+		DEFAULT_POLL_INTERVAL
+	}
+
+	/// Minimum confirmations a UTXO needs before [`Api::wait_until_funding`] accepts it.
+	///
+	/// `0` accepts unconfirmed funds, which could disappear on a reorg or be double spent.
+	fn funding_min_confirmations(&self) -> u64 {
+		0
+	}
+
+	/// Maximum size of the transaction hex accepted by [`Api::broadcast`].
+	fn max_broadcast_size(&self) -> usize {
+		DEFAULT_MAX_BROADCAST_SIZE
+	}
+
+	/// How many of the transactions of [`Api::broadcast_stream`] may be in flight at once.
+	fn max_concurrent_broadcasts(&self) -> usize {
+		1
+	}
+
+	/// How long [`Api::broadcast`] waits for another backend to see the transaction.
+	///
+	/// `None` trusts the broadcasting backend.
+	fn broadcast_verify_window(&self) -> Option<Duration> {
+		None
+	}
+
+	/// The base URI which answered the last request of the `method`, if known.
+	fn last_base_uri_of(&self, _method: &str) -> Option<String> {
+		None
+	}
+
+	/// The cap every transaction [`Api::broadcast`] sends is counted against.
+	fn spend_cap(&self) -> Option<&SpendCap> {
+		None
+	}
+
+	/// How [`Api::get_unspent_address_reconciled`] settles the proxies which disagree.
+	fn reconciliation(&self) -> Reconciliation {
+		Reconciliation::default()
+	}
+
+	/// The highest fee rate [`Api::broadcast`] lets through, `None` doesn't check.
+	///
+	/// Pricing the inputs takes their parents, unless [`Api::broadcast_spending`] is given them.
+	fn max_fee_rate(&self) -> Option<FeeRate> {
+		None
+	}
 }
 
 pub trait Http {
@@ -25,6 +164,13 @@ pub trait Http {
 		U: AsRef<str>,
 		P: Serialize,
 		R: DeserializeOwned;
+
+	/// Send a single request to the given base URI, without any retry or failover.
+	async fn post_to<U, P, R>(&self, base_uri: &str, uri: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned;
 }
 
 pub trait Api: Config + Http {
@@ -35,18 +181,36 @@ pub trait Api: Config + Http {
 	// 	format!("{}/{}", self.base_uri(), uri.as_ref())
 	// }
 
+	/// Parse the `address` and make sure it belongs to the configured network.
+	fn address_of<S>(&self, address: S) -> Result<Address>
+	where
+		S: AsRef<str>,
+	{
+		let address = address.as_ref();
+		let a = Address::from_str(address)
+			.map_err(|e| ElectrumXError::InvalidAddress { address: address.into(), source: e })?;
+		let expected = *self.network();
+
+		if !a.is_valid_for_network(expected) {
+			Err(ElectrumXError::AddressWrongNetwork { expected, found: *a.network() })?;
+		}
+
+		Ok(a.assume_checked())
+	}
+
 	async fn get_by_ticker<S>(&self, ticker: S) -> Result<Ticker>
 	where
 		S: AsRef<str>,
 	{
 		Ok(self
-			.post::<_, _, Response<ResponseResult<Ticker>>>(
+			.post::<_, _, Response<ResponseResult<Option<Ticker>>>>(
 				// self.uri_of("blockchain.atomicals.get_by_ticker"),
 				"blockchain.atomicals.get_by_ticker",
 				Params::new([ticker.as_ref()]),
 			)
 			.await?
 			.response
+			.found(ticker.as_ref())?
 			.result)
 	}
 
@@ -55,208 +219,2664 @@ pub trait Api: Config + Http {
 		S: AsRef<str>,
 	{
 		Ok(self
-			.post::<_, _, Response<ResponseResult<Ft>>>(
+			.post::<_, _, Response<ResponseResult<Option<Ft>>>>(
 				// self.uri_of("blockchain.atomicals.get_ft_info"),
 				"blockchain.atomicals.get_ft_info",
 				Params::new([atomical_id.as_ref()]),
 			)
 			.await?
-			.response)
+			.response
+			.found(atomical_id.as_ref())?)
 	}
 
-	async fn get_unspent_address<S>(&self, address: S) -> Result<Vec<Utxo>>
+	/// Poll the atomical until the index resolves it, giving up after `timeout`.
+	///
+	/// The index recognizes a new atomical a while after its reveal confirms, so a step
+	/// depending on it has to wait.
+	async fn wait_until_atomical_indexed<S>(
+		&self,
+		atomical_id: S,
+		timeout: Duration,
+	) -> Result<ResponseResult<Ft>>
 	where
 		S: AsRef<str>,
 	{
-		self.get_unspent_scripthash(util::address2scripthash(
-			&Address::from_str(address.as_ref()).unwrap().require_network(*self.network())?,
-		)?)
-		.await
+		let atomical_id = atomical_id.as_ref();
+		let start = self.clock().now();
+
+		loop {
+			match self.get_ft_info(atomical_id).await {
+				Err(e)
+					if matches!(
+						e.downcast_ref::<ElectrumXError>(),
+						Some(ElectrumXError::NotFound(_))
+					) =>
+					(),
+				r => return r,
+			}
+
+			if self.clock().now() - start >= timeout {
+				Err(ElectrumXError::NotIndexed { atomical_id: atomical_id.into(), timeout })?;
+			}
+
+			tracing::info!("waiting for {atomical_id} to be indexed");
+
+			self.clock().sleep(self.poll_interval()).await;
+		}
 	}
 
-	async fn get_unspent_scripthash<S>(&self, scripthash: S) -> Result<Vec<Utxo>>
+	/// The id of the atomical created by the `vout` output of the reveal `txid`, once the indexer
+	/// resolves it.
+	async fn atomical_id_of(&self, reveal_txid: &Txid, vout: u32) -> Result<String> {
+		Ok(self.get_atomical(util::atomical_id_of(reveal_txid, vout)).await?.result.atomical_id)
+	}
+
+	async fn get_atomical<S>(&self, atomical_id: S) -> Result<ResponseResult<Atomical>>
 	where
 		S: AsRef<str>,
 	{
-		let mut utxos = self
-			.post::<_, _, Response<Vec<Unspent>>>(
-				// self.uri_of("blockchain.scripthash.listunspent"),
-				"blockchain.scripthash.listunspent",
-				Params::new([scripthash.as_ref()]),
+		Ok(self
+			.post::<_, _, Response<ResponseResult<Option<Atomical>>>>(
+				"blockchain.atomicals.get",
+				Params::new([atomical_id.as_ref()]),
 			)
 			.await?
 			.response
-			.into_iter()
-			.map(|u| u.into())
-			.collect::<Vec<Utxo>>();
+			.found(atomical_id.as_ref())?)
+	}
 
-		utxos.sort_by(|a, b| a.value.cmp(&b.value));
+	/// The atomicals carried by the UTXOs of the `address`, in the order they first appear.
+	///
+	/// The value of a UTXO carrying several atomicals is only theirs as far as the proxy reports
+	/// their amounts, it's ambiguous otherwise.
+	async fn get_atomicals_at_address<S>(&self, address: S) -> Result<Vec<AtomicalHolding>>
+	where
+		S: AsRef<str>,
+	{
+		let mut held = Vec::<(String, u64, u64, usize)>::new();
 
-		Ok(utxos)
+		for u in self.get_unspent_address(address).await? {
+			let shared = u.atomicals.len() > 1;
+
+			for id in u.atomicals {
+				let (a, ambiguous) = match u.atomical_values.get(&id) {
+					Some(v) => (*v, 0),
+					None if shared => (0, u.value),
+					None => (u.value, 0),
+				};
+
+				match held.iter_mut().find(|(i, ..)| *i == id) {
+					Some((_, amount, ambiguous_amount, utxos)) => {
+						*amount += a;
+						*ambiguous_amount += ambiguous;
+						*utxos += 1;
+					},
+					None => held.push((id, a, ambiguous, 1)),
+				}
+			}
+		}
+
+		stream::iter(held)
+			.map(|(id, amount, ambiguous_amount, utxos)| async move {
+				let a = self.get_atomical(&id).await?.result;
+
+				Ok(AtomicalHolding {
+					atomical_id: id,
+					r#type: a.r#type,
+					ticker: a.ticker,
+					amount,
+					ambiguous_amount,
+					utxos,
+				})
+			})
+			.buffered(MAX_CONCURRENT_LOOKUPS)
+			.try_collect()
+			.await
 	}
 
-	async fn wait_until_utxo<S>(&self, address: S, satoshis: u64) -> Result<Utxo>
+	/// A [`PortfolioSnapshot`] of the atomicals held by the `addresses`.
+	async fn portfolio_snapshot<S>(&self, addresses: &[S]) -> Result<PortfolioSnapshot>
 	where
 		S: AsRef<str>,
 	{
-		loop {
-			for u in self.get_unspent_address(address.as_ref()).await? {
-				if u.atomicals.is_empty() && u.value >= satoshis {
-					tracing::info!(
-						"Detected Funding UTXO {txid}:{vout}) with value {value} for funding...",
-						txid = u.txid,
-						vout = u.vout,
-						value = u.value
-					);
-					return Ok(u);
+		let mut addresses = addresses.iter().map(|a| a.as_ref().to_owned()).collect::<Vec<_>>();
+		let mut totals = Vec::<AtomicalHolding>::new();
+		let mut by_address = Vec::new();
+
+		addresses.sort();
+		addresses.dedup();
+
+		for address in addresses {
+			let mut holdings = self.get_atomicals_at_address(&address).await?;
+
+			holdings.sort_by(|a, b| a.atomical_id.cmp(&b.atomical_id));
+
+			for h in &holdings {
+				match totals.iter_mut().find(|t| t.atomical_id == h.atomical_id) {
+					Some(t) => {
+						t.amount += h.amount;
+						t.ambiguous_amount += h.ambiguous_amount;
+						t.utxos += h.utxos;
+					},
+					None => totals.push(h.clone()),
 				}
 			}
 
-			tracing::info!(
-				"WAITING for UTXO... UNTIL {btc} BTC RECEIVED AT {addr}",
-				btc = satoshis as f64 / 100000000.,
-				addr = address.as_ref()
-			);
-
-			time::sleep(Duration::from_secs(5)).await;
+			by_address.push(AddressHoldings { address, holdings });
 		}
+
+		totals.sort_by(|a, b| a.atomical_id.cmp(&b.atomical_id));
+
+		Ok(PortfolioSnapshot {
+			timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+			network: self.network().to_string(),
+			totals,
+			addresses: by_address,
+		})
 	}
 
-	async fn broadcast<S>(&self, tx: S) -> Result<String>
+	/// A page of the transactions of the atomical, newest first.
+	///
+	/// Filter them by `op_type`, e.g. `"dmt"` for the mints of a DFT.
+	async fn get_atomical_history<S>(
+		&self,
+		atomical_id: S,
+		op_type: Option<&str>,
+		offset: u64,
+		limit: u64,
+	) -> Result<AtomicalHistory>
 	where
 		S: AsRef<str>,
 	{
 		Ok(self
-			.post::<_, _, Response<String>>(
-				"blockchain.transaction.broadcast",
-				Params::new([tx.as_ref()]),
+			.post::<_, _, Response<AtomicalHistory>>(
+				"blockchain.atomicals.transaction_by_atomical_id",
+				Params::new((atomical_id.as_ref(), limit, offset, op_type, true)),
 			)
 			.await?
 			.response)
 	}
-}
-impl<T> Api for T where T: Config + Http {}
 
-#[derive(Debug)]
-pub struct ElectrumX {
-	pub client: ReqwestClient,
-	pub network: Network,
-	pub base_uris: Vec<String>,
-	pub max_retries: usize,
-	last_successful_uri_index: Mutex<usize>,
-}
-impl Config for ElectrumX {
-	fn network(&self) -> &Network {
-		&self.network
-	}
+	/// Every mint of the `ticker`, each checked against the ticker's mint amount.
+	async fn get_ticker_mints<S>(&self, ticker: S) -> Result<Vec<MintEntry>>
+	where
+		S: AsRef<str>,
+	{
+		let atomical_id = self.get_by_ticker(ticker).await?.atomical_id;
+		let mint_amount = self.get_ft_info(&atomical_id).await?.result.mint_amount;
 
-	fn base_uris(&self) -> &[String] {
-		&self.base_uris
+		self.get_atomical_mints(atomical_id, mint_amount).await
 	}
-}
-impl Http for ElectrumX {
-	async fn post<U, P, R>(&self, endpoint: U, params: P) -> Result<R>
+
+	/// Like [`Api::get_ticker_mints`] but for an already resolved DFT.
+	///
+	/// Walks the whole history page by page, so it's slow for tickers with many mints.
+	async fn get_atomical_mints<S>(
+		&self,
+		atomical_id: S,
+		mint_amount: u64,
+	) -> Result<Vec<MintEntry>>
 	where
-		U: AsRef<str>,
-		P: Serialize,
-		R: DeserializeOwned,
+		S: AsRef<str>,
 	{
-		let mut attempts = 0;
-		let retry_delay = Duration::from_secs(2);
-		let mut uri_change_count = 0;
-
-		// 从 Mutex 中获取当前的 URI 索引
-		let mut uri_index = {
-			let lock = self.last_successful_uri_index.lock().unwrap();
-			*lock
-		};
+		let mut mints = Vec::new();
+		let mut offset = 0;
 
 		loop {
-			let uri = format!("{}/{}", self.base_uris[uri_index], endpoint.as_ref());
+			let page = self
+				.get_atomical_history(atomical_id.as_ref(), Some("dmt"), offset, HISTORY_PAGE_SIZE)
+				.await?;
+			let n = page.result.len() as u64;
 
-			match self.client.post(&uri).json(&params).send().await {
-				Ok(response) => {
-					let resp_text = response.text().await?;
-					match serde_json::from_str(&resp_text) {
-						Ok(parsed) => {
-							// 在单独的作用域中更新 last_successful_uri_index
-							{
-								let mut lock = self.last_successful_uri_index.lock().unwrap();
-								*lock = uri_index;
-							}
-							return Ok(parsed);
-						},
-						Err(e) => {
-							tracing::info!("request {} parse response failed: {}", uri, e);
-							// 解析失败时继续尝试
-						},
-					}
-				},
-				Err(e) => {
-					tracing::info!("request {} failed: {}", uri, e);
-					// 请求失败时继续尝试
-				},
+			mints.extend(
+				future::try_join_all(page.result.iter().map(|t| async move {
+					Ok::<_, Error>(MintEntry::of(
+						&self.get_tx(&t.txid).await?,
+						t.height,
+						mint_amount,
+					))
+				}))
+				.await?,
+			);
+
+			offset += n;
+
+			// `total` saves the request for an empty last page when the server reports it.
+			if n < HISTORY_PAGE_SIZE || (page.total != 0 && offset >= page.total) {
+				return Ok(mints);
 			}
+		}
+	}
 
-			if attempts >= self.max_retries {
-				if uri_change_count < self.base_uris.len() - 1 {
-					uri_index = (uri_index + 1) % self.base_uris.len();
-					uri_change_count += 1;
-					tracing::info!("switching to URI {}", self.base_uris[uri_index]);
-					attempts = 0; // 重置尝试次数
-				} else {
-					return Err(anyhow::Error::msg("All URIs exhausted, still failed").into());
-				}
-			} else {
-				attempts += 1;
-				tracing::info!("retrying in {} seconds...", retry_delay.as_secs());
-				tokio::time::sleep(retry_delay).await;
+	async fn get_unspent_address<S>(&self, address: S) -> Result<Vec<Utxo>>
+	where
+		S: AsRef<str>,
+	{
+		self.get_unspent_scripthash(util::address2scripthash(&self.address_of(address)?)?).await
+	}
+
+	/// The clean UTXOs of the `address` funding `satoshis`, picked by the `selector`, e.g. a
+	/// [`selection::UtxoSelection`].
+	///
+	/// Only the UTXOs with the [`Config::funding_min_confirmations`] are picked from, as listed by
+	/// [`Api::get_unspent_address_reconciled`] since spending a UTXO one proxy got wrong wastes
+	/// the whole transaction.
+	async fn select_unspent_address<S, C>(
+		&self,
+		address: S,
+		satoshis: u64,
+		selector: C,
+	) -> Result<Vec<Utxo>>
+	where
+		S: AsRef<str>,
+		C: CoinSelector,
+	{
+		let min_confirmations = self.funding_min_confirmations();
+		let tip = if min_confirmations > 0 { self.get_block_height().await? } else { 0 };
+		// Whatever the selector does, never hand it UTXOs which would burn atomicals.
+		let utxos = self
+			.get_unspent_address_reconciled(address)
+			.await?
+			.into_iter()
+			.filter(|u| u.atomicals.is_empty() && u.confirmations(tip) >= min_confirmations)
+			.collect::<Vec<_>>();
+
+		match selector.select(&utxos, satoshis) {
+			Some(us) => Ok(us),
+			None => Err(ElectrumXError::InsufficientFunds {
+				needed: satoshis,
+				available: utxos.iter().map(|u| u.value).sum(),
+			})?,
+		}
+	}
+
+	async fn is_utxo_unspent<S>(&self, address: S, outpoint: &OutPoint) -> Result<bool>
+	where
+		S: AsRef<str>,
+	{
+		let (txid, vout) = (outpoint.txid.to_string(), outpoint.vout);
+		let scripthash = util::address2scripthash(&self.address_of(address)?)?;
+		// Parsed up to the outpoint only, a lot of work saved on a large wallet.
+		let mut utxos = pin!(self.get_unspent_scripthash_stream(scripthash).await?);
+
+		while let Some(u) = utxos.try_next().await? {
+			if u.txid == txid && u.vout == vout {
+				return Ok(true);
 			}
 		}
+
+		Ok(false)
 	}
-}
 
-#[derive(Debug)]
-pub struct ElectrumXBuilder {
-	pub network: Network,
-	pub base_uris: Vec<String>,
-}
-impl ElectrumXBuilder {
-	#[cfg(test)]
-	pub fn testnet() -> Self {
-		Self {
-			network: Network::Testnet,
-			base_uris: vec!["https://eptestnet.atomicals.xyz/proxy".into()],
+	/// Make sure the UTXO at `outpoint` of the `address` wasn't spent meanwhile, e.g. by another
+	/// user of a shared wallet, right before spending it.
+	async fn verify_utxo_unspent<S>(&self, address: S, outpoint: &OutPoint) -> Result<()>
+	where
+		S: AsRef<str>,
+	{
+		if !self.is_utxo_unspent(address, outpoint).await? {
+			Err(ElectrumXError::UtxoSpent(*outpoint))?;
 		}
+
+		Ok(())
 	}
 
-	pub fn network(mut self, network: Network) -> Self {
-		self.network = network;
+	/// Make sure the commit landed, its `outpoint` at the reveal `address` being unspent, before
+	/// broadcasting a reveal which would be unspendable otherwise.
+	async fn verify_commit<S>(&self, address: S, outpoint: &OutPoint) -> Result<()>
+	where
+		S: AsRef<str>,
+	{
+		if !self.is_utxo_unspent(address, outpoint).await? {
+			Err(ElectrumXError::CommitNotFound(*outpoint))?;
+		}
 
-		self
+		Ok(())
 	}
 
-	pub fn base_uris<S>(mut self, base_uris: S) -> Self
+	async fn get_unspent_scripthash<S>(&self, scripthash: S) -> Result<Vec<Utxo>>
 	where
-		S: Into<String>,
+		S: Into<ScriptHash>,
 	{
-		self.base_uris = base_uris.into().split(',').map(String::from).collect();
+		let mut utxos = self
+			.post::<_, _, Response<Vec<Unspent>>>(
+				// self.uri_of("blockchain.scripthash.listunspent"),
+				"blockchain.scripthash.listunspent",
+				Params::new([scripthash.into()]),
+			)
+			.await?
+			.response
+			.into_iter()
+			.map(|u| u.into())
+			.collect::<Vec<Utxo>>();
 
-		self
+		utxos.sort_by(|a, b| a.value.cmp(&b.value));
+
+		Ok(utxos)
 	}
 
-	pub fn build(self) -> Result<ElectrumX> {
-		Ok(ElectrumX {
-			client: ReqwestClientBuilder::new().timeout(Duration::from_secs(30)).build()?,
-			network: self.network,
-			base_uris: self.base_uris,
-			max_retries: 3, // 设置默认的重试次数
-			last_successful_uri_index: Mutex::new(0),
-		})
+	/// Like [`Api::get_unspent_address`] but from every base URI at once, their lists settled by
+	/// [`Config::reconciliation`].
+	///
+	/// The base URIs which fail are left out, this only fails if all of them do.
+	async fn get_unspent_address_reconciled<S>(&self, address: S) -> Result<Vec<Utxo>>
+	where
+		S: AsRef<str>,
+	{
+		let scripthash = util::address2scripthash(&self.address_of(address)?)?;
+		let method = "blockchain.scripthash.listunspent";
+		let rs = future::join_all(self.base_uri_for(method).iter().map(|u| {
+			let params = Params::new([scripthash.clone()]);
+
+			async move { self.post_to::<_, _, Response<Vec<Unspent>>>(u, method, params).await }
+		}))
+		.await;
+		let mut lists = Vec::new();
+		let mut last_error = None;
+
+		for r in rs {
+			match r {
+				Ok(r) => lists.push(r.response.into_iter().map(Into::into).collect()),
+				Err(e) => last_error = Some(e),
+			}
+		}
+
+		if let Some(e) = last_error.filter(|_| lists.is_empty()) {
+			return Err(e);
+		}
+
+		let mut utxos = self.reconciliation().reconcile(lists);
+
+		utxos.sort_by_key(|u| u.value);
+
+		Ok(utxos)
 	}
-}
-impl Default for ElectrumXBuilder {
-	fn default() -> Self {
-		Self { network: Network::Bitcoin, base_uris: vec!["https://ep.atomicals.xyz/proxy".into()] }
+
+	/// Like [`Api::get_unspent_scripthash`] but the entries are neither sorted nor parsed upfront.
+	///
+	/// Each UTXO is only parsed once it's polled, so the caller can stop early.
+	async fn get_unspent_scripthash_stream<S>(
+		&self,
+		scripthash: S,
+	) -> Result<impl Stream<Item = Result<Utxo>>>
+	where
+		S: Into<ScriptHash>,
+	{
+		Ok(stream::iter(
+			self.post::<_, _, Response<Vec<serde_json::Value>>>(
+				"blockchain.scripthash.listunspent",
+				Params::new([scripthash.into()]),
+			)
+			.await?
+			.response,
+		)
+		.map(|v| Ok(serde_json::from_value::<Unspent>(v)?.into())))
+	}
+
+	async fn get_balance_address<S>(&self, address: S) -> Result<Balance>
+	where
+		S: AsRef<str>,
+	{
+		self.get_balance_scripthash(util::address2scripthash(&self.address_of(address)?)?).await
+	}
+
+	async fn get_balance_scripthash<S>(&self, scripthash: S) -> Result<Balance>
+	where
+		S: Into<ScriptHash>,
+	{
+		Ok(self
+			.post::<_, _, Response<Balance>>(
+				"blockchain.scripthash.get_balance",
+				Params::new([scripthash.into()]),
+			)
+			.await?
+			.response)
+	}
+
+	async fn get_mempool_address<S>(&self, address: S) -> Result<Vec<MempoolTx>>
+	where
+		S: AsRef<str>,
+	{
+		self.get_mempool_scripthash(util::address2scripthash(&self.address_of(address)?)?).await
+	}
+
+	async fn get_mempool_scripthash<S>(&self, scripthash: S) -> Result<Vec<MempoolTx>>
+	where
+		S: Into<ScriptHash>,
+	{
+		Ok(self
+			.post::<_, _, Response<Vec<MempoolTx>>>(
+				"blockchain.scripthash.get_mempool",
+				Params::new([scripthash.into()]),
+			)
+			.await?
+			.response)
+	}
+
+	async fn get_block_height(&self) -> Result<u64> {
+		Ok(self
+			.post::<_, _, Response<Header>>("blockchain.headers.subscribe", Params::empty())
+			.await?
+			.response
+			.height)
+	}
+
+	/// The minimum fee rate the backend node relays.
+	async fn relay_fee(&self) -> Result<FeeRate> {
+		Ok(FeeRate::from_btc_per_kvb(
+			self.post::<_, _, Response<f64>>("blockchain.relayfee", Params::empty())
+				.await?
+				.response,
+		))
+	}
+
+	/// The height the atomicals index has caught up with, it may lag the chain tip.
+	async fn index_height(&self) -> Result<u64> {
+		Ok(self
+			.post::<_, _, Response<GlobalResponse>>(
+				"blockchain.atomicals.get_global",
+				Params::empty(),
+			)
+			.await?
+			.response
+			.global
+			.height)
+	}
+
+	/// How many blocks the atomicals index lags the chain tip.
+	///
+	/// A lagging index answers with stale or empty results, so a mint which did land looks
+	/// missing until it catches up.
+	async fn index_lag(&self) -> Result<u64> {
+		let (tip, index) = future::try_join(self.get_block_height(), self.index_height()).await?;
+		let lag = tip.saturating_sub(index);
+
+		if lag > MAX_INDEX_LAG {
+			tracing::warn!(tip, index, lag, "atomicals index is lagging, lookups may be stale");
+		}
+
+		Ok(lag)
+	}
+
+	/// Wait until the atomicals index lags the chain tip by at most `max_lag` blocks.
+	///
+	/// Call it before concluding that something is not found.
+	async fn wait_for_index(&self, max_lag: u64) -> Result<()> {
+		while self.index_lag().await? > max_lag {
+			self.clock().sleep(self.poll_interval()).await;
+		}
+
+		Ok(())
+	}
+
+	/// Probe every base URI with a cheap request, reporting its block height or why it failed.
+	async fn health(&self) -> Vec<(String, Result<u64>)> {
+		future::join_all(self.base_uris().iter().map(|u| async move {
+			let r = self
+				.post_to::<_, _, Response<Header>>(
+					u,
+					"blockchain.headers.subscribe",
+					Params::empty(),
+				)
+				.await
+				.map(|r| r.response.height);
+
+			(u.to_owned(), r)
+		}))
+		.await
+	}
+
+	async fn get_tx<S>(&self, txid: S) -> Result<Tx>
+	where
+		S: AsRef<str>,
+	{
+		Ok(self
+			.post::<_, _, Response<Tx>>(
+				"blockchain.transaction.get",
+				Params::new((txid.as_ref(), true)),
+			)
+			.await?
+			.response)
+	}
+
+	/// The fee paid by each of the `txids` and its effective fee rate, fetching each of their
+	/// parents once.
+	async fn get_tx_fees<S>(&self, txids: &[S]) -> Result<HashMap<String, (u64, FeeRate)>>
+	where
+		S: AsRef<str>,
+	{
+		let mut parents = HashMap::<String, Tx>::new();
+		let mut fees = HashMap::new();
+
+		for txid in txids {
+			let tx = self.get_tx(txid).await?;
+			let mut input = 0;
+
+			for i in &tx.vin {
+				let (Some(p), Some(vout)) = (&i.txid, i.vout) else {
+					Err(anyhow::anyhow!("transaction {} is a coinbase, it pays no fee", tx.txid))?
+				};
+
+				if !parents.contains_key(p) {
+					parents.insert(p.to_owned(), self.get_tx(p).await?);
+				}
+
+				input += parents[p]
+					.vout
+					.iter()
+					.find(|o| o.n == vout)
+					.ok_or_else(|| ElectrumXError::NotFound(format!("{p}:{vout}")))?
+					.sats();
+			}
+
+			let fee = input.checked_sub(tx.vout.iter().map(Vout::sats).sum()).ok_or_else(|| {
+				anyhow::anyhow!("transaction {} spends more than it has", tx.txid)
+			})?;
+
+			fees.insert(
+				txid.as_ref().to_owned(),
+				(fee, FeeRate::from_sat_per_vb(fee as f64 / tx.vsize as f64)),
+			);
+		}
+
+		Ok(fees)
+	}
+
+	/// Confirmations of each of the `txids`, queried concurrently within the client's concurrency
+	/// limit.
+	async fn get_confirmations<S>(&self, txids: &[S]) -> Result<HashMap<String, u64>>
+	where
+		S: AsRef<str>,
+	{
+		Ok(future::try_join_all(txids.iter().map(|t| async move {
+			Result::<_, Error>::Ok((t.as_ref().to_owned(), self.get_tx(t).await?.confirmations))
+		}))
+		.await?
+		.into_iter()
+		.collect())
+	}
+
+	/// A snapshot of the ticker's mint progress along with the funding address's state.
+	async fn mint_status<S, A>(&self, ticker: S, funding_address: A) -> Result<MintStatus>
+	where
+		S: AsRef<str>,
+		A: AsRef<str>,
+	{
+		let MintStatusLite { atomical_id, open, mint_count, max_mints, remaining_mints, .. } =
+			self.mint_status_lite(ticker).await?;
+
+		Ok(MintStatus {
+			atomical_id,
+			open,
+			mint_count,
+			max_mints,
+			remaining_mints,
+			balance: self.get_balance_address(funding_address.as_ref()).await?,
+			pending: self.get_mempool_address(funding_address.as_ref()).await?,
+		})
+	}
+
+	async fn mint_status_lite<S>(&self, ticker: S) -> Result<MintStatusLite>
+	where
+		S: AsRef<str>,
+	{
+		let atomical_id = self.get_by_ticker(ticker).await?.atomical_id;
+		let ResponseResult { global, result: ft } = self.get_ft_info(&atomical_id).await?;
+		let remaining_mints = ft.remaining_mints();
+		let started = global.map(|g| ft.mint_height <= g.height + 1).unwrap_or(true);
+
+		Ok(MintStatusLite {
+			atomical_id,
+			open: started && remaining_mints > 0,
+			mint_count: ft.dft_info.mint_count,
+			max_mints: ft.max_mints,
+			remaining_mints,
+			remaining_supply: remaining_mints * ft.mint_amount,
+		})
+	}
+
+	/// The mint progress of each of the `tickers`, fetched concurrently.
+	///
+	/// A ticker which can't be fetched is left out with a warning, this only fails if none can.
+	async fn tickers_status(&self, tickers: &[&str]) -> Result<Vec<(String, MintStatusLite)>> {
+		let rs = stream::iter(tickers)
+			.map(|&t| async move { (t, self.mint_status_lite(t).await) })
+			.buffered(MAX_CONCURRENT_LOOKUPS)
+			.collect::<Vec<_>>()
+			.await;
+		let mut statuses = Vec::new();
+		let mut last_error = None;
+
+		for (t, r) in rs {
+			match r {
+				Ok(s) => statuses.push((t.to_owned(), s)),
+				Err(e) => {
+					tracing::warn!("failed to get the status of {t}: {e:#}");
+
+					last_error = Some(e);
+				},
+			}
+		}
+
+		match last_error {
+			Some(e) if statuses.is_empty() => Err(e),
+			_ => Ok(statuses),
+		}
+	}
+
+	/// How many mints are left before the ticker reaches its `max_mints` cap.
+	async fn remaining_mints<S>(&self, ticker: S) -> Result<u64>
+	where
+		S: AsRef<str>,
+	{
+		let atomical_id = self.get_by_ticker(ticker).await?.atomical_id;
+
+		Ok(self.get_ft_info(atomical_id).await?.result.remaining_mints())
+	}
+
+	async fn wait_until_utxo<S>(&self, address: S, satoshis: u64) -> Result<Utxo>
+	where
+		S: AsRef<str>,
+	{
+		Ok(self.wait_until_utxo_of(&[address], satoshis).await?.1)
+	}
+
+	/// Wait until any of the `addresses` holds a clean UTXO of at least `satoshis`.
+	///
+	/// Returns the index of the address which got funded, along with the UTXO.
+	async fn wait_until_utxo_of<S>(&self, addresses: &[S], satoshis: u64) -> Result<(usize, Utxo)>
+	where
+		S: AsRef<str>,
+	{
+		self.wait_until_utxo_matching(addresses, |u| u.atomicals.is_empty() && u.value >= satoshis)
+			.await
+	}
+
+	/// Like [`Api::wait_until_utxo_of`] but with any criteria, e.g. to pick a UTXO holding a
+	/// specific atomical.
+	async fn wait_until_utxo_matching<S, F>(
+		&self,
+		addresses: &[S],
+		predicate: F,
+	) -> Result<(usize, Utxo)>
+	where
+		S: AsRef<str>,
+		F: Fn(&Utxo) -> bool,
+	{
+		self.poll_until_utxo(addresses, 0, predicate).await
+	}
+
+	/// Like [`Api::wait_until_utxo`] for the funds of a mint, which also need the
+	/// [`Config::funding_min_confirmations`].
+	async fn wait_until_funding<S>(&self, address: S, satoshis: u64) -> Result<Utxo>
+	where
+		S: AsRef<str>,
+	{
+		Ok(self
+			.wait_until_funding_matching(&[address], |u| {
+				u.atomicals.is_empty() && u.value >= satoshis
+			})
+			.await?
+			.1)
+	}
+
+	/// Like [`Api::wait_until_utxo_matching`] but with the [`Config::funding_min_confirmations`]
+	/// on top of the `predicate`.
+	async fn wait_until_funding_matching<S, F>(
+		&self,
+		addresses: &[S],
+		predicate: F,
+	) -> Result<(usize, Utxo)>
+	where
+		S: AsRef<str>,
+		F: Fn(&Utxo) -> bool,
+	{
+		self.poll_until_utxo(addresses, self.funding_min_confirmations(), predicate).await
+	}
+
+	// The loop of the waits above.
+	async fn poll_until_utxo<S, F>(
+		&self,
+		addresses: &[S],
+		min_confirmations: u64,
+		predicate: F,
+	) -> Result<(usize, Utxo)>
+	where
+		S: AsRef<str>,
+		F: Fn(&Utxo) -> bool,
+	{
+		loop {
+			if let Some(r) =
+				self.find_utxo_matching(addresses, min_confirmations, &predicate).await?
+			{
+				return Ok(r);
+			}
+
+			self.clock().sleep(self.poll_interval()).await;
+		}
+	}
+
+	/// Like [`Api::wait_until_funding`] but `target` re-estimates the satoshis before each poll,
+	/// e.g. at the current fee rate, so the funds are still enough once they arrive.
+	///
+	/// `target` also returns what the amount was derived from, handed back along with the UTXO.
+	async fn wait_until_funding_repriced<S, F, Fut, T>(
+		&self,
+		address: S,
+		mut target: F,
+	) -> Result<(Utxo, T)>
+	where
+		S: AsRef<str>,
+		F: FnMut() -> Fut,
+		Fut: Future<Output = Result<(u64, T)>>,
+	{
+		let mut last = None;
+
+		loop {
+			let (satoshis, t) = target().await?;
+
+			if let Some(l) = last.filter(|&l| satoshis > l) {
+				tracing::warn!("funding target revised upward from {l} to {satoshis} sats");
+			}
+
+			last = Some(satoshis);
+
+			if let Some((_, u)) = self
+				.find_utxo_matching(&[address.as_ref()], self.funding_min_confirmations(), |u| {
+					u.atomicals.is_empty() && u.value >= satoshis
+				})
+				.await?
+			{
+				return Ok((u, t));
+			}
+
+			self.clock().sleep(self.poll_interval()).await;
+		}
+	}
+
+	// A single poll of `poll_until_utxo`.
+	async fn find_utxo_matching<S, F>(
+		&self,
+		addresses: &[S],
+		min_confirmations: u64,
+		predicate: F,
+	) -> Result<Option<(usize, Utxo)>>
+	where
+		S: AsRef<str>,
+		F: Fn(&Utxo) -> bool,
+	{
+		let tip = if min_confirmations > 0 { self.get_block_height().await? } else { 0 };
+		// Stagger the queries a bit, the concurrency itself is bounded by the client.
+		let unspents = future::join_all(addresses.iter().enumerate().map(|(i, a)| async move {
+			if i != 0 {
+				let delay = i as u64 * 100 + rand::thread_rng().gen_range(0..100);
+
+				self.clock().sleep(Duration::from_millis(delay)).await;
+			}
+
+			self.get_unspent_address(a.as_ref()).await
+		}))
+		.await;
+
+		for (i, us) in unspents.into_iter().enumerate() {
+			for u in us? {
+				if predicate(&u) && u.confirmations(tip) >= min_confirmations {
+					tracing::info!(
+						"Detected Funding UTXO {txid}:{vout}) with value {value} for funding...",
+						txid = u.txid,
+						vout = u.vout,
+						value = u.value
+					);
+
+					return Ok(Some((i, u)));
+				}
+			}
+		}
+
+		tracing::info!(
+			"WAITING for UTXO... UNTIL A MATCHING ONE IS RECEIVED AT {addr}",
+			addr = addresses.iter().map(|a| a.as_ref()).collect::<Vec<_>>().join(", ")
+		);
+
+		Ok(None)
+	}
+
+	/// Wait until the transaction has at least `confirmations` confirmations.
+	///
+	/// Returns the transaction, whose `blockhash` can later be checked with
+	/// [`Api::ensure_confirmed_in`]. If it leaves its block meanwhile, the wait starts over.
+	async fn wait_for_confirmations<S>(&self, txid: S, confirmations: u64) -> Result<Tx>
+	where
+		S: AsRef<str>,
+	{
+		let txid = txid.as_ref();
+		let mut seen_in = None;
+
+		loop {
+			let tx = self.get_tx(txid).await?;
+
+			if let Some(b) = seen_in.take().filter(|b| tx.blockhash.as_ref() != Some(b)) {
+				tracing::warn!(
+					txid,
+					from = b,
+					to = tx.blockhash.as_deref().unwrap_or("mempool"),
+					"reorg detected, waiting for the confirmations again"
+				);
+			}
+
+			if tx.confirmations >= confirmations {
+				return Ok(tx);
+			}
+
+			tracing::info!(
+				"waiting for {txid} to be confirmed, {}/{confirmations} confirmations",
+				tx.confirmations
+			);
+
+			seen_in = tx.blockhash;
+
+			self.clock().sleep(self.poll_interval()).await;
+		}
+	}
+
+	/// Make sure the transaction is still in the block it was confirmed in, e.g. a parent before
+	/// building on top of it.
+	async fn ensure_confirmed_in<S>(&self, txid: S, blockhash: &str) -> Result<()>
+	where
+		S: AsRef<str>,
+	{
+		let tx = self.get_tx(txid.as_ref()).await?;
+
+		if tx.blockhash.as_deref() != Some(blockhash) {
+			Err(ElectrumXError::ReorgDetected {
+				txid: txid.as_ref().into(),
+				blockhash: blockhash.into(),
+			})?;
+		}
+
+		Ok(())
+	}
+
+	/// Send all the `queries` at once, each a `(base_uri, method, params)`, and return the first
+	/// result which parses.
+	///
+	/// For when any source will do, e.g. the block height, or to return as soon as one of the
+	/// redundant sources answers.
+	async fn first_ok<I, B, M, P, R>(&self, queries: I) -> Result<R>
+	where
+		I: IntoIterator<Item = (B, M, P)>,
+		B: AsRef<str>,
+		M: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		let queries = queries
+			.into_iter()
+			.map(|(b, m, p)| Box::pin(async move { self.post_to(b.as_ref(), m, p).await }))
+			.collect::<Vec<_>>();
+
+		if queries.is_empty() {
+			Err(anyhow::anyhow!("no query to send"))?;
+		}
+
+		Ok(future::select_ok(queries).await?.0)
+	}
+
+	/// Make sure the `tx` spends from the configured network, by looking up its first input.
+	///
+	/// Output scripts carry no network, so a mainnet transaction decodes just fine on testnet.
+	/// Its inputs however are unknown there.
+	async fn ensure_network_of(&self, tx: &Transaction) -> Result<()> {
+		let Some(i) = tx.input.first() else { return Ok(()) };
+
+		self.post::<_, _, Response<String>>(
+			"blockchain.transaction.get",
+			Params::new((i.previous_output.txid.to_string(), false)),
+		)
+		.await
+		.map_err(|e| {
+			// Anything else, e.g. unreachable proxies, says nothing about the network.
+			if is_tx_not_found(&e) {
+				e.context(ElectrumXError::NetworkMismatch {
+					network: *self.network(),
+					txid: tx.txid(),
+				})
+			} else {
+				e
+			}
+		})?;
+
+		Ok(())
+	}
+
+	async fn broadcast<S>(&self, tx: S) -> Result<String>
+	where
+		S: AsRef<str>,
+	{
+		self.broadcast_spending(tx, &[]).await
+	}
+
+	/// Like [`Api::broadcast`] but along with the `prevouts` the inputs spend, in order, as the
+	/// signer knows them anyway.
+	///
+	/// The fee rate ceiling and the spend cap then don't fetch the parents to price the inputs.
+	async fn broadcast_spending<S>(&self, tx: S, prevouts: &[TxOut]) -> Result<String>
+	where
+		S: AsRef<str>,
+	{
+		let size = tx.as_ref().len();
+		let limit = self.max_broadcast_size();
+
+		if size > limit {
+			Err(ElectrumXError::TransactionTooLarge { size, limit })?;
+		}
+
+		let decoded = util::tx_from_hex(tx.as_ref())?;
+		let known = decoded
+			.input
+			.iter()
+			.map(|i| i.previous_output)
+			.zip(prevouts.iter().cloned())
+			.collect::<HashMap<_, _>>();
+
+		self.ensure_network_of(&decoded).await?;
+		self.ensure_sane_fee_rate(&decoded, &known).await?;
+		self.charge_spend(&decoded, &known).await?;
+
+		let expected = decoded.txid();
+		let got = self
+			.post::<_, _, Response<String>>(
+				"blockchain.transaction.broadcast",
+				Params::new([tx.as_ref()]),
+			)
+			.await?
+			.response;
+
+		// Don't trust a proxy which mangled the transaction or returned the wrong id.
+		match got.parse::<Txid>() {
+			Ok(txid) if txid == expected => (),
+			_ => Err(ElectrumXError::TxidMismatch { expected, got: got.clone() })?,
+		}
+
+		if let Some(w) = self.broadcast_verify_window() {
+			self.verify_propagation(expected, w).await?;
+		}
+
+		Ok(got)
+	}
+
+	// Refuse the transaction if it would take the session over its spend cap.
+	async fn charge_spend(&self, tx: &Transaction, known: &HashMap<OutPoint, TxOut>) -> Result<()> {
+		let Some(cap) = self.spend_cap() else { return Ok(()) };
+		let mut prevouts = HashMap::new();
+
+		for o in cap.inputs_to_price(tx) {
+			prevouts.insert(o, self.prevout_in(known, o).await?);
+		}
+
+		let amount = cap.charge(tx, &prevouts)?;
+
+		tracing::info!(txid = %tx.txid(), amount, spent = cap.spent(), "charged the spend cap");
+
+		Ok(())
+	}
+
+	// Refuse a fee rate above the ceiling, a fat-fingered fee can't be taken back.
+	async fn ensure_sane_fee_rate(
+		&self,
+		tx: &Transaction,
+		known: &HashMap<OutPoint, TxOut>,
+	) -> Result<()> {
+		let Some(max) = self.max_fee_rate() else { return Ok(()) };
+		let mut inputs = 0;
+
+		for i in &tx.input {
+			inputs += self.prevout_in(known, i.previous_output).await?.value.to_sat();
+		}
+
+		let outputs = tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+		let fee_rate =
+			FeeRate::from_sat_per_vb(inputs.saturating_sub(outputs) as f64 / tx.vsize() as f64);
+
+		if fee_rate > max {
+			Err(ElectrumXError::FeeRateAbsurd { txid: tx.txid(), fee_rate, max })?;
+		}
+
+		Ok(())
+	}
+
+	// The `known` one, or else the one of the parent.
+	async fn prevout_in(
+		&self,
+		known: &HashMap<OutPoint, TxOut>,
+		outpoint: OutPoint,
+	) -> Result<TxOut> {
+		match known.get(&outpoint) {
+			Some(o) => Ok(o.clone()),
+			None => self.prevout_of(outpoint).await,
+		}
+	}
+
+	async fn prevout_of(&self, outpoint: OutPoint) -> Result<TxOut> {
+		let parent = self.get_tx(outpoint.txid.to_string()).await?;
+		let v = parent
+			.vout
+			.iter()
+			.find(|v| v.n == outpoint.vout)
+			.ok_or_else(|| ElectrumXError::NotFound(format!("previous output {outpoint}")))?;
+
+		Ok(TxOut {
+			value: Amount::from_sat(v.sats()),
+			script_pubkey: ScriptBuf::from_hex(&v.script_pubkey.hex)?,
+		})
+	}
+
+	/// Poll the backends other than the broadcasting one until any of them sees the `txid`.
+	///
+	/// Catches proxies which claim a success for a broadcast they never relayed.
+	async fn verify_propagation(&self, txid: Txid, window: Duration) -> Result<()> {
+		let method = "blockchain.transaction.get";
+		let broadcaster = self.last_base_uri_of("blockchain.transaction.broadcast");
+		let base_uris = self.base_uri_for(method);
+		let mut witnesses =
+			base_uris.iter().filter(|u| Some(*u) != broadcaster.as_ref()).collect::<Vec<_>>();
+
+		// Still better than nothing.
+		if witnesses.is_empty() {
+			witnesses = base_uris.iter().collect();
+		}
+
+		let deadline = self.clock().now() + window;
+		let params = (txid.to_string(), false);
+
+		loop {
+			if self
+				.first_ok::<_, _, _, _, Response<String>>(
+					witnesses.iter().map(|u| (u, method, Params::new(&params))),
+				)
+				.await
+				.is_ok()
+			{
+				return Ok(());
+			}
+			if self.clock().now() >= deadline {
+				Err(ElectrumXError::BroadcastUnconfirmed { txid, window })?;
+			}
+
+			self.clock().sleep(Duration::from_secs(1)).await;
+		}
+	}
+
+	/// Broadcast the PSBTs signed by an external wallet, e.g. ones from
+	/// [`util::build_unsigned_psbt`], returning the result of each in order.
+	///
+	/// Inputs which were signed through the key path but not finalized are finalized here. The
+	/// transactions go out through [`Api::broadcast_stream`], a failure doesn't stop the others.
+	async fn broadcast_signed_psbts(&self, psbts: Vec<Psbt>) -> Result<Vec<Result<Txid>>> {
+		let mut txs = Vec::new();
+
+		for (i, mut psbt) in psbts.into_iter().enumerate() {
+			for (j, input) in psbt.inputs.iter_mut().enumerate() {
+				if input.final_script_witness.is_some() {
+					continue;
+				}
+
+				let Some(sig) = input.tap_key_sig else {
+					Err(anyhow::anyhow!("input {j} of the PSBT {i} is not signed"))?
+				};
+
+				input.final_script_witness = Some(Witness::from_slice(&[sig.to_vec()]));
+			}
+
+			// Also refuses an absurd fee, which the signer might not have checked.
+			txs.push(encode::serialize_hex(&psbt.extract_tx()?));
+		}
+
+		let mut rs = self.broadcast_stream(&txs).collect::<Vec<_>>().await;
+
+		rs.sort_by_key(|(i, _)| *i);
+
+		Ok(rs.into_iter().map(|(_, r)| r).collect())
+	}
+
+	/// Broadcast the `tx` to every base URI at once, for the widest propagation.
+	///
+	/// Unlike [`Api::broadcast`], this doesn't stop at the first success and reports the result
+	/// of each base URI. A backend which already knows the transaction counts as a success.
+	async fn try_broadcast_all<S>(&self, tx: S) -> Result<Vec<(String, Result<Txid>)>>
+	where
+		S: AsRef<str>,
+	{
+		let tx = tx.as_ref();
+		let size = tx.len();
+		let limit = self.max_broadcast_size();
+
+		if size > limit {
+			Err(ElectrumXError::TransactionTooLarge { size, limit })?;
+		}
+
+		let decoded = util::tx_from_hex(tx)?;
+
+		self.ensure_network_of(&decoded).await?;
+		self.ensure_sane_fee_rate(&decoded, &HashMap::new()).await?;
+		self.charge_spend(&decoded, &HashMap::new()).await?;
+
+		let txid = decoded.txid();
+		let method = "blockchain.transaction.broadcast";
+
+		Ok(future::join_all(self.base_uri_for(method).iter().map(|u| async move {
+			let r = match self.post_to::<_, _, Response<String>>(u, method, Params::new([tx])).await
+			{
+				Ok(r) => r.response.parse().map_err(Into::into),
+				Err(e) if is_already_known(&e) => Ok(txid),
+				Err(e) => Err(e),
+			};
+
+			(u.to_owned(), r)
+		}))
+		.await)
+	}
+
+	/// Broadcast each of the `txs`, yielding its index and result as soon as it completes.
+	///
+	/// At most [`Config::max_concurrent_broadcasts`] are in flight, still paced by the client. A
+	/// failure doesn't stop the others, so the caller can react to it right away.
+	fn broadcast_stream<I, S>(&self, txs: I) -> impl Stream<Item = (usize, Result<Txid>)>
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		stream::iter(txs.into_iter().enumerate())
+			.map(move |(i, tx)| async move {
+				(i, self.broadcast(tx).await.and_then(|txid| Ok(txid.parse()?)))
+			})
+			.buffer_unordered(self.max_concurrent_broadcasts())
+	}
+}
+impl<T> Api for T where T: Config + Http {}
+
+/// Whether the broadcast failed because the transaction is already known to the node.
+pub fn is_already_known(e: &Error) -> bool {
+	let e = format!("{e:#}");
+
+	["txn-already-known", "txn-already-in-mempool", "already in block chain"]
+		.iter()
+		.any(|m| e.contains(m))
+}
+
+// Whether the node doesn't know the looked up transaction.
+fn is_tx_not_found(e: &Error) -> bool {
+	let e = format!("{e:#}");
+
+	["No such mempool or blockchain transaction", "transaction not found"]
+		.iter()
+		.any(|m| e.contains(m))
+}
+
+/// Whether the request failed on the way, rather than being refused, so retrying might help.
+pub fn is_transient(e: &Error) -> bool {
+	e.chain().any(|e| {
+		e.is::<reqwest::Error>()
+			|| matches!(
+				e.downcast_ref::<ElectrumXError>(),
+				Some(ElectrumXError::AllUrisExhausted { cause: Exhaustion::Unreachable, .. })
+			)
+	})
+}
+
+/// Whether the node definitely refused the request, unlike a transient or an unknown failure
+/// after which it might have gone through.
+pub fn is_rejected(e: &Error) -> bool {
+	!is_already_known(e)
+		&& e.downcast_ref::<ElectrumXError>().is_some_and(ElectrumXError::is_rejection)
+}
+
+#[derive(Debug)]
+pub struct ElectrumX {
+	// One per base URI, so reconnecting to one of them keeps the pools of the others.
+	clients: RwLock<HashMap<String, ReqwestClient>>,
+	client_options: ClientOptions,
+	pub network: Network,
+	pub base_uris: Vec<String>,
+	pub max_retries: usize,
+	// Stick to the first base URI and return the first error as is.
+	pub failover: bool,
+	pub strategy: Strategy,
+	pub require_healthy: usize,
+	pub max_broadcast_size: usize,
+	pub broadcast_verify_window: Option<Duration>,
+	pub funding_min_confirmations: u64,
+	pub poll_interval: Duration,
+	pub clock: Arc<dyn Clock>,
+	pub backoff: Arc<dyn BackoffPolicy>,
+	pub method_aliases: HashMap<String, String>,
+	pub method_timeouts: HashMap<String, Duration>,
+	pub retry_on_empty: HashMap<String, usize>,
+	pub request_interceptor: Option<RequestInterceptor>,
+	pub response_validators: HashMap<String, ResponseValidator>,
+	pub spend_cap: Option<SpendCap>,
+	pub max_fee_rate: Option<FeeRate>,
+	pub reconciliation: Reconciliation,
+	recorder: Option<Mutex<Vec<Exchange>>>,
+	// Bounds the number of in-flight requests across all the callers.
+	pub semaphore: Semaphore,
+	// Bounds the in-flight broadcasts on top, proxies are stricter on them.
+	pub broadcast_semaphore: Semaphore,
+	pub max_concurrent_broadcasts: usize,
+	pub broadcast_interval: Duration,
+	// When the next broadcast may be sent.
+	next_broadcast_at: Mutex<Option<Instant>>,
+	last_successful_uri_index: Mutex<usize>,
+	connect_failures: Mutex<HashMap<String, usize>>,
+	race_penalties: Mutex<HashMap<String, u32>>,
+	rate_limits: Mutex<HashMap<String, RateLimit>>,
+	last_base_uris: Mutex<HashMap<String, String>>,
+}
+impl ElectrumX {
+	/// A handle pinning the requests sent through it to the first base URI which succeeds.
+	///
+	/// Useful for a commit and reveal sequence, so the reveal goes to the backend which has
+	/// already seen the commit. The other callers sharing this client are not affected.
+	pub fn with_sticky_uri(&self) -> StickyUri<'_> {
+		StickyUri { client: self, uri: Default::default() }
+	}
+
+	/// Fail unless at least [`ElectrumXBuilder::require_healthy`] base URIs pass the
+	/// [`Api::health`] probe.
+	///
+	/// Call it before a long run, rather than discovering midway that most proxies are down.
+	pub async fn ensure_ready(&self) -> Result<()> {
+		let mut healthy = 0;
+
+		for (u, r) in self.health().await {
+			match r {
+				Ok(height) => {
+					tracing::info!(base_uri = %u, height, "healthy");
+
+					healthy += 1;
+				},
+				Err(e) => tracing::warn!(base_uri = %u, error = %e, "unhealthy"),
+			}
+		}
+
+		if healthy < self.require_healthy {
+			Err(ElectrumXError::NotEnoughHealthy { healthy, required: self.require_healthy })?;
+		}
+
+		Ok(())
+	}
+
+	/// The HTTP client of the `base_uri`, built on first use for one outside the base URIs.
+	pub fn client_of(&self, base_uri: &str) -> Result<ReqwestClient> {
+		if let Some(c) = self.clients.read().unwrap().get(base_uri) {
+			return Ok(c.clone());
+		}
+
+		let c = self.client_options.build()?;
+
+		Ok(self.clients.write().unwrap().entry(base_uri.into()).or_insert(c).clone())
+	}
+
+	// With the method's own timeout, if it overrides the default one.
+	fn request_of(
+		&self,
+		base_uri: &str,
+		uri: &str,
+		method: &str,
+		body: &Value,
+	) -> Result<RequestBuilder> {
+		let r = self.client_of(base_uri)?.post(uri).json(body);
+
+		Ok(match self.method_timeouts.get(method) {
+			Some(&t) => r.timeout(t),
+			None => r,
+		})
+	}
+
+	// The method name the proxy actually serves, see [`ElectrumXBuilder::method_alias`].
+	fn method_of<'a>(&'a self, method: &'a str) -> &'a str {
+		self.method_aliases.get(method).map(|m| m.as_str()).unwrap_or(method)
+	}
+
+	// Serialize the params into the request body, then let the interceptor adjust it.
+	fn body_of<P>(&self, params: P) -> Result<Value>
+	where
+		P: Serialize,
+	{
+		let mut body = serde_json::to_value(params)?;
+
+		if let Some(i) = &self.request_interceptor {
+			(i.0)(&mut body);
+		}
+
+		Ok(body)
+	}
+
+	// Let the validator of the method reject a response which parsed fine but makes no sense.
+	fn validate(&self, method: &str, resp_text: &str) -> Result<()> {
+		let Some(v) = self.response_validators.get(method) else { return Ok(()) };
+
+		(v.0)(&serde_json::from_str(resp_text)?)
+			.map_err(|e| e.context(format!("response of {method} failed validation")))
+	}
+
+	/// Snapshot the configuration and the traffic recorded so far, along with the `outcome` of
+	/// the run.
+	///
+	/// The traffic is only recorded if [`ElectrumXBuilder::record`] was set.
+	pub fn scenario(&self, outcome: Result<(), String>) -> Scenario {
+		Scenario::new(
+			self.network,
+			self.base_uris.clone(),
+			self.recorder.as_ref().map(|r| r.lock().unwrap().clone()).unwrap_or_default(),
+			outcome,
+		)
+	}
+
+	/// Close the client, logging a summary of how each base URI fared.
+	///
+	/// Dropping the client without it is fine too, anything it spawned is then cancelled along
+	/// with the runtime and the summary is lost. A closed client is not handed out by the
+	/// [`registry::ClientRegistry`] anymore.
+	pub async fn shutdown(&self) {
+		let connect_failures = self.connect_failures.lock().unwrap();
+		let race_penalties = self.race_penalties.lock().unwrap();
+		let rate_limits = self.rate_limits.lock().unwrap();
+
+		for u in &self.base_uris {
+			tracing::info!(
+				base_uri = %u,
+				connect_failures = connect_failures.get(u).copied().unwrap_or_default(),
+				race_penalty = race_penalties.get(u).copied().unwrap_or_default(),
+				rate_limit_remaining = rate_limits.get(u).map(|l| l.remaining),
+				"final stats"
+			);
+		}
+
+		if let Some(r) = &self.recorder {
+			tracing::info!(exchanges = r.lock().unwrap().len(), "recorded traffic");
+		}
+
+		self.semaphore.close();
+		self.broadcast_semaphore.close();
+	}
+
+	fn is_closed(&self) -> bool {
+		self.semaphore.is_closed()
+	}
+
+	// Keep the response which was handed back to the caller, for the scenario.
+	fn record(&self, method: &str, request: &Value, response: String) {
+		if let Some(r) = &self.recorder {
+			r.lock().unwrap().push(Exchange {
+				method: method.into(),
+				request: request.clone(),
+				response,
+			});
+		}
+	}
+
+	fn track_rate_limit(&self, base_uri: &str, headers: &HeaderMap) {
+		if let Some(l) = RateLimit::from_headers(headers, self.clock.now()) {
+			self.rate_limits.lock().unwrap().insert(base_uri.into(), l);
+		}
+	}
+
+	// How long until the base URI accepts requests again, if it ran out of its quota.
+	fn rate_limited_for(&self, base_uri: &str) -> Option<Duration> {
+		let l = *self.rate_limits.lock().unwrap().get(base_uri)?;
+		let now = self.clock.now();
+
+		(l.remaining == 0 && l.reset_at > now).then(|| (l.reset_at - now).min(MAX_RATE_LIMIT_WAIT))
+	}
+
+	// Pace the broadcasts, other methods pass right through.
+	async fn throttle(&self, method: &str) -> Result<Option<SemaphorePermit<'_>>> {
+		if method != "blockchain.transaction.broadcast" {
+			return Ok(None);
+		}
+
+		let permit = self.broadcast_semaphore.acquire().await?;
+		// Reserve a slot, so concurrent broadcasts are spaced out as well.
+		let wait = {
+			let mut next = self.next_broadcast_at.lock().unwrap();
+			let now = self.clock.now();
+			let at = next.map_or(now, |n| n.max(now));
+
+			*next = Some(at + self.broadcast_interval);
+
+			at - now
+		};
+
+		if !wait.is_zero() {
+			self.clock.sleep(wait).await;
+		}
+
+		Ok(Some(permit))
+	}
+
+	// Pin the base URI which just succeeded, if the request came through a sticky handle.
+	fn stick_to(&self, sticky: Option<&Mutex<Option<String>>>, method: &str, base_uri: &str) {
+		self.last_base_uris.lock().unwrap().insert(method.into(), base_uri.into());
+
+		if let Some(s) = sticky {
+			s.lock().unwrap().get_or_insert_with(|| base_uri.into());
+		}
+	}
+
+	// Send to all the base URIs at once and take the first response which parses.
+	async fn race<R>(
+		&self,
+		sticky: Option<&Mutex<Option<String>>>,
+		base_uris: &[String],
+		endpoint: &str,
+		body: &Value,
+	) -> Result<R>
+	where
+		R: DeserializeOwned,
+	{
+		let penalties = self.race_penalties.lock().unwrap().clone();
+		let mut racers = base_uris
+			.iter()
+			.enumerate()
+			.map(|(i, u)| {
+				let penalty = penalties.get(u).copied().unwrap_or_default().min(MAX_RACE_PENALTY);
+
+				async move {
+					// So a base URI which is fast but returns junk can't keep winning the race.
+					if penalty > 0 {
+						self.clock.sleep(RACE_PENALTY * penalty).await;
+					}
+
+					let uri = uri_of(u, self.method_of(endpoint));
+					let started = Instant::now();
+					let r = async {
+						let _permit = self.semaphore.acquire().await?;
+						let response = self.request_of(u, &uri, endpoint, body)?.send().await?;
+
+						self.track_rate_limit(u, response.headers());
+
+						if response.status().is_redirection() {
+							Err(redirected(&uri, &response))?;
+						}
+
+						let resp_text = response.text().await?;
+
+						let parsed = serde_json::from_str::<R>(&resp_text)
+							.map_err(|e| error_of(&resp_text, e))
+							.and_then(|r| self.validate(endpoint, &resp_text).map(|_| r));
+
+						Ok::<_, Error>((resp_text, parsed))
+					}
+					.await;
+
+					(i, r, started.elapsed().as_millis() as u64)
+				}
+			})
+			.collect::<FuturesUnordered<_>>();
+		let mut errors = Vec::new();
+
+		while let Some((i, r, latency_ms)) = racers.next().await {
+			let base_uri = &base_uris[i];
+
+			match r {
+				Ok((resp_text, Ok(parsed))) => {
+					self.record(endpoint, body, resp_text);
+					self.race_penalties.lock().unwrap().remove(base_uri);
+					*self.last_successful_uri_index.lock().unwrap() = i;
+					self.stick_to(sticky, endpoint, base_uri);
+
+					return Ok(parsed);
+				},
+				Ok((resp_text, Err(e))) => {
+					match e.downcast_ref::<ElectrumXError>() {
+						// There is no point to wait for the others.
+						Some(ElectrumXError::Atomicals(..)) => {
+							self.record(endpoint, body, resp_text);
+
+							return Err(e);
+						},
+						// A well formed error response is not junk.
+						Some(_) => (),
+						None => {
+							*self
+								.race_penalties
+								.lock()
+								.unwrap()
+								.entry(base_uri.into())
+								.or_default() += 1;
+						},
+					}
+
+					tracing::info!(
+						base_uri = %base_uri,
+						method = endpoint,
+						latency_ms,
+						error = %e,
+						"race parse response failed"
+					);
+
+					errors.push((base_uri.to_owned(), e));
+				},
+				Err(e) => {
+					tracing::info!(
+						base_uri = %base_uri,
+						method = endpoint,
+						latency_ms,
+						error = %e,
+						"race request failed"
+					);
+
+					errors.push((base_uri.to_owned(), e));
+				},
+			}
+		}
+
+		Err(exhausted(errors))
+	}
+
+	// With `sticky`, stick to the base URI it pinned, or pin the first one which succeeds.
+	async fn post_pinned<U, P, R>(
+		&self,
+		sticky: Option<&Mutex<Option<String>>>,
+		endpoint: U,
+		params: P,
+	) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		let _broadcast_permit = self.throttle(endpoint.as_ref()).await?;
+		let base_uris = self.base_uri_for(endpoint.as_ref());
+		let body = self.body_of(params)?;
+
+		if base_uris.is_empty() {
+			Err(anyhow::anyhow!("no base URI available for {}", endpoint.as_ref()))?;
+		}
+
+		let base_uris = if self.failover { base_uris } else { &base_uris[..1] };
+		// Where `base_uris` starts in the whole list, which the rotation indexes into.
+		let (offset, base_uris) = match sticky
+			.and_then(|s| s.lock().unwrap().clone())
+			.and_then(|p| base_uris.iter().position(|u| *u == p))
+		{
+			Some(i) => (i, &base_uris[i..=i]),
+			None => (0, base_uris),
+		};
+
+		if self.strategy == Strategy::Race && base_uris.len() > 1 {
+			return self.race(sticky, base_uris, endpoint.as_ref(), &body).await;
+		}
+
+		let mut attempts = 0;
+		let mut uri_change_count = 0;
+		let max_empty_retries =
+			self.retry_on_empty.get(endpoint.as_ref()).copied().unwrap_or_default();
+		let mut empty_retries = max_empty_retries;
+		// The last error of each base URI.
+		let mut errors = base_uris.iter().map(|_| None).collect::<Vec<_>>();
+
+		// 从 Mutex 中获取当前的 URI 索引
+		let mut uri_index = {
+			let lock = self.last_successful_uri_index.lock().unwrap();
+			*lock % base_uris.len()
+		};
+
+		loop {
+			if let Some(wait) = self.rate_limited_for(&base_uris[uri_index]) {
+				if self.failover && uri_change_count < base_uris.len() - 1 {
+					tracing::info!(
+						base_uri = %base_uris[uri_index],
+						method = endpoint.as_ref(),
+						"rate limited, switching URI"
+					);
+
+					uri_index = (uri_index + 1) % base_uris.len();
+					uri_change_count += 1;
+					attempts = 0;
+
+					continue;
+				}
+
+				tracing::info!(
+					base_uri = %base_uris[uri_index],
+					method = endpoint.as_ref(),
+					wait_ms = wait.as_millis() as u64,
+					"rate limited, waiting"
+				);
+				self.clock.sleep(wait).await;
+			}
+
+			let uri = uri_of(&base_uris[uri_index], self.method_of(endpoint.as_ref()));
+			// Per attempt, so the requests backing off don't hold up the others while sleeping.
+			let permit = self.semaphore.acquire().await?;
+			let started = Instant::now();
+
+			match self
+				.request_of(&base_uris[uri_index], &uri, endpoint.as_ref(), &body)?
+				.send()
+				.await
+			{
+				Ok(response) if response.status().is_redirection() => {
+					let e = redirected(&uri, &response);
+
+					drop(permit);
+
+					if !self.failover {
+						return Err(e.into());
+					}
+
+					tracing::warn!(
+						uri = %uri,
+						method = endpoint.as_ref(),
+						attempt = attempts,
+						error = %e,
+						"redirect not followed"
+					);
+
+					errors[uri_index] = Some(e.into());
+					// Retrying won't change the answer.
+					attempts = self.max_retries;
+				},
+				Ok(response) => {
+					self.connect_failures.lock().unwrap().remove(&base_uris[uri_index]);
+					self.track_rate_limit(&base_uris[uri_index], response.headers());
+
+					let resp_text = response.text().await?;
+					let latency_ms = started.elapsed().as_millis() as u64;
+
+					drop(permit);
+					tracing::debug!(
+						uri = %uri,
+						method = endpoint.as_ref(),
+						attempt = attempts,
+						latency_ms,
+						"request finished"
+					);
+
+					match serde_json::from_str(&resp_text)
+						.map_err(|e| error_of(&resp_text, e))
+						.and_then(|r| self.validate(endpoint.as_ref(), &resp_text).map(|_| r))
+					{
+						Ok(_) if empty_retries > 0 && is_empty_response(&resp_text) => {
+							empty_retries -= 1;
+
+							tracing::info!(
+								uri = %uri,
+								method = endpoint.as_ref(),
+								attempt = attempts,
+								latency_ms,
+								"empty result, retrying"
+							);
+							self.clock
+								.sleep(self.backoff.delay(max_empty_retries - empty_retries))
+								.await;
+
+							continue;
+						},
+						Ok(parsed) => {
+							// 在单独的作用域中更新 last_successful_uri_index
+							{
+								let mut lock = self.last_successful_uri_index.lock().unwrap();
+								*lock = offset + uri_index;
+							}
+							self.stick_to(sticky, endpoint.as_ref(), &base_uris[uri_index]);
+							self.record(endpoint.as_ref(), &body, resp_text);
+
+							return Ok(parsed);
+						},
+						Err(e) => {
+							// There is no point to retry on these.
+							if !self.failover
+								|| matches!(
+									e.downcast_ref::<ElectrumXError>(),
+									Some(ElectrumXError::Atomicals(..))
+								) {
+								self.record(endpoint.as_ref(), &body, resp_text);
+
+								return Err(e);
+							}
+
+							tracing::info!(
+								uri = %uri,
+								method = endpoint.as_ref(),
+								attempt = attempts,
+								latency_ms,
+								error = %e,
+								"parse response failed"
+							);
+							// 解析失败时继续尝试
+
+							errors[uri_index] = Some(e);
+						},
+					}
+				},
+				Err(e) => {
+					drop(permit);
+
+					if !self.failover {
+						return Err(e.into());
+					}
+
+					tracing::info!(
+						uri = %uri,
+						method = endpoint.as_ref(),
+						attempt = attempts,
+						latency_ms = started.elapsed().as_millis() as u64,
+						error = %e,
+						"request failed"
+					);
+					// 请求失败时继续尝试
+
+					if is_stale_connection(&e) {
+						self.reconnect_on_failures(&base_uris[uri_index])?;
+					}
+
+					errors[uri_index] = Some(e.into());
+				},
+			}
+
+			if attempts >= self.max_retries {
+				if uri_change_count < base_uris.len() - 1 {
+					uri_index = (uri_index + 1) % base_uris.len();
+					uri_change_count += 1;
+					tracing::info!(
+						base_uri = %base_uris[uri_index],
+						method = endpoint.as_ref(),
+						"switching URI"
+					);
+					attempts = 0; // 重置尝试次数
+				} else {
+					return Err(exhausted(
+						base_uris.iter().cloned().zip(errors).filter_map(|(u, e)| Some((u, e?))),
+					));
+				}
+			} else {
+				attempts += 1;
+
+				let delay = self.backoff.delay(attempts);
+
+				tracing::info!(
+					method = endpoint.as_ref(),
+					attempt = attempts,
+					delay_ms = delay.as_millis() as u64,
+					"retrying"
+				);
+				self.clock.sleep(delay).await;
+			}
+		}
+	}
+
+	// Drop the connection pool of the base URI, so its next request resolves and connects again.
+	fn reconnect_on_failures(&self, base_uri: &str) -> Result<()> {
+		let mut fs = self.connect_failures.lock().unwrap();
+		let f = fs.entry(base_uri.into()).or_default();
+
+		*f += 1;
+
+		if *f >= MAX_CONNECT_FAILURES {
+			tracing::info!(base_uri, connect_failures = *f, "reconnecting");
+
+			*f = 0;
+			self.clients.write().unwrap().insert(base_uri.into(), self.client_options.build()?);
+		}
+
+		Ok(())
+	}
+}
+impl Config for ElectrumX {
+	fn network(&self) -> &Network {
+		&self.network
+	}
+
+	fn base_uris(&self) -> &[String] {
+		&self.base_uris
+	}
+
+	fn clock(&self) -> &dyn Clock {
+		&*self.clock
+	}
+
+	fn poll_interval(&self) -> Duration {
+		self.poll_interval
+	}
+
+	fn funding_min_confirmations(&self) -> u64 {
+		self.funding_min_confirmations
+	}
+
+	fn max_broadcast_size(&self) -> usize {
+		self.max_broadcast_size
+	}
+
+	fn max_concurrent_broadcasts(&self) -> usize {
+		self.max_concurrent_broadcasts
+	}
+
+	fn broadcast_verify_window(&self) -> Option<Duration> {
+		self.broadcast_verify_window
+	}
+
+	fn last_base_uri_of(&self, method: &str) -> Option<String> {
+		self.last_base_uris.lock().unwrap().get(method).cloned()
+	}
+
+	fn spend_cap(&self) -> Option<&SpendCap> {
+		self.spend_cap.as_ref()
+	}
+
+	fn reconciliation(&self) -> Reconciliation {
+		self.reconciliation
+	}
+
+	fn max_fee_rate(&self) -> Option<FeeRate> {
+		self.max_fee_rate
+	}
+}
+impl Http for ElectrumX {
+	async fn post<U, P, R>(&self, endpoint: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		self.post_pinned(None, endpoint, params).await
+	}
+
+	async fn post_to<U, P, R>(&self, base_uri: &str, endpoint: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		let _broadcast_permit = self.throttle(endpoint.as_ref()).await?;
+		let uri = uri_of(base_uri, self.method_of(endpoint.as_ref()));
+		let body = self.body_of(params)?;
+
+		if let Some(wait) = self.rate_limited_for(base_uri) {
+			tracing::info!(
+				base_uri,
+				method = endpoint.as_ref(),
+				wait_ms = wait.as_millis() as u64,
+				"rate limited, waiting"
+			);
+			self.clock.sleep(wait).await;
+		}
+
+		let _permit = self.semaphore.acquire().await?;
+		let started = Instant::now();
+		let response = self.request_of(base_uri, &uri, endpoint.as_ref(), &body)?.send().await?;
+
+		self.track_rate_limit(base_uri, response.headers());
+
+		if response.status().is_redirection() {
+			Err(redirected(&uri, &response))?;
+		}
+
+		let resp_text = response.text().await?;
+
+		tracing::debug!(
+			uri = %uri,
+			method = endpoint.as_ref(),
+			latency_ms = started.elapsed().as_millis() as u64,
+			"request finished"
+		);
+
+		let parsed = serde_json::from_str(&resp_text)
+			.map_err(|e| {
+				error_of(&resp_text, e)
+					.context(format!("request {uri} parse response failed, response: {resp_text}"))
+			})
+			.and_then(|r| self.validate(endpoint.as_ref(), &resp_text).map(|_| r));
+
+		if parsed.is_ok() {
+			self.last_base_uris.lock().unwrap().insert(endpoint.as_ref().into(), base_uri.into());
+		}
+
+		self.record(endpoint.as_ref(), &body, resp_text);
+
+		parsed
+	}
+}
+
+// Join with exactly one `/`, base URIs are normalized at build time but could be set directly.
+fn uri_of(base_uri: &str, endpoint: &str) -> String {
+	format!("{}/{}", base_uri.trim_end_matches('/'), endpoint.trim_start_matches('/'))
+}
+
+// A redirect which wasn't followed, because of the limit or the method change it requires, is
+// otherwise a confusing parse failure.
+fn redirected(uri: &str, response: &ReqwestResponse) -> ElectrumXError {
+	ElectrumXError::Redirected {
+		uri: uri.into(),
+		location: response
+			.headers()
+			.get(LOCATION)
+			.and_then(|l| l.to_str().ok())
+			.unwrap_or_default()
+			.into(),
+	}
+}
+
+// Whether the request failed on a connection which is likely dead, e.g. a stale keep-alive one
+// the server dropped meanwhile, which shows up as a timeout or a reset rather than a refusal.
+fn is_stale_connection(e: &reqwest::Error) -> bool {
+	if e.is_connect() || e.is_timeout() {
+		return true;
+	}
+
+	let mut source = e.source();
+
+	while let Some(s) = source {
+		if s.downcast_ref::<io::Error>().is_some_and(|e| {
+			matches!(
+				e.kind(),
+				ErrorKind::ConnectionReset
+					| ErrorKind::ConnectionAborted
+					| ErrorKind::BrokenPipe
+					| ErrorKind::UnexpectedEof
+			)
+		}) {
+			return true;
+		}
+
+		source = s.source();
+	}
+
+	false
+}
+
+fn is_empty_response(resp_text: &str) -> bool {
+	match serde_json::from_str::<Response<Value>>(resp_text).map(|r| r.response) {
+		Ok(Value::Null) => true,
+		Ok(Value::Array(a)) => a.is_empty(),
+		Ok(Value::Object(o)) => o.is_empty(),
+		_ => false,
+	}
+}
+
+// Classify why every base URI failed, from the last error of each.
+fn exhausted<I>(errors: I) -> Error
+where
+	I: IntoIterator<Item = (String, Error)>,
+{
+	let mut errors = errors.into_iter().collect::<Vec<_>>();
+	let rejection_of = |e: &Error| match e.downcast_ref::<ElectrumXError>() {
+		Some(ElectrumXError::Rpc { message, .. } | ElectrumXError::Atomicals(_, message)) =>
+			Some(message.to_owned()),
+		_ => None,
+	};
+
+	// All the proxies agreeing on the rejection means the request itself is wrong.
+	if let Some(r) = errors.first().and_then(|(_, e)| rejection_of(e)) {
+		if errors.iter().all(|(_, e)| rejection_of(e).as_ref() == Some(&r)) {
+			let n = errors.len();
+
+			return errors
+				.swap_remove(0)
+				.1
+				.context(format!("all {n} base URIs rejected the request"));
+		}
+	}
+
+	let cause = if errors.iter().all(|(_, e)| e.is::<reqwest::Error>()) {
+		Exhaustion::Unreachable
+	} else {
+		Exhaustion::Mixed
+	};
+
+	ElectrumXError::AllUrisExhausted {
+		cause,
+		errors: errors.into_iter().map(|(u, e)| (u, e.to_string())).collect(),
+	}
+	.into()
+}
+
+// Turn a response which failed to parse into the most specific error.
+fn error_of(resp_text: &str, e: serde_json::Error) -> Error {
+	match serde_json::from_str::<ErrorResponse>(resp_text)
+		.ok()
+		.and_then(ErrorResponse::into_rpc_error)
+	{
+		Some(RpcError { code, message }) => match AtomicalsError::from_message(&message) {
+			Some(a) => ElectrumXError::Atomicals(a, message).into(),
+			None => ElectrumXError::Rpc { code, message }.into(),
+		},
+		None => e.into(),
+	}
+}
+
+/// How [`ElectrumX`] spreads a request over the base URIs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+	/// Try the base URIs one after another, retrying each a few times.
+	#[default]
+	Sequential,
+	/// Send to all the base URIs at once and take the first valid response.
+	///
+	/// Lower latency at the cost of more load on the proxies.
+	Race,
+}
+
+/// How to settle the UTXO lists of proxies which are slightly out of sync.
+///
+/// A lagging proxy may still list an output the others have seen spent, or not list yet one
+/// which was just received. Either way, an outpoint only some of them list is in doubt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reconciliation {
+	/// An outpoint is spent if any proxy misses it.
+	///
+	/// Never attempts a double spend, at the cost of waiting for every proxy to see new funds.
+	#[default]
+	Conservative,
+	/// An outpoint is available if any proxy lists it.
+	///
+	/// Picks up new funds right away, but a transaction spending an output which turns out to be
+	/// spent already gets rejected, after its fees were estimated and its work was done.
+	Optimistic,
+}
+impl Reconciliation {
+	/// Merge the UTXO lists of the proxies into one, in the order they first appear.
+	pub fn reconcile(self, lists: Vec<Vec<Utxo>>) -> Vec<Utxo> {
+		let n = lists.len();
+		let mut counts = HashMap::<(String, u32), usize>::new();
+		let mut utxos = Vec::new();
+
+		for u in lists.into_iter().flatten() {
+			let c = counts.entry((u.txid.clone(), u.vout)).or_default();
+
+			if *c == 0 {
+				utxos.push(u);
+			}
+
+			*c += 1;
+		}
+
+		match self {
+			Self::Conservative =>
+				utxos.retain(|u| counts.get(&(u.txid.clone(), u.vout)) == Some(&n)),
+			Self::Optimistic => (),
+		}
+
+		utxos
+	}
+}
+
+// Advertised by the proxy through the `X-RateLimit-*` headers.
+#[derive(Clone, Copy, Debug)]
+struct RateLimit {
+	remaining: u64,
+	reset_at: Instant,
+}
+impl RateLimit {
+	fn from_headers(headers: &HeaderMap, now: Instant) -> Option<Self> {
+		let header = |n| headers.get(n)?.to_str().ok()?.trim().parse::<u64>().ok();
+		let remaining = header("x-ratelimit-remaining")?;
+		let reset = header("x-ratelimit-reset")?;
+		// Either the seconds until the reset or the unix timestamp of it.
+		let reset = if reset > 1_000_000_000 {
+			reset.saturating_sub(
+				SystemTime::now()
+					.duration_since(UNIX_EPOCH)
+					.map(|d| d.as_secs())
+					.unwrap_or_default(),
+			)
+		} else {
+			reset
+		};
+
+		Some(Self { remaining, reset_at: now + Duration::from_secs(reset) })
+	}
+}
+
+/// Returned by [`ElectrumX::with_sticky_uri`], the pin goes away along with it.
+#[derive(Debug)]
+pub struct StickyUri<'a> {
+	client: &'a ElectrumX,
+	uri: Mutex<Option<String>>,
+}
+impl Config for StickyUri<'_> {
+	fn network(&self) -> &Network {
+		self.client.network()
+	}
+
+	fn base_uris(&self) -> &[String] {
+		self.client.base_uris()
+	}
+
+	fn clock(&self) -> &dyn Clock {
+		self.client.clock()
+	}
+
+	fn poll_interval(&self) -> Duration {
+		self.client.poll_interval()
+	}
+
+	fn funding_min_confirmations(&self) -> u64 {
+		self.client.funding_min_confirmations()
+	}
+
+	fn max_broadcast_size(&self) -> usize {
+		self.client.max_broadcast_size()
+	}
+
+	fn max_concurrent_broadcasts(&self) -> usize {
+		self.client.max_concurrent_broadcasts()
+	}
+
+	fn broadcast_verify_window(&self) -> Option<Duration> {
+		self.client.broadcast_verify_window()
+	}
+
+	fn last_base_uri_of(&self, method: &str) -> Option<String> {
+		self.client.last_base_uri_of(method)
+	}
+
+	fn spend_cap(&self) -> Option<&SpendCap> {
+		self.client.spend_cap()
+	}
+
+	fn reconciliation(&self) -> Reconciliation {
+		self.client.reconciliation()
+	}
+
+	fn max_fee_rate(&self) -> Option<FeeRate> {
+		self.client.max_fee_rate()
+	}
+}
+impl Http for StickyUri<'_> {
+	async fn post<U, P, R>(&self, endpoint: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		self.client.post_pinned(Some(&self.uri), endpoint, params).await
+	}
+
+	async fn post_to<U, P, R>(&self, base_uri: &str, endpoint: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		self.client.post_to(base_uri, endpoint, params).await
+	}
+}
+
+/// Set by [`ElectrumXBuilder::request_interceptor`].
+#[derive(Clone)]
+pub struct RequestInterceptor(Arc<dyn Fn(&mut Value) + Send + Sync>);
+impl Debug for RequestInterceptor {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		f.write_str("RequestInterceptor")
+	}
+}
+
+/// Set by [`ElectrumXBuilder::validate_response`].
+#[derive(Clone)]
+pub struct ResponseValidator(Arc<ValidateFn>);
+type ValidateFn = dyn Fn(&Value) -> Result<()> + Send + Sync;
+impl Debug for ResponseValidator {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		f.write_str("ResponseValidator")
+	}
+}
+
+#[derive(Debug)]
+pub struct ElectrumXBuilder {
+	pub network: Network,
+	pub base_uris: Vec<String>,
+	pub max_concurrency: usize,
+	pub max_concurrent_broadcasts: usize,
+	pub broadcast_interval: Duration,
+	pub failover: bool,
+	pub strategy: Strategy,
+	pub require_healthy: usize,
+	pub max_broadcast_size: usize,
+	pub broadcast_verify_window: Option<Duration>,
+	pub timeout: Duration,
+	pub connect_timeout: Option<Duration>,
+	pub max_retries: usize,
+	pub max_redirects: usize,
+	pub proxy: Option<String>,
+	pub funding_min_confirmations: u64,
+	pub poll_interval: Duration,
+	pub clock: Arc<dyn Clock>,
+	pub backoff: Arc<dyn BackoffPolicy>,
+	pub method_aliases: HashMap<String, String>,
+	pub method_timeouts: HashMap<String, Duration>,
+	pub retry_on_empty: HashMap<String, usize>,
+	pub request_interceptor: Option<RequestInterceptor>,
+	pub response_validators: HashMap<String, ResponseValidator>,
+	pub spend_cap: Option<u64>,
+	pub max_fee_rate: Option<FeeRate>,
+	pub reconciliation: Reconciliation,
+	pub record: bool,
+}
+impl ElectrumXBuilder {
+	#[cfg(test)]
+	pub fn testnet() -> Self {
+		Self { network: Network::Testnet, ..Default::default() }
+	}
+
+	pub fn network(mut self, network: Network) -> Self {
+		self.network = network;
+
+		self
+	}
+
+	pub fn base_uris<S>(mut self, base_uris: S) -> Self
+	where
+		S: Into<String>,
+	{
+		self.base_uris = base_uris.into().split(',').map(String::from).collect();
+
+		self
+	}
+
+	pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+		self.max_concurrency = max_concurrency;
+
+		self
+	}
+
+	/// Allow at most `max_concurrent_broadcasts` broadcasts in flight, within the general
+	/// [`ElectrumXBuilder::max_concurrency`], and start them at least `interval` apart.
+	///
+	/// Defaults to 2 broadcasts 250ms apart, which paces a burst of reveals below the usual
+	/// anti-spam limits without slowing down a single commit and reveal.
+	pub fn broadcast_throttle(
+		mut self,
+		max_concurrent_broadcasts: usize,
+		interval: Duration,
+	) -> Self {
+		self.max_concurrent_broadcasts = max_concurrent_broadcasts;
+		self.broadcast_interval = interval;
+
+		self
+	}
+
+	/// Only use the first base URI and surface its errors directly, without retrying.
+	///
+	/// Useful while debugging a specific proxy.
+	pub fn no_failover(mut self) -> Self {
+		self.failover = false;
+
+		self
+	}
+
+	pub fn strategy(mut self, strategy: Strategy) -> Self {
+		self.strategy = strategy;
+
+		self
+	}
+
+	/// Make [`ElectrumX::ensure_ready`] require at least `require_healthy` reachable base URIs.
+	pub fn require_healthy(mut self, require_healthy: usize) -> Self {
+		self.require_healthy = require_healthy;
+
+		self
+	}
+
+	/// Raise or lower the transaction hex size limit for servers with a different request size
+	/// limit.
+	pub fn max_broadcast_size(mut self, max_broadcast_size: usize) -> Self {
+		self.max_broadcast_size = max_broadcast_size;
+
+		self
+	}
+
+	/// Make [`Api::broadcast`] wait up to `window` for another backend to see the transaction,
+	/// failing with [`ElectrumXError::BroadcastUnconfirmed`] otherwise.
+	pub fn verify_broadcast(mut self, window: Duration) -> Self {
+		self.broadcast_verify_window = Some(window);
+
+		self
+	}
+
+	/// Give up on a request after `timeout`, defaults to 30s.
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = timeout;
+
+		self
+	}
+
+	/// Give up connecting to a base URI after `connect_timeout`, independent of the 30s total
+	/// request timeout.
+	///
+	/// Keep it short to fail over quickly from unreachable proxies.
+	pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+		self.connect_timeout = Some(connect_timeout);
+
+		self
+	}
+
+	/// Try each base URI `max_retries` times before moving on to the next one, defaults to 3.
+	pub fn max_retries(mut self, max_retries: usize) -> Self {
+		self.max_retries = max_retries;
+
+		self
+	}
+
+	/// Send every request through the HTTP `proxy`, e.g. `http://127.0.0.1:8080`.
+	pub fn proxy<S>(mut self, proxy: S) -> Self
+	where
+		S: Into<String>,
+	{
+		self.proxy = Some(proxy.into());
+
+		self
+	}
+
+	/// Follow up to `max_redirects` redirects of a moved proxy, `0` disables following them.
+	///
+	/// Only `307` and `308` are followed, as the others drop the request body. Each followed
+	/// redirect is logged and any other one fails with [`ElectrumXError::Redirected`], so the base
+	/// URI can be updated.
+	pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+		self.max_redirects = max_redirects;
+
+		self
+	}
+
+	/// Require funding UTXOs to have at least `funding_min_confirmations` confirmations.
+	///
+	/// Defaults to `0`, which accepts unconfirmed funds. Those could be dropped by a reorg or
+	/// double spent, invalidating everything built on top of them.
+	pub fn funding_min_confirmations(mut self, funding_min_confirmations: u64) -> Self {
+		self.funding_min_confirmations = funding_min_confirmations;
+
+		self
+	}
+
+	/// Sleep `poll_interval` between the polls of the waits, defaults to 5s.
+	pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+		self.poll_interval = poll_interval;
+
+		self
+	}
+
+	/// Replace the clock driving the retry delays and poll intervals with one which only moves on
+	/// demand in tests.
+	#[cfg(test)]
+	pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+		self.clock = clock;
+
+		self
+	}
+
+	/// Replace the policy timing the retries, a constant 2 seconds by default.
+	///
+	/// See [`backoff`] for the built-in ones.
+	pub fn backoff(mut self, backoff: Arc<dyn BackoffPolicy>) -> Self {
+		self.backoff = backoff;
+
+		self
+	}
+
+	/// Send the built-in `method` as `alias`, for proxies which namespace the methods
+	/// differently.
+	///
+	/// E.g. `.method_alias("blockchain.atomicals.get_by_ticker", "v1/atomicals.get_by_ticker")`.
+	pub fn method_alias<M, A>(mut self, method: M, alias: A) -> Self
+	where
+		M: Into<String>,
+		A: Into<String>,
+	{
+		self.method_aliases.insert(method.into(), alias.into());
+
+		self
+	}
+
+	/// Give up on `method` after `timeout` instead of the 30s default, e.g. longer for the
+	/// `blockchain.scripthash.listunspent` of a huge wallet and shorter for a ticker lookup.
+	pub fn method_timeout<M>(mut self, method: M, timeout: Duration) -> Self
+	where
+		M: Into<String>,
+	{
+		self.method_timeouts.insert(method.into(), timeout);
+
+		self
+	}
+
+	/// Retry `method` up to `retries` times while it returns an empty result, which is likely
+	/// the proxy lagging behind on indexing, e.g. a freshly funded address without UTXOs yet.
+	///
+	/// Off by default, as an empty result is usually a legit answer. Only applies to the
+	/// [`Strategy::Sequential`] strategy.
+	pub fn retry_on_empty<M>(mut self, method: M, retries: usize) -> Self
+	where
+		M: Into<String>,
+	{
+		self.retry_on_empty.insert(method.into(), retries);
+
+		self
+	}
+
+	/// Inspect or rewrite the JSON body of every request right before it is sent.
+	///
+	/// An escape hatch for quirky servers, e.g. to add a field or change the params encoding.
+	pub fn request_interceptor<F>(mut self, request_interceptor: F) -> Self
+	where
+		F: 'static + Fn(&mut Value) + Send + Sync,
+	{
+		self.request_interceptor = Some(RequestInterceptor(Arc::new(request_interceptor)));
+
+		self
+	}
+
+	/// Check the responses of `method` once they are deserialized.
+	///
+	/// A rejected response is handled like one which failed to parse, the next base URI is tried.
+	pub fn validate_response<M, F>(mut self, method: M, validator: F) -> Self
+	where
+		M: Into<String>,
+		F: 'static + Fn(&Value) -> Result<()> + Send + Sync,
+	{
+		self.response_validators.insert(method.into(), ResponseValidator(Arc::new(validator)));
+
+		self
+	}
+
+	/// Refuse the broadcasts once they would spend more than `satoshis` in total, fees included.
+	///
+	/// A guardrail against a runaway loop draining the funding wallet, see [`SpendCap`].
+	pub fn spend_cap(mut self, satoshis: u64) -> Self {
+		self.spend_cap = Some(satoshis);
+
+		self
+	}
+
+	/// Refuse the broadcasts paying more than `fee_rate`, 1,000 sat/vB by default.
+	pub fn max_fee_rate(mut self, fee_rate: FeeRate) -> Self {
+		self.max_fee_rate = Some(fee_rate);
+
+		self
+	}
+
+	/// Broadcast whatever the fee rate, e.g. to get a stuck transaction out at any cost.
+	pub fn allow_absurd_fee_rate(mut self) -> Self {
+		self.max_fee_rate = None;
+
+		self
+	}
+
+	/// Settle the proxies which disagree on the UTXOs this way, conservatively by default.
+	///
+	/// See [`Reconciliation`] for the tradeoff.
+	pub fn reconciliation(mut self, reconciliation: Reconciliation) -> Self {
+		self.reconciliation = reconciliation;
+
+		self
+	}
+
+	/// Record every request and response, for [`ElectrumX::scenario`].
+	pub fn record(mut self) -> Self {
+		self.record = true;
+
+		self
+	}
+
+	/// Unless [`ElectrumXBuilder::base_uris`] is set, the [`default_base_uris_of`] the network
+	/// are used.
+	pub fn build(self) -> Result<ElectrumX> {
+		let base_uris = self.resolve_base_uris()?;
+		let client_options = self.client_options();
+
+		Ok(ElectrumX {
+			clients: RwLock::new(
+				base_uris
+					.iter()
+					.map(|u| Ok((u.to_owned(), client_options.build()?)))
+					.collect::<Result<_>>()?,
+			),
+			client_options,
+			network: self.network,
+			base_uris,
+			max_retries: self.max_retries,
+			failover: self.failover,
+			strategy: self.strategy,
+			require_healthy: self.require_healthy,
+			max_broadcast_size: self.max_broadcast_size,
+			broadcast_verify_window: self.broadcast_verify_window,
+			funding_min_confirmations: self.funding_min_confirmations,
+			poll_interval: self.poll_interval,
+			clock: self.clock,
+			backoff: self.backoff,
+			method_aliases: self.method_aliases,
+			method_timeouts: self.method_timeouts,
+			retry_on_empty: self.retry_on_empty,
+			request_interceptor: self.request_interceptor,
+			response_validators: self.response_validators,
+			spend_cap: self.spend_cap.map(SpendCap::new),
+			max_fee_rate: self.max_fee_rate,
+			reconciliation: self.reconciliation,
+			recorder: self.record.then(Default::default),
+			semaphore: Semaphore::new(self.max_concurrency),
+			broadcast_semaphore: Semaphore::new(self.max_concurrent_broadcasts),
+			max_concurrent_broadcasts: self.max_concurrent_broadcasts,
+			broadcast_interval: self.broadcast_interval,
+			next_broadcast_at: Default::default(),
+			last_successful_uri_index: Mutex::new(0),
+			connect_failures: Default::default(),
+			race_penalties: Default::default(),
+			rate_limits: Default::default(),
+			last_base_uris: Default::default(),
+		})
+	}
+
+	// The normalized base URIs the client will be built with.
+	fn client_options(&self) -> ClientOptions {
+		ClientOptions {
+			timeout: self.timeout,
+			connect_timeout: self.connect_timeout,
+			max_redirects: self.max_redirects,
+			proxy: self.proxy.clone(),
+		}
+	}
+
+	fn resolve_base_uris(&self) -> Result<Vec<String>> {
+		// Otherwise a mainnet URI on testnet silently returns nothing.
+		let base_uris = if self.base_uris.is_empty() {
+			default_base_uris_of(self.network)?
+		} else {
+			self.base_uris.clone()
+		};
+
+		Ok(base_uris
+			.iter()
+			.map(|u| {
+				let u = u.trim().trim_end_matches('/');
+
+				match Url::parse(u) {
+					Ok(p) if matches!(p.scheme(), "http" | "https") => Ok(u.to_owned()),
+					Ok(p) => Err(ElectrumXError::InvalidBaseUri {
+						uri: u.into(),
+						reason: format!("unsupported scheme {}", p.scheme()),
+					}),
+					Err(e) =>
+						Err(ElectrumXError::InvalidBaseUri { uri: u.into(), reason: e.to_string() }),
+				}
+			})
+			.collect::<Result<Vec<_>, _>>()?)
+	}
+}
+impl Default for ElectrumXBuilder {
+	fn default() -> Self {
+		Self {
+			network: Network::Bitcoin,
+			base_uris: Vec::new(),
+			max_concurrency: 4,
+			max_concurrent_broadcasts: 2,
+			broadcast_interval: Duration::from_millis(250),
+			failover: true,
+			strategy: Strategy::default(),
+			require_healthy: 0,
+			max_broadcast_size: DEFAULT_MAX_BROADCAST_SIZE,
+			broadcast_verify_window: None,
+			timeout: Duration::from_secs(30),
+			connect_timeout: None,
+			max_retries: 3, // 设置默认的重试次数
+			max_redirects: DEFAULT_MAX_REDIRECTS,
+			proxy: None,
+			funding_min_confirmations: 0,
+			poll_interval: DEFAULT_POLL_INTERVAL,
+			clock: Arc::new(RealClock),
+			backoff: Arc::new(backoff::Constant::default()),
+			method_aliases: Default::default(),
+			method_timeouts: Default::default(),
+			retry_on_empty: Default::default(),
+			request_interceptor: None,
+			response_validators: Default::default(),
+			spend_cap: None,
+			max_fee_rate: Some(FeeRate::from_sat_per_vb(DEFAULT_MAX_FEE_RATE)),
+			reconciliation: Default::default(),
+			record: false,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ClientOptions {
+	timeout: Duration,
+	connect_timeout: Option<Duration>,
+	max_redirects: usize,
+	proxy: Option<String>,
+}
+impl ClientOptions {
+	fn build(&self) -> Result<ReqwestClient> {
+		let max_redirects = self.max_redirects;
+		let mut b =
+			ReqwestClientBuilder::new().timeout(self.timeout).redirect(Policy::custom(move |a| {
+				// The others turn the `POST` into a `GET`, dropping the request.
+				if a.previous().len() > max_redirects
+					|| !matches!(
+						a.status(),
+						StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT
+					) {
+					return a.stop();
+				}
+
+				tracing::warn!(
+					from = %a.previous().last().map(|u| u.as_str()).unwrap_or_default(),
+					to = %a.url(),
+					"redirected, consider updating the base URI"
+				);
+
+				a.follow()
+			}));
+
+		if let Some(t) = self.connect_timeout {
+			b = b.connect_timeout(t);
+		}
+		if let Some(p) = &self.proxy {
+			b = b.proxy(Proxy::all(p)?);
+		}
+
+		Ok(b.build()?)
 	}
 }
@@ -0,0 +1,63 @@
+// std
+use std::{fmt::Debug, time::Duration};
+
+/// How long [`ElectrumX`](crate::electrumx::ElectrumX) waits before retrying a request.
+pub trait BackoffPolicy: Debug + Send + Sync {
+	/// The delay before the `attempt`-th retry, counting from `1`.
+	fn delay(&self, attempt: usize) -> Duration;
+}
+
+/// The same delay before every retry, 2 seconds by default.
+#[derive(Debug)]
+pub struct Constant(pub Duration);
+impl Default for Constant {
+	fn default() -> Self {
+		Self(Duration::from_secs(2))
+	}
+}
+impl BackoffPolicy for Constant {
+	fn delay(&self, _: usize) -> Duration {
+		self.0
+	}
+}
+
+/// `step` more before each retry, up to `max`.
+#[derive(Debug)]
+pub struct Linear {
+	pub step: Duration,
+	pub max: Duration,
+}
+impl BackoffPolicy for Linear {
+	fn delay(&self, attempt: usize) -> Duration {
+		self.step.saturating_mul(attempt.max(1) as _).min(self.max)
+	}
+}
+
+/// `base` multiplied by `factor` before each retry, up to `max`.
+#[derive(Debug)]
+pub struct Exponential {
+	pub base: Duration,
+	pub factor: u32,
+	pub max: Duration,
+}
+impl BackoffPolicy for Exponential {
+	fn delay(&self, attempt: usize) -> Duration {
+		let exponent = attempt.max(1).saturating_sub(1).min(u32::MAX as _) as u32;
+
+		self.base.saturating_mul(self.factor.saturating_pow(exponent)).min(self.max)
+	}
+}
+#[test]
+fn backoff_policies_should_work() {
+	let s = Duration::from_secs;
+	let delays = |p: &dyn BackoffPolicy| (1..=5).map(|a| p.delay(a)).collect::<Vec<_>>();
+
+	assert_eq!(delays(&Constant::default()), [s(2); 5]);
+	assert_eq!(delays(&Linear { step: s(1), max: s(3) }), [s(1), s(2), s(3), s(3), s(3)]);
+	assert_eq!(
+		delays(&Exponential { base: s(1), factor: 2, max: s(10) }),
+		[s(1), s(2), s(4), s(8), s(10)]
+	);
+	// No overflow on a large attempt.
+	assert_eq!(Exponential { base: s(1), factor: 2, max: s(10) }.delay(usize::MAX), s(10));
+}
@@ -0,0 +1,107 @@
+// std
+#[cfg(test)] use std::sync::Mutex;
+use std::{
+	fmt::Debug,
+	future::Future,
+	pin::Pin,
+	time::{Duration, Instant},
+};
+// crates.io
+#[cfg(test)] use tokio::sync::Notify;
+use tokio::time;
+
+pub type Sleep<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Source of time for the retry delays and poll intervals.
+pub trait Clock: Debug + Send + Sync {
+	fn now(&self) -> Instant;
+
+	fn sleep(&self, duration: Duration) -> Sleep<'_>;
+}
+
+#[derive(Debug)]
+pub struct RealClock;
+impl Clock for RealClock {
+	fn now(&self) -> Instant {
+		Instant::now()
+	}
+
+	fn sleep(&self, duration: Duration) -> Sleep<'_> {
+		Box::pin(time::sleep(duration))
+	}
+}
+
+/// A clock which only moves when [`TestClock::advance`] is called.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct TestClock {
+	start: Instant,
+	elapsed: Mutex<Duration>,
+	notify: Notify,
+}
+#[cfg(test)]
+impl TestClock {
+	pub fn new() -> Self {
+		Self { start: Instant::now(), elapsed: Default::default(), notify: Notify::new() }
+	}
+
+	pub fn advance(&self, duration: Duration) {
+		*self.elapsed.lock().unwrap() += duration;
+
+		self.notify.notify_waiters();
+	}
+}
+#[cfg(test)]
+impl Default for TestClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+#[cfg(test)]
+impl Clock for TestClock {
+	fn now(&self) -> Instant {
+		self.start + *self.elapsed.lock().unwrap()
+	}
+
+	fn sleep(&self, duration: Duration) -> Sleep<'_> {
+		let deadline = self.now() + duration;
+
+		Box::pin(async move {
+			loop {
+				// Register before checking, so an `advance` in between isn't missed.
+				let n = self.notify.notified();
+
+				if self.now() >= deadline {
+					return;
+				}
+
+				n.await;
+			}
+		})
+	}
+}
+#[tokio::test]
+async fn test_clock_should_only_move_on_advance() {
+	// std
+	use std::sync::Arc;
+
+	let c = Arc::new(TestClock::new());
+	let start = c.now();
+	let s = tokio::spawn({
+		let c = c.clone();
+
+		async move { c.sleep(Duration::from_secs(5)).await }
+	});
+
+	c.advance(Duration::from_secs(2));
+	tokio::task::yield_now().await;
+
+	assert!(!s.is_finished());
+
+	while !s.is_finished() {
+		c.advance(Duration::from_secs(3));
+		tokio::task::yield_now().await;
+	}
+
+	assert!(c.now() - start >= Duration::from_secs(5));
+}
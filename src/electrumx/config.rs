@@ -0,0 +1,297 @@
+// std
+use std::{collections::HashMap, fs, path::Path, sync::Arc, time::Duration};
+// crates.io
+use bitcoin::Network;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+// atomicalsir
+use crate::{
+	electrumx::{
+		backoff::{BackoffPolicy, Constant, Exponential, Linear},
+		r#type::FeeRate,
+		ElectrumXBuilder, Reconciliation, Strategy,
+	},
+	prelude::*,
+};
+
+/// The settings of an [`ElectrumX`](crate::electrumx::ElectrumX) client, read from a JSON file.
+///
+/// Every field is optional and falls back to the [`ElectrumXBuilder`] default. Durations are
+/// in milliseconds. Unknown fields are rejected, so a typo doesn't silently do nothing.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+	#[serde(deserialize_with = "deserialize_network")]
+	pub network: Option<Network>,
+	pub base_uris: Vec<String>,
+	pub timeout_ms: Option<u64>,
+	pub connect_timeout_ms: Option<u64>,
+	pub method_timeouts_ms: HashMap<String, u64>,
+	pub max_retries: Option<usize>,
+	pub backoff: Option<Backoff>,
+	pub failover: Option<bool>,
+	pub strategy: Option<Strategy>,
+	pub proxy: Option<String>,
+	pub max_concurrency: Option<usize>,
+	pub max_concurrent_broadcasts: Option<usize>,
+	pub broadcast_interval_ms: Option<u64>,
+	pub max_broadcast_size: Option<usize>,
+	pub max_redirects: Option<usize>,
+	pub funding_min_confirmations: Option<u64>,
+	pub poll_interval_ms: Option<u64>,
+	pub max_fee_rate_sat_per_vb: Option<f64>,
+	pub allow_absurd_fee_rate: bool,
+	pub reconciliation: Option<Reconciliation>,
+	pub method_aliases: HashMap<String, String>,
+	pub retry_on_empty: HashMap<String, usize>,
+	pub request_fields: Map<String, Value>,
+	pub response_fields: HashMap<String, Vec<String>>,
+}
+impl ClientConfig {
+	pub fn load<P>(path: P) -> Result<Self>
+	where
+		P: AsRef<Path>,
+	{
+		let path = path.as_ref();
+
+		serde_json::from_slice(&fs::read(path)?)
+			.map_err(|e| anyhow::anyhow!("invalid config {}: {e}", path.display()))
+	}
+}
+
+/// The delays between the retries, see [`backoff`](crate::electrumx::backoff).
+///
+/// Tagged by its `kind`, e.g. `{"kind": "exponential", "base_ms": 500, "factor": 2, "max_ms":
+/// 10000}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Backoff {
+	Constant { delay_ms: u64 },
+	Linear { step_ms: u64, max_ms: u64 },
+	Exponential { base_ms: u64, factor: u32, max_ms: u64 },
+}
+impl Backoff {
+	fn policy(self) -> Arc<dyn BackoffPolicy> {
+		let ms = Duration::from_millis;
+
+		match self {
+			Self::Constant { delay_ms } => Arc::new(Constant(ms(delay_ms))),
+			Self::Linear { step_ms, max_ms } =>
+				Arc::new(Linear { step: ms(step_ms), max: ms(max_ms) }),
+			Self::Exponential { base_ms, factor, max_ms } =>
+				Arc::new(Exponential { base: ms(base_ms), factor, max: ms(max_ms) }),
+		}
+	}
+}
+
+impl ElectrumXBuilder {
+	/// A builder with the settings of `config`, the others being left at their default.
+	///
+	/// Rejects the values the client can't work with, e.g. no concurrency at all.
+	pub fn from_config(config: ClientConfig) -> Result<Self> {
+		let mut b = Self::default();
+		let ms = Duration::from_millis;
+		let invalid = |field: &str, reason: &str| anyhow::anyhow!("invalid {field}: {reason}");
+
+		if config.base_uris.iter().any(|u| u.trim().is_empty()) {
+			Err(invalid("base_uris", "empty URI"))?;
+		}
+		if config.max_concurrency == Some(0) {
+			Err(invalid("max_concurrency", "at least 1 is needed"))?;
+		}
+		if config.max_concurrent_broadcasts == Some(0) {
+			Err(invalid("max_concurrent_broadcasts", "at least 1 is needed"))?;
+		}
+		if config.max_broadcast_size == Some(0) {
+			Err(invalid("max_broadcast_size", "at least 1 byte is needed"))?;
+		}
+		if config.request_fields.contains_key("params") {
+			Err(invalid("request_fields", "the params can't be overridden"))?;
+		}
+		if let Some(Backoff::Exponential { factor: 0, .. }) = config.backoff {
+			Err(invalid("backoff", "a factor of at least 1 is needed"))?;
+		}
+		if let Some(p) = &config.proxy {
+			reqwest::Proxy::all(p).map_err(|e| invalid("proxy", &format!("{p}, {e}")))?;
+		}
+
+		if let Some(n) = config.network {
+			b = b.network(n);
+		}
+		if !config.base_uris.is_empty() {
+			b.base_uris = config.base_uris;
+		}
+		if let Some(t) = config.timeout_ms {
+			b = b.timeout(ms(t));
+		}
+		if let Some(t) = config.connect_timeout_ms {
+			b = b.connect_timeout(ms(t));
+		}
+		for (m, t) in config.method_timeouts_ms {
+			b = b.method_timeout(m, ms(t));
+		}
+		if let Some(r) = config.max_retries {
+			b = b.max_retries(r);
+		}
+		if let Some(p) = config.backoff {
+			b = b.backoff(p.policy());
+		}
+		if config.failover == Some(false) {
+			b = b.no_failover();
+		}
+		if let Some(s) = config.strategy {
+			b = b.strategy(s);
+		}
+		if let Some(p) = config.proxy {
+			b = b.proxy(p);
+		}
+		if let Some(c) = config.max_concurrency {
+			b = b.max_concurrency(c);
+		}
+		if config.max_concurrent_broadcasts.is_some() || config.broadcast_interval_ms.is_some() {
+			let c = config.max_concurrent_broadcasts.unwrap_or(b.max_concurrent_broadcasts);
+			let i = config.broadcast_interval_ms.map(ms).unwrap_or(b.broadcast_interval);
+
+			b = b.broadcast_throttle(c, i);
+		}
+		if let Some(s) = config.max_broadcast_size {
+			b = b.max_broadcast_size(s);
+		}
+		if let Some(r) = config.max_redirects {
+			b = b.max_redirects(r);
+		}
+		if let Some(c) = config.funding_min_confirmations {
+			b = b.funding_min_confirmations(c);
+		}
+		if let Some(i) = config.poll_interval_ms {
+			b = b.poll_interval(ms(i));
+		}
+		if let Some(r) = config.max_fee_rate_sat_per_vb {
+			b = b.max_fee_rate(FeeRate::from_sat_per_vb(r));
+		}
+		if config.allow_absurd_fee_rate {
+			b = b.allow_absurd_fee_rate();
+		}
+		if let Some(r) = config.reconciliation {
+			b = b.reconciliation(r);
+		}
+		for (m, a) in config.method_aliases {
+			b = b.method_alias(m, a);
+		}
+		for (m, r) in config.retry_on_empty {
+			b = b.retry_on_empty(m, r);
+		}
+		if !config.request_fields.is_empty() {
+			let fields = config.request_fields;
+
+			b = b.request_interceptor(move |body| {
+				if let Value::Object(o) = body {
+					o.extend(fields.clone());
+				}
+			});
+		}
+		for (m, fs) in config.response_fields {
+			b = b.validate_response(m, move |v| {
+				if let Some(f) = fs.iter().find(|f| v["response"].get(f).is_none()) {
+					Err(anyhow::anyhow!("missing field {f}"))?;
+				}
+
+				Ok(())
+			});
+		}
+
+		Ok(b)
+	}
+}
+
+fn deserialize_network<'de, D>(deserializer: D) -> Result<Option<Network>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	Option::<String>::deserialize(deserializer)?
+		.map(|n| n.parse().map_err(serde::de::Error::custom))
+		.transpose()
+}
+#[test]
+fn client_config_should_work() {
+	let c = serde_json::from_str::<ClientConfig>(
+		r#"{
+			"network": "testnet",
+			"base_uris": ["https://eptestnet.atomicals.xyz/proxy"],
+			"timeout_ms": 5000,
+			"strategy": "race",
+			"max_concurrency": 8,
+			"failover": false,
+			"max_broadcast_size": 4194304,
+			"max_concurrent_broadcasts": 2,
+			"broadcast_interval_ms": 250,
+			"retry_on_empty": { "blockchain.scripthash.listunspent": 2 },
+			"request_fields": { "api_key": "secret" },
+			"response_fields": { "blockchain.atomicals.get": ["atomical_id", "atomical_number"] },
+			"backoff": { "kind": "exponential", "base_ms": 100, "factor": 2, "max_ms": 300 }
+		}"#,
+	)
+	.unwrap();
+	let b = ElectrumXBuilder::from_config(c).unwrap();
+
+	assert_eq!(b.network, Network::Testnet);
+	assert_eq!(b.base_uris, ["https://eptestnet.atomicals.xyz/proxy"]);
+	assert_eq!(b.timeout, Duration::from_secs(5));
+	assert_eq!(b.strategy, Strategy::Race);
+	assert_eq!(b.max_concurrency, 8);
+	assert!(!b.failover);
+	assert_eq!(b.max_broadcast_size, 4 * 1024 * 1024);
+	assert_eq!(b.max_concurrent_broadcasts, 2);
+	assert_eq!(b.broadcast_interval, Duration::from_millis(250));
+	assert_eq!(b.retry_on_empty, HashMap::from([("blockchain.scripthash.listunspent".into(), 2)]));
+	assert_eq!(
+		(1..=3).map(|a| b.backoff.delay(a)).collect::<Vec<_>>(),
+		[Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(300)]
+	);
+	// Left at the default.
+	assert_eq!(b.max_retries, 3);
+
+	let v = &b.response_validators["blockchain.atomicals.get"].0;
+
+	assert!(
+		v(&serde_json::json!({ "response": { "atomical_id": "a", "atomical_number": 1 } })).is_ok()
+	);
+	assert!(v(&serde_json::json!({ "response": { "atomical_id": "a" } }))
+		.unwrap_err()
+		.to_string()
+		.contains("missing field atomical_number"));
+	assert_eq!(
+		b.build().unwrap().body_of(crate::electrumx::r#type::Params::new(["atom"])).unwrap(),
+		serde_json::json!({ "params": ["atom"], "api_key": "secret" })
+	);
+
+	for (c, e) in [
+		(r#"{"max_retry": 1}"#, "unknown field `max_retry`"),
+		(r#"{"network": "moon"}"#, "failed to parse moon as network"),
+		(r#"{"strategy": "fastest"}"#, "unknown variant `fastest`"),
+		(r#"{"timeout_ms": "5s"}"#, "expected u64"),
+		(r#"{"backoff": {"kind": "random"}}"#, "unknown variant `random`"),
+	] {
+		let r = serde_json::from_str::<ClientConfig>(c).unwrap_err().to_string();
+
+		assert!(r.contains(e), "{r}");
+	}
+	for (c, e) in [
+		(r#"{"max_concurrency": 0}"#, "invalid max_concurrency"),
+		(r#"{"max_concurrent_broadcasts": 0}"#, "invalid max_concurrent_broadcasts"),
+		(r#"{"max_broadcast_size": 0}"#, "invalid max_broadcast_size"),
+		(r#"{"base_uris": ["https://ep.atomicals.xyz/proxy", ""]}"#, "invalid base_uris"),
+		(r#"{"proxy": "not a proxy"}"#, "invalid proxy"),
+		(r#"{"request_fields": {"params": []}}"#, "invalid request_fields"),
+		(
+			r#"{"backoff": {"kind": "exponential", "base_ms": 1, "factor": 0, "max_ms": 1}}"#,
+			"invalid backoff",
+		),
+	] {
+		let r = ElectrumXBuilder::from_config(serde_json::from_str(c).unwrap())
+			.unwrap_err()
+			.to_string();
+
+		assert!(r.contains(e), "{r}");
+	}
+}
@@ -0,0 +1,115 @@
+// std
+use std::time::Duration;
+// crates.io
+use bitcoin::{address::ParseError as AddressParseError, Network, OutPoint, Txid};
+use thiserror::Error as ThisError;
+// atomicalsir
+use crate::electrumx::r#type::FeeRate;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+	#[error("{0} not found")]
+	NotFound(String),
+	#[error("server rejected the request with code {code}: {message}")]
+	Rpc { code: i64, message: String },
+	#[error("atomicals rejected the request, {0}: {1}")]
+	Atomicals(AtomicalsError, String),
+	#[error("invalid base URI {uri}: {reason}")]
+	InvalidBaseUri { uri: String, reason: String },
+	#[error("no default base URI for {0}, set one explicitly")]
+	NoDefaultBaseUri(Network),
+	#[error("only {healthy} base URIs are healthy but {required} are required")]
+	NotEnoughHealthy { healthy: usize, required: usize },
+	#[error("invalid address {address}: {source}")]
+	InvalidAddress { address: String, source: AddressParseError },
+	#[error("address belongs to {found} but {expected} is expected")]
+	AddressWrongNetwork { expected: Network, found: Network },
+	#[error("transaction hex of {size} bytes exceeds the broadcast limit of {limit} bytes")]
+	TransactionTooLarge { size: usize, limit: usize },
+	#[error("server returned txid {got} but the broadcast transaction is {expected}")]
+	TxidMismatch { expected: Txid, got: String },
+	#[error(
+		"inputs of transaction {txid} are unknown on {network}, is it meant for another network?"
+	)]
+	NetworkMismatch { network: Network, txid: Txid },
+	#[error("no other backend saw transaction {txid} within {window:?} of the broadcast")]
+	BroadcastUnconfirmed { txid: Txid, window: Duration },
+	#[error("{uri} redirected to {location:?}, update the base URI")]
+	Redirected { uri: String, location: String },
+	#[error("all base URIs exhausted, {cause}, the last errors: {errors:?}")]
+	AllUrisExhausted { cause: Exhaustion, errors: Vec<(String, String)> },
+	#[error("transaction {txid} does not satisfy the bitwork {bitwork}")]
+	BitworkNotSatisfied { txid: Txid, bitwork: String },
+	#[error("transaction {txid} left block {blockhash}, it was reorged out")]
+	ReorgDetected { txid: String, blockhash: String },
+	#[error("{atomical_id} was not indexed within {timeout:?}")]
+	NotIndexed { atomical_id: String, timeout: Duration },
+	#[error("UTXO {0} was spent meanwhile")]
+	UtxoSpent(OutPoint),
+	#[error("commit output {0} is not found unspent, the commit did not propagate")]
+	CommitNotFound(OutPoint),
+	#[error(
+		"{amount} more sats would exceed the spend cap of {limit} sats, {spent} sats are spent"
+	)]
+	SpendCapExceeded { spent: u64, amount: u64, limit: u64 },
+	#[error("transaction {txid} pays {fee_rate}, above the sanity ceiling of {max}")]
+	FeeRateAbsurd { txid: Txid, fee_rate: FeeRate, max: FeeRate },
+	#[error("{needed} sats are needed but only {available} sats are available")]
+	InsufficientFunds { needed: u64, available: u64 },
+	#[error("{stage} mining aborted, {reason}")]
+	MiningAborted { stage: String, reason: String },
+}
+impl Error {
+	/// Whether the node, or the pre-broadcast checks, refused the request for sure, so it never
+	/// took effect.
+	pub fn is_rejection(&self) -> bool {
+		matches!(
+			self,
+			Self::Rpc { .. }
+				| Self::Atomicals(..)
+				| Self::TransactionTooLarge { .. }
+				| Self::NetworkMismatch { .. }
+				| Self::FeeRateAbsurd { .. }
+		)
+	}
+}
+
+/// Why every base URI failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ThisError)]
+pub enum Exhaustion {
+	#[error("none of them could be reached, the proxies seem down")]
+	Unreachable,
+	#[error("they failed in different ways")]
+	Mixed,
+}
+
+/// Atomicals specific rejections, which won't go away by retrying.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ThisError)]
+pub enum AtomicalsError {
+	#[error("ticker already exists")]
+	TickerExists,
+	#[error("mint closed")]
+	MintClosed,
+	#[error("mint not started yet")]
+	MintNotStarted,
+	#[error("invalid bitwork")]
+	InvalidBitwork,
+}
+impl AtomicalsError {
+	pub fn from_message(message: &str) -> Option<Self> {
+		let m = message.to_lowercase();
+
+		if m.contains("ticker") && (m.contains("already exists") || m.contains("already claimed")) {
+			Some(Self::TickerExists)
+		} else if m.contains("mint closed") || m.contains("max mints") || m.contains("fully minted")
+		{
+			Some(Self::MintClosed)
+		} else if m.contains("mint_height") {
+			Some(Self::MintNotStarted)
+		} else if m.contains("bitwork") {
+			Some(Self::InvalidBitwork)
+		} else {
+			None
+		}
+	}
+}
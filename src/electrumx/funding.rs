@@ -0,0 +1,278 @@
+// std
+use std::collections::{HashSet, VecDeque};
+// crates.io
+use futures::{stream, Stream};
+// atomicalsir
+use crate::{
+	electrumx::{r#type::Utxo, Api},
+	prelude::*,
+};
+
+/// Funding addresses with a quota of UTXOs each, consumed in a round-robin way.
+///
+/// A UTXO is handed out once, even if it is still unspent when the pool is polled again.
+#[derive(Debug)]
+pub struct FundingPool {
+	addresses: Vec<(String, usize)>,
+	consumed: Vec<usize>,
+	cursor: usize,
+	reserved: HashSet<(String, u32)>,
+}
+impl FundingPool {
+	pub fn new<I, S>(addresses: I) -> Self
+	where
+		I: IntoIterator<Item = (S, usize)>,
+		S: Into<String>,
+	{
+		let addresses = addresses.into_iter().map(|(a, q)| (a.into(), q)).collect::<Vec<_>>();
+
+		Self {
+			consumed: vec![0; addresses.len()],
+			addresses,
+			cursor: 0,
+			reserved: Default::default(),
+		}
+	}
+
+	/// Wait for a funding UTXO of at least `satoshis`.
+	///
+	/// Returns the index of the address it was drawn from, starting the search right after the
+	/// previous one.
+	pub async fn next<A>(&mut self, api: &A, satoshis: u64) -> Result<(usize, Utxo)>
+	where
+		A: Api,
+	{
+		let candidates = self.candidates();
+
+		if candidates.is_empty() {
+			Err(anyhow::anyhow!("all funding addresses reached their quotas"))?;
+		}
+
+		let (i, u) = api
+			.wait_until_funding_matching(
+				&candidates.iter().map(|&i| &self.addresses[i].0).collect::<Vec<_>>(),
+				|u| {
+					u.atomicals.is_empty()
+						&& u.value >= satoshis
+						&& !self.reserved.contains(&(u.txid.clone(), u.vout))
+				},
+			)
+			.await?;
+		let i = candidates[i];
+
+		self.reserved.insert((u.txid.clone(), u.vout));
+		self.consumed[i] += 1;
+		self.cursor = (i + 1) % self.addresses.len();
+
+		Ok((i, u))
+	}
+
+	fn candidates(&self) -> Vec<usize> {
+		(0..self.addresses.len())
+			.map(|i| (self.cursor + i) % self.addresses.len())
+			.filter(|&i| self.consumed[i] < self.addresses[i].1)
+			.collect()
+	}
+}
+#[test]
+fn candidates_should_work() {
+	let mut p = FundingPool::new([("a", 1), ("b", 2), ("c", 0)]);
+
+	assert_eq!(p.candidates(), [0, 1]);
+
+	p.consumed[0] = 1;
+	p.cursor = 1;
+
+	assert_eq!(p.candidates(), [1]);
+
+	p.consumed[1] = 1;
+	p.cursor = 2;
+
+	assert_eq!(p.candidates(), [1]);
+}
+
+/// Watch a funding address and only report the qualifying UTXOs which weren't reported before.
+#[derive(Debug)]
+pub struct FundingScanner {
+	address: String,
+	satoshis: u64,
+	seen: HashSet<(String, u32)>,
+}
+impl FundingScanner {
+	pub fn new<S>(address: S, satoshis: u64) -> Self
+	where
+		S: Into<String>,
+	{
+		Self { address: address.into(), satoshis, seen: Default::default() }
+	}
+
+	/// Poll until there is at least one new clean UTXO of at least `satoshis`.
+	pub async fn scan<A>(&mut self, api: &A) -> Result<Vec<Utxo>>
+	where
+		A: Api,
+	{
+		let min_confirmations = api.funding_min_confirmations();
+
+		loop {
+			let tip = if min_confirmations > 0 { api.get_block_height().await? } else { 0 };
+			let utxos = api
+				.get_unspent_address(&self.address)
+				.await?
+				.into_iter()
+				.filter(|u| {
+					u.atomicals.is_empty()
+						&& u.value >= self.satoshis
+						&& u.confirmations(tip) >= min_confirmations
+						&& self.seen.insert((u.txid.clone(), u.vout))
+				})
+				.collect::<Vec<_>>();
+
+			if !utxos.is_empty() {
+				return Ok(utxos);
+			}
+
+			api.clock().sleep(api.poll_interval()).await;
+		}
+	}
+
+	/// An endless stream of the newly available UTXOs.
+	pub fn into_stream<A>(self, api: &A) -> impl Stream<Item = Result<Utxo>> + '_
+	where
+		A: Api,
+	{
+		stream::unfold((self, VecDeque::new()), move |(mut s, mut q)| async move {
+			if q.is_empty() {
+				match s.scan(api).await {
+					Ok(us) => q.extend(us),
+					Err(e) => return Some((Err(e), (s, q))),
+				}
+			}
+
+			let u = q.pop_front()?;
+
+			Some((Ok(u), (s, q)))
+		})
+	}
+}
+
+/// UTXOs matched to the expected amounts, e.g. the equal outputs of a split transaction.
+#[derive(Debug, Default)]
+pub struct FundingMatch {
+	/// The UTXO of each expected amount, in the same order, if one was found.
+	pub matched: Vec<Option<Utxo>>,
+	/// The clean UTXOs which were not matched to any amount.
+	pub leftover: Vec<Utxo>,
+}
+impl FundingMatch {
+	pub fn is_complete(&self) -> bool {
+		self.matched.iter().all(Option::is_some)
+	}
+}
+
+/// Match each expected amount to a distinct clean UTXO of exactly that value.
+///
+/// Every UTXO is handed out at most once, so duplicated amounts get one UTXO each and two tasks
+/// never grab the same one.
+#[derive(Debug)]
+pub struct FundingMatcher {
+	expected: Vec<u64>,
+	txid: Option<String>,
+}
+impl FundingMatcher {
+	pub fn new<I>(expected: I) -> Self
+	where
+		I: IntoIterator<Item = u64>,
+	{
+		Self { expected: expected.into_iter().collect(), txid: None }
+	}
+
+	/// Only match the outputs of the transaction `txid`, e.g. the split transaction.
+	pub fn of_tx<S>(mut self, txid: S) -> Self
+	where
+		S: Into<String>,
+	{
+		self.txid = Some(txid.into());
+
+		self
+	}
+
+	pub fn match_utxos(&self, utxos: &[Utxo]) -> FundingMatch {
+		let mut leftover = utxos
+			.iter()
+			.filter(|u| u.atomicals.is_empty() && self.txid.as_ref().is_none_or(|t| *t == u.txid))
+			.cloned()
+			.collect::<Vec<_>>();
+		let matched = self
+			.expected
+			.iter()
+			.map(|&v| leftover.iter().position(|u| u.value == v).map(|i| leftover.remove(i)))
+			.collect();
+
+		FundingMatch { matched, leftover }
+	}
+
+	/// Poll the address until every expected amount is matched.
+	pub async fn wait<A>(&self, api: &A, address: &str) -> Result<FundingMatch>
+	where
+		A: Api,
+	{
+		let min_confirmations = api.funding_min_confirmations();
+
+		loop {
+			let tip = if min_confirmations > 0 { api.get_block_height().await? } else { 0 };
+			let utxos = api
+				.get_unspent_address(address)
+				.await?
+				.into_iter()
+				.filter(|u| u.confirmations(tip) >= min_confirmations)
+				.collect::<Vec<_>>();
+			let m = self.match_utxos(&utxos);
+
+			if m.is_complete() {
+				return Ok(m);
+			}
+
+			tracing::info!(
+				"waiting for {} of {} expected funding UTXOs...",
+				m.matched.iter().filter(|u| u.is_none()).count(),
+				self.expected.len()
+			);
+
+			api.clock().sleep(api.poll_interval()).await;
+		}
+	}
+}
+#[test]
+fn match_utxos_should_work() {
+	let utxos = [1_000, 5_000, 1_000, 2_000, 1_000]
+		.into_iter()
+		.enumerate()
+		.map(|(i, value)| Utxo {
+			txid: format!("{i:064x}"),
+			vout: 0,
+			value,
+			height: 0,
+			// The last one is not clean.
+			atomicals: if i == 4 { vec![format!("{:064x}i0", 0)] } else { Vec::new() },
+			atomical_values: Default::default(),
+		})
+		.collect::<Vec<_>>();
+	let m = FundingMatcher::new([1_000, 2_000, 1_000, 1_000]).match_utxos(&utxos);
+
+	assert!(!m.is_complete());
+	assert_eq!(
+		m.matched.iter().map(|u| u.as_ref().map(|u| u.txid.clone())).collect::<Vec<_>>(),
+		[
+			Some(format!("{:064x}", 0)),
+			Some(format!("{:064x}", 3)),
+			Some(format!("{:064x}", 2)),
+			None
+		]
+	);
+	assert_eq!(m.leftover.iter().map(|u| u.value).collect::<Vec<_>>(), [5_000]);
+
+	let m = FundingMatcher::new([1_000]).of_tx(format!("{:064x}", 2)).match_utxos(&utxos);
+
+	assert_eq!(m.matched[0].as_ref().map(|u| u.txid.clone()), Some(format!("{:064x}", 2)));
+	assert!(m.leftover.is_empty());
+}
@@ -0,0 +1,102 @@
+// std
+use std::{
+	collections::{hash_map::Entry, HashMap},
+	sync::{Arc, Mutex},
+};
+// crates.io
+use bitcoin::Network;
+// atomicalsir
+use crate::{
+	electrumx::{ClientOptions, ElectrumX, ElectrumXBuilder},
+	prelude::*,
+};
+
+// The proxy and the timeouts are part of the connection pool itself, the spend cap and the
+// recording are state of the client.
+type Key = (Network, Vec<String>, ClientOptions, Option<u64>, bool);
+
+/// Hands out the same [`ElectrumX`] for the same network, base URIs, connection options, spend
+/// cap and recording.
+///
+/// So tools managing many wallets share a single connection pool, concurrency limit and the
+/// stats of each base URI, instead of building a client per wallet. A builder which differs in
+/// any of these gets a client of its own.
+#[derive(Debug, Default)]
+pub struct ClientRegistry {
+	clients: Mutex<HashMap<Key, Arc<ElectrumX>>>,
+}
+impl ClientRegistry {
+	/// The client matching the `builder`, built from it on first use or once the previous one
+	/// has been shut down.
+	///
+	/// Meant for builders set up alike, the behavior of a shared client, e.g. its retries, is
+	/// the one of the builder which created it.
+	pub fn get(&self, builder: ElectrumXBuilder) -> Result<Arc<ElectrumX>> {
+		let key = (
+			builder.network,
+			builder.resolve_base_uris()?,
+			builder.client_options(),
+			builder.spend_cap,
+			builder.record,
+		);
+
+		Ok(match self.clients.lock().unwrap().entry(key) {
+			Entry::Occupied(e) if !e.get().is_closed() => e.get().clone(),
+			Entry::Occupied(mut e) => {
+				e.insert(Arc::new(builder.build()?));
+
+				e.get().clone()
+			},
+			Entry::Vacant(e) => e.insert(Arc::new(builder.build()?)).clone(),
+		})
+	}
+
+	/// Shut down every client handed out which isn't closed yet, see [`ElectrumX::shutdown`].
+	pub async fn shutdown(&self) {
+		let clients = self.clients.lock().unwrap().values().cloned().collect::<Vec<_>>();
+
+		for c in clients.iter().filter(|c| !c.is_closed()) {
+			c.shutdown().await;
+		}
+	}
+}
+#[test]
+fn get_should_share_clients() {
+	let r = ClientRegistry::default();
+	let a = r.get(ElectrumXBuilder::default()).unwrap();
+
+	// The same base URIs, once normalized.
+	assert!(Arc::ptr_eq(
+		&a,
+		&r.get(ElectrumXBuilder::default().base_uris("https://ep.atomicals.xyz/proxy/")).unwrap()
+	));
+	assert!(!Arc::ptr_eq(&a, &r.get(ElectrumXBuilder::testnet()).unwrap()));
+	assert!(!Arc::ptr_eq(
+		&a,
+		&r.get(ElectrumXBuilder::default().base_uris("https://ep2.atomicals.xyz/proxy")).unwrap()
+	));
+	assert!(!Arc::ptr_eq(
+		&a,
+		&r.get(ElectrumXBuilder::default().proxy("http://127.0.0.1:8080")).unwrap()
+	));
+	assert!(!Arc::ptr_eq(&a, &r.get(ElectrumXBuilder::default().spend_cap(1)).unwrap()));
+}
+#[tokio::test]
+async fn get_should_rebuild_closed_clients() {
+	let r = ClientRegistry::default();
+	let a = r.get(ElectrumXBuilder::default()).unwrap();
+
+	a.shutdown().await;
+
+	assert!(!Arc::ptr_eq(&a, &r.get(ElectrumXBuilder::default()).unwrap()));
+}
+#[tokio::test]
+async fn shutdown_should_close_every_client() {
+	let r = ClientRegistry::default();
+	let (a, b) =
+		(r.get(ElectrumXBuilder::default()).unwrap(), r.get(ElectrumXBuilder::testnet()).unwrap());
+
+	r.shutdown().await;
+
+	assert!(a.is_closed() && b.is_closed());
+}
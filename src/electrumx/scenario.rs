@@ -0,0 +1,119 @@
+// std
+use std::{
+	collections::HashMap,
+	fs::{self, File},
+	path::Path,
+	sync::Mutex,
+};
+// crates.io
+use bitcoin::Network;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+// atomicalsir
+use crate::{
+	electrumx::{error_of, Config, Http},
+	prelude::*,
+};
+
+/// A request and the response which was handed back to the caller.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Exchange {
+	pub method: String,
+	pub request: Value,
+	pub response: String,
+}
+
+/// A self-contained record of a run: the configuration, the traffic and the outcome.
+///
+/// Load it to replay the exact same responses offline, as a drop-in [`Config`] and [`Http`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Scenario {
+	#[serde(serialize_with = "serialize_network", deserialize_with = "deserialize_network")]
+	pub network: Network,
+	pub base_uris: Vec<String>,
+	pub exchanges: Vec<Exchange>,
+	pub outcome: Result<(), String>,
+	// How many of the exchanges of each method were replayed.
+	#[serde(skip)]
+	replayed: Mutex<HashMap<String, usize>>,
+}
+impl Scenario {
+	pub fn new(
+		network: Network,
+		base_uris: Vec<String>,
+		exchanges: Vec<Exchange>,
+		outcome: Result<(), String>,
+	) -> Self {
+		Self { network, base_uris, exchanges, outcome, replayed: Default::default() }
+	}
+
+	pub fn load<P>(path: P) -> Result<Self>
+	where
+		P: AsRef<Path>,
+	{
+		Ok(serde_json::from_reader(File::open(path)?)?)
+	}
+
+	pub fn save<P>(&self, path: P) -> Result<()>
+	where
+		P: AsRef<Path>,
+	{
+		Ok(fs::write(path, serde_json::to_vec_pretty(self)?)?)
+	}
+}
+impl Config for Scenario {
+	fn network(&self) -> &Network {
+		&self.network
+	}
+
+	fn base_uris(&self) -> &[String] {
+		&self.base_uris
+	}
+}
+impl Http for Scenario {
+	async fn post<U, P, R>(&self, uri: U, _: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		let method = uri.as_ref();
+		let response = {
+			let mut replayed = self.replayed.lock().unwrap();
+			let n = replayed.entry(method.into()).or_default();
+			let e =
+				self.exchanges.iter().filter(|e| e.method == method).nth(*n).ok_or_else(|| {
+					anyhow::anyhow!("scenario has no more responses for {method}")
+				})?;
+
+			*n += 1;
+
+			&e.response
+		};
+
+		serde_json::from_str(response).map_err(|e| error_of(response, e))
+	}
+
+	async fn post_to<U, P, R>(&self, _: &str, uri: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		self.post(uri, params).await
+	}
+}
+
+fn serialize_network<S>(network: &Network, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+{
+	serializer.collect_str(network)
+}
+
+fn deserialize_network<'de, D>(deserializer: D) -> Result<Network, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,147 @@
+// std
+use std::cmp::Reverse;
+// atomicalsir
+use crate::electrumx::r#type::Utxo;
+
+/// How to pick the UTXOs funding a given amount.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UtxoSelection {
+	/// Minimizes the number of inputs.
+	LargestFirst,
+	/// Cleans up the dust.
+	#[default]
+	SmallestFirst,
+	/// Minimizes the change, preferring a single UTXO which covers the amount on its own.
+	ClosestMatch,
+}
+impl UtxoSelection {
+	/// Order the clean UTXOs by preference for funding `satoshis`.
+	///
+	/// UTXOs carrying atomicals are left out, spending them would burn the atomicals.
+	pub fn sort(self, utxos: &[Utxo], satoshis: u64) -> Vec<Utxo> {
+		let mut utxos =
+			utxos.iter().filter(|u| u.atomicals.is_empty()).cloned().collect::<Vec<_>>();
+
+		match self {
+			Self::LargestFirst => utxos.sort_by_key(|u| Reverse(u.value)),
+			Self::SmallestFirst => utxos.sort_by_key(|u| u.value),
+			// The ones covering the amount by the least first, then the others largest first.
+			Self::ClosestMatch => utxos.sort_by_key(|u| {
+				if u.value >= satoshis {
+					(false, u.value - satoshis)
+				} else {
+					(true, satoshis - u.value)
+				}
+			}),
+		}
+
+		utxos
+	}
+
+	/// Take the UTXOs in the order of [`UtxoSelection::sort`] until they sum up to `satoshis`.
+	///
+	/// Returns `None` if all of them together are not enough.
+	pub fn select(self, utxos: &[Utxo], satoshis: u64) -> Option<Vec<Utxo>> {
+		let mut sum = 0;
+		let mut selected = Vec::new();
+
+		for u in self.sort(utxos, satoshis) {
+			if sum >= satoshis {
+				break;
+			}
+
+			sum += u.value;
+
+			selected.push(u);
+		}
+
+		(sum >= satoshis).then_some(selected)
+	}
+}
+
+/// Picks the UTXOs funding a given amount, for custom coin selection policies.
+///
+/// Implemented by [`UtxoSelection`] and by closures of the same signature as
+/// [`CoinSelector::select`].
+pub trait CoinSelector {
+	/// A subset of the clean `utxos` funding `satoshis`, `None` if they are not enough.
+	fn select(&self, utxos: &[Utxo], satoshis: u64) -> Option<Vec<Utxo>>;
+}
+impl CoinSelector for UtxoSelection {
+	fn select(&self, utxos: &[Utxo], satoshis: u64) -> Option<Vec<Utxo>> {
+		UtxoSelection::select(*self, utxos, satoshis)
+	}
+}
+impl<F> CoinSelector for F
+where
+	F: Fn(&[Utxo], u64) -> Option<Vec<Utxo>>,
+{
+	fn select(&self, utxos: &[Utxo], satoshis: u64) -> Option<Vec<Utxo>> {
+		self(utxos, satoshis)
+	}
+}
+#[cfg(test)]
+fn values_of(utxos: &[Utxo]) -> Vec<u64> {
+	utxos.iter().map(|u| u.value).collect()
+}
+#[cfg(test)]
+fn utxos() -> Vec<Utxo> {
+	[3_000, 500, 12_000, 7_000, 1_000, 9_000]
+		.into_iter()
+		.enumerate()
+		.map(|(i, value)| Utxo {
+			txid: format!("{i:064x}"),
+			vout: 0,
+			value,
+			height: 0,
+			// The largest one is not clean.
+			atomicals: if value == 12_000 { vec![format!("{:064x}i0", 0)] } else { Vec::new() },
+			atomical_values: Default::default(),
+		})
+		.collect()
+}
+#[test]
+fn largest_first_should_work() {
+	let s = UtxoSelection::LargestFirst;
+
+	assert_eq!(values_of(&s.sort(&utxos(), 8_000)), [9_000, 7_000, 3_000, 1_000, 500]);
+	assert_eq!(values_of(&s.select(&utxos(), 8_000).unwrap()), [9_000]);
+	assert_eq!(values_of(&s.select(&utxos(), 10_000).unwrap()), [9_000, 7_000]);
+}
+#[test]
+fn smallest_first_should_work() {
+	let s = UtxoSelection::SmallestFirst;
+
+	assert_eq!(values_of(&s.sort(&utxos(), 8_000)), [500, 1_000, 3_000, 7_000, 9_000]);
+	assert_eq!(values_of(&s.select(&utxos(), 4_000).unwrap()), [500, 1_000, 3_000]);
+	assert!(s.select(&utxos(), 20_501).is_none());
+}
+#[test]
+fn closest_match_should_work() {
+	let s = UtxoSelection::ClosestMatch;
+
+	assert_eq!(values_of(&s.sort(&utxos(), 6_500)), [7_000, 9_000, 3_000, 1_000, 500]);
+	assert_eq!(values_of(&s.select(&utxos(), 6_500).unwrap()), [7_000]);
+	assert_eq!(values_of(&s.select(&utxos(), 10_000).unwrap()), [9_000, 7_000]);
+	assert_eq!(values_of(&s.select(&utxos(), 20_500).unwrap()), [9_000, 7_000, 3_000, 1_000, 500]);
+}
+#[test]
+fn coin_selector_should_work() {
+	fn select<C>(selector: C, satoshis: u64) -> Option<Vec<u64>>
+	where
+		C: CoinSelector,
+	{
+		selector.select(&utxos(), satoshis).map(|us| values_of(&us))
+	}
+
+	assert_eq!(select(UtxoSelection::default(), 1_200), Some(vec![500, 1_000]));
+	// Only spend the UTXOs above the dust.
+	assert_eq!(
+		select(
+			|us: &[Utxo], sats| UtxoSelection::LargestFirst
+				.select(&us.iter().filter(|u| u.value >= 1_000).cloned().collect::<Vec<_>>(), sats),
+			20_500
+		),
+		None
+	);
+}
@@ -0,0 +1,136 @@
+// std
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Mutex,
+};
+// crates.io
+use bitcoin::{OutPoint, Transaction, TxOut, Txid};
+// atomicalsir
+use crate::electrumx::error::Error;
+
+/// A hard cap on the satoshis a session may spend across all of its broadcasts.
+///
+/// A transaction spends its fee plus whatever it pays to scripts other than the ones of its
+/// inputs, the change doesn't count. Spending an output which was already paid for, e.g. a
+/// reveal spending its commit, is free, so is broadcasting the same transaction again.
+#[derive(Debug)]
+pub struct SpendCap {
+	limit: u64,
+	state: Mutex<State>,
+}
+#[derive(Debug, Default)]
+struct State {
+	spent: u64,
+	charged: HashSet<Txid>,
+	// Outputs of the charged transactions which were counted as spent.
+	paid: HashSet<OutPoint>,
+}
+impl SpendCap {
+	pub fn new(limit: u64) -> Self {
+		Self { limit, state: Default::default() }
+	}
+
+	pub fn spent(&self) -> u64 {
+		self.state.lock().unwrap().spent
+	}
+
+	/// The inputs of `tx` whose previous outputs [`SpendCap::charge`] needs.
+	pub fn inputs_to_price(&self, tx: &Transaction) -> Vec<OutPoint> {
+		let s = self.state.lock().unwrap();
+
+		if s.charged.contains(&tx.txid()) {
+			return Vec::new();
+		}
+
+		tx.input.iter().map(|i| i.previous_output).filter(|o| !s.paid.contains(o)).collect()
+	}
+
+	/// Count `tx` against the cap, returns the satoshis it spends.
+	///
+	/// `prevouts` holds the previous outputs of [`SpendCap::inputs_to_price`].
+	pub fn charge(
+		&self,
+		tx: &Transaction,
+		prevouts: &HashMap<OutPoint, TxOut>,
+	) -> Result<u64, Error> {
+		let mut s = self.state.lock().unwrap();
+		let txid = tx.txid();
+
+		if s.charged.contains(&txid) {
+			return Ok(0);
+		}
+
+		let inputs = tx
+			.input
+			.iter()
+			.map(|i| i.previous_output)
+			.filter(|o| !s.paid.contains(o))
+			.map(|o| {
+				prevouts.get(&o).ok_or_else(|| Error::NotFound(format!("previous output {o}")))
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		let is_change = |o: &TxOut| inputs.iter().any(|i| i.script_pubkey == o.script_pubkey);
+		let change =
+			tx.output.iter().filter(|o| is_change(o)).map(|o| o.value.to_sat()).sum::<u64>();
+		let amount = inputs.iter().map(|i| i.value.to_sat()).sum::<u64>().saturating_sub(change);
+
+		if s.spent + amount > self.limit {
+			Err(Error::SpendCapExceeded { spent: s.spent, amount, limit: self.limit })?;
+		}
+
+		s.spent += amount;
+		s.charged.insert(txid);
+
+		for (i, o) in tx.output.iter().enumerate() {
+			if !is_change(o) {
+				s.paid.insert(OutPoint::new(txid, i as _));
+			}
+		}
+
+		Ok(amount)
+	}
+}
+#[test]
+fn spend_cap_should_work() {
+	// crates.io
+	use bitcoin::{
+		absolute::LockTime, hashes::Hash, transaction::Version, Amount, ScriptBuf, TxIn,
+	};
+
+	let script = |b| ScriptBuf::from(vec![b; 34]);
+	let out = |value, b| TxOut { value: Amount::from_sat(value), script_pubkey: script(b) };
+	let tx = |inputs: &[OutPoint], output| Transaction {
+		version: Version::TWO,
+		lock_time: LockTime::ZERO,
+		input: inputs.iter().map(|&o| TxIn { previous_output: o, ..Default::default() }).collect(),
+		output,
+	};
+	let cap = SpendCap::new(10_000);
+	let funding = OutPoint::new(Txid::all_zeros(), 0);
+	// 6,000 to the reveal, 1,000 of fee and 93,000 of change.
+	let commit = tx(&[funding], vec![out(6_000, 1), out(93_000, 0)]);
+	let prevouts = HashMap::from([(funding, out(100_000, 0))]);
+
+	assert_eq!(cap.inputs_to_price(&commit), [funding]);
+	assert_eq!(cap.charge(&commit, &prevouts).unwrap(), 7_000);
+	// A rebroadcast.
+	assert!(cap.inputs_to_price(&commit).is_empty());
+	assert_eq!(cap.charge(&commit, &prevouts).unwrap(), 0);
+
+	// The reveal was paid by the commit.
+	let reveal = tx(&[OutPoint::new(commit.txid(), 0)], vec![out(5_000, 2)]);
+
+	assert!(cap.inputs_to_price(&reveal).is_empty());
+	assert_eq!(cap.charge(&reveal, &HashMap::new()).unwrap(), 0);
+
+	// The change is spent again.
+	let change = OutPoint::new(commit.txid(), 1);
+	let commit = tx(&[change], vec![out(6_000, 1), out(86_000, 0)]);
+	let prevouts = HashMap::from([(change, out(93_000, 0))]);
+
+	assert!(matches!(
+		cap.charge(&commit, &prevouts),
+		Err(Error::SpendCapExceeded { spent: 7_000, amount: 7_000, limit: 10_000 })
+	));
+	assert_eq!(cap.spent(), 7_000);
+}
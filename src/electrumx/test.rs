@@ -1,6 +1,193 @@
+// std
+use std::{
+	collections::VecDeque,
+	io::{Read, Write},
+	net::TcpListener,
+	thread,
+};
+// crates.io
+use bitcoin::hashes::Hash;
+use serde_json::{json, Value};
 // atomicalsir
 use super::*;
 
+// Serves canned responses per method, in order.
+#[derive(Debug, Default)]
+struct MockApi {
+	clock: clock::TestClock,
+	responses: Mutex<HashMap<String, VecDeque<Value>>>,
+	base_uris: Vec<String>,
+	broadcast_verify_window: Option<Duration>,
+	max_fee_rate: Option<FeeRate>,
+	funding_min_confirmations: u64,
+	poll_interval: Option<Duration>,
+}
+impl MockApi {
+	fn respond<S>(self, method: S, response: Value) -> Self
+	where
+		S: Into<String>,
+	{
+		self.responses
+			.lock()
+			.unwrap()
+			.entry(method.into())
+			.or_default()
+			.push_back(json!({ "success": true, "response": response }));
+
+		self
+	}
+}
+impl Config for MockApi {
+	fn network(&self) -> &Network {
+		&Network::Testnet
+	}
+
+	fn base_uris(&self) -> &[String] {
+		&self.base_uris
+	}
+
+	fn clock(&self) -> &dyn Clock {
+		&self.clock
+	}
+
+	fn broadcast_verify_window(&self) -> Option<Duration> {
+		self.broadcast_verify_window
+	}
+
+	fn max_fee_rate(&self) -> Option<FeeRate> {
+		self.max_fee_rate
+	}
+
+	fn funding_min_confirmations(&self) -> u64 {
+		self.funding_min_confirmations
+	}
+
+	fn poll_interval(&self) -> Duration {
+		self.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL)
+	}
+}
+impl Http for MockApi {
+	async fn post<U, P, R>(&self, uri: U, _: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		let r = self
+			.responses
+			.lock()
+			.unwrap()
+			.get_mut(uri.as_ref())
+			.and_then(VecDeque::pop_front)
+			.ok_or_else(|| anyhow::anyhow!("no response for {}", uri.as_ref()))?;
+
+		Ok(serde_json::from_value(r)?)
+	}
+
+	async fn post_to<U, P, R>(&self, _: &str, uri: U, params: P) -> Result<R>
+	where
+		U: AsRef<str>,
+		P: Serialize,
+		R: DeserializeOwned,
+	{
+		self.post(uri, params).await
+	}
+}
+
+// Serve `body` with HTTP 200 after `delay` to every request, returns the base URI.
+fn serve(delay: Duration, body: &'static str) -> String {
+	serve_all(delay, vec![body])
+}
+
+// Like `serve` but the n-th request gets the n-th body, the last one is repeated.
+fn serve_all(delay: Duration, bodies: Vec<&'static str>) -> String {
+	serve_raw(
+		delay,
+		bodies
+			.into_iter()
+			.map(|b| {
+				format!(
+					"HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{b}",
+					b.len()
+				)
+			})
+			.collect(),
+	)
+}
+
+// Like `serve_all` but with the whole HTTP responses, status line and headers included.
+fn serve_raw(delay: Duration, responses: Vec<String>) -> String {
+	let l = TcpListener::bind("127.0.0.1:0").unwrap();
+	let uri = format!("http://{}", l.local_addr().unwrap());
+
+	thread::spawn(move || {
+		for (i, mut s) in l.incoming().flatten().enumerate() {
+			let response = responses[i.min(responses.len() - 1)].clone();
+
+			thread::spawn(move || {
+				let mut req = Vec::new();
+				let mut buf = [0; 1024];
+
+				// Read the whole request, so the client doesn't see a reset.
+				while let Ok(n @ 1..) = s.read(&mut buf) {
+					req.extend_from_slice(&buf[..n]);
+
+					let req = String::from_utf8_lossy(&req);
+
+					if let Some((head, body)) = req.split_once("\r\n\r\n") {
+						let len = head
+							.lines()
+							.find_map(|l| {
+								l.to_lowercase()
+									.strip_prefix("content-length:")?
+									.trim()
+									.parse()
+									.ok()
+							})
+							.unwrap_or(0);
+
+						if body.len() >= len {
+							break;
+						}
+					}
+				}
+
+				thread::sleep(delay);
+
+				let _ = s.write_all(response.as_bytes());
+			});
+		}
+	});
+
+	uri
+}
+
+// A minimal transaction spending a single input.
+fn tx() -> Transaction {
+	Transaction {
+		version: bitcoin::transaction::Version::TWO,
+		lock_time: bitcoin::absolute::LockTime::ZERO,
+		input: vec![Default::default()],
+		output: vec![bitcoin::TxOut {
+			value: bitcoin::Amount::from_sat(546),
+			script_pubkey: Default::default(),
+		}],
+	}
+}
+
+fn unspent(txid: &str, vout: u32, value: u64) -> Value {
+	json!({
+		"txid": txid,
+		"tx_hash": txid,
+		"index": vout,
+		"tx_pos": vout,
+		"vout": vout,
+		"height": 0,
+		"value": value,
+		"atomicals": [],
+	})
+}
+
 #[tokio::test]
 async fn get_by_ticker_should_work() {
 	let _ = tracing_subscriber::fmt::try_init();
@@ -28,3 +215,1561 @@ async fn get_unspent_address_should_work() {
 		.await
 		.unwrap();
 }
+
+#[test]
+fn null_result_should_be_not_found() {
+	let r = serde_json::from_str::<Response<ResponseResult<Option<Ticker>>>>(
+		r#"{"success":true,"response":{"result":null}}"#,
+	)
+	.unwrap();
+
+	assert!(matches!(r.response.found("quark"), Err(error::Error::NotFound(t)) if t == "quark"));
+}
+
+#[tokio::test]
+async fn atomical_id_of_should_require_it_indexed() {
+	let txid = Txid::all_zeros();
+	let id = format!("{txid}i0");
+	let e =
+		MockApi::default().respond("blockchain.atomicals.get", json!({ "result": null })).respond(
+			"blockchain.atomicals.get",
+			json!({ "result": { "atomical_id": id, "atomical_number": 1, "type": "NFT" } }),
+		);
+
+	assert!(matches!(
+		e.atomical_id_of(&txid, 0).await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::NotFound(i)) if i == id
+	));
+	assert_eq!(e.atomical_id_of(&txid, 0).await.unwrap(), id);
+}
+
+#[tokio::test]
+async fn broadcast_should_reject_oversized_tx() {
+	let e = ElectrumXBuilder::testnet().max_broadcast_size(4).build().unwrap();
+
+	assert!(matches!(
+		e.broadcast("0011223344").await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::TransactionTooLarge { size: 10, limit: 4 })
+	));
+}
+
+#[tokio::test]
+async fn sticky_uri_should_only_pin_its_own_requests() {
+	let a = serve(Duration::ZERO, r#"{"success":true,"response":1}"#);
+	let b = serve(Duration::ZERO, r#"{"success":true,"response":2}"#);
+	let e = ElectrumXBuilder::default().base_uris(format!("{a},{b}")).build().unwrap();
+	let sticky = e.with_sticky_uri();
+	let x = "blockchain.x";
+
+	assert_eq!(sticky.post::<_, _, Response<u64>>(x, Params::empty()).await.unwrap().response, 1);
+
+	// As if another mint sharing the client moved on to `b`.
+	*e.last_successful_uri_index.lock().unwrap() = 1;
+
+	assert_eq!(e.post::<_, _, Response<u64>>(x, Params::empty()).await.unwrap().response, 2);
+	assert_eq!(
+		e.with_sticky_uri().post::<_, _, Response<u64>>(x, Params::empty()).await.unwrap().response,
+		2
+	);
+	assert_eq!(sticky.post::<_, _, Response<u64>>(x, Params::empty()).await.unwrap().response, 1);
+}
+
+#[test]
+fn unconfirmed_tx_should_have_no_confirmations() {
+	let tx = serde_json::from_str::<Tx>(
+		r#"{
+			"txid": "aabbcc",
+			"hex": "00",
+			"vsize": 1,
+			"vin": [{ "txid": "ddeeff", "vout": 1 }],
+			"vout": [{ "value": 0.00000546, "n": 0, "scriptPubKey": { "hex": "51" } }]
+		}"#,
+	)
+	.unwrap();
+
+	assert_eq!(tx.confirmations, 0);
+	assert!(tx.blockhash.is_none());
+}
+
+#[test]
+fn address_of_should_reject_bad_addresses() {
+	let e = ElectrumXBuilder::testnet().build().unwrap();
+
+	assert!(matches!(
+		e.address_of("tb1pnotanaddress").unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::InvalidAddress { .. })
+	));
+	assert!(matches!(
+		e.address_of("bc1pqkq0rg5yjrx6u08nhmc652s33g96jmdz4gjp9d46ew6ahun7xuvqaerzsp")
+			.unwrap_err()
+			.downcast::<error::Error>(),
+		Ok(error::Error::AddressWrongNetwork {
+			expected: Network::Testnet,
+			found: Network::Bitcoin
+		})
+	));
+}
+
+#[tokio::test]
+async fn post_should_retry_on_the_clock() {
+	let c = Arc::new(clock::TestClock::new());
+	let start = c.now();
+	let e = ElectrumXBuilder::default()
+		.base_uris("http://127.0.0.1:1")
+		.clock(c.clone())
+		.build()
+		.unwrap();
+	let f = e.get_block_height();
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => c.advance(Duration::from_millis(100)),
+		}
+	};
+
+	assert!(r.is_err());
+	// `max_retries` of 3, 2 seconds each.
+	assert!(c.now() - start >= Duration::from_secs(6));
+}
+
+#[tokio::test]
+async fn backing_off_requests_should_release_their_permits() {
+	let c = Arc::new(clock::TestClock::new());
+	let e = ElectrumXBuilder::default()
+		.base_uris("http://127.0.0.1:1")
+		.max_concurrency(1)
+		.clock(c)
+		.build()
+		.unwrap();
+	let failing = e.get_block_height();
+
+	tokio::pin!(failing);
+
+	// It fails right away, then backs off on a clock which never moves.
+	assert!(tokio::time::timeout(Duration::from_millis(200), &mut failing).await.is_err());
+
+	let ok = serve(Duration::ZERO, r#"{"success":true,"response":1}"#);
+	let r = tokio::time::timeout(
+		Duration::from_secs(5),
+		e.post_to::<_, _, Response<u64>>(&ok, "blockchain.x", Params::empty()),
+	)
+	.await;
+
+	assert_eq!(r.unwrap().unwrap().response, 1);
+}
+
+#[tokio::test]
+async fn timeouts_should_reconnect_only_their_base_uri() {
+	let slow = serve(Duration::from_secs(2), r#"{"success":true,"response":1}"#);
+	let fast = serve(Duration::ZERO, r#"{"success":true,"response":2}"#);
+	let c = Arc::new(clock::TestClock::new());
+	let e = ElectrumXBuilder::default()
+		.base_uris(format!("{slow},{fast}"))
+		.timeout(Duration::from_millis(100))
+		.max_retries(MAX_CONNECT_FAILURES - 1)
+		.clock(c.clone())
+		.build()
+		.unwrap();
+	let f = e.post::<_, _, Response<u64>>("blockchain.x", Params::empty());
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => c.advance(Duration::from_millis(100)),
+		}
+	};
+
+	assert_eq!(r.unwrap().response, 2);
+	// The timeouts of `slow` rebuilt its client, which reset the count.
+	assert_eq!(e.connect_failures.lock().unwrap().get(&slow), Some(&0));
+	assert!(e.connect_failures.lock().unwrap().get(&fast).is_none());
+}
+
+#[tokio::test]
+async fn post_should_retry_with_the_backoff_policy() {
+	let c = Arc::new(clock::TestClock::new());
+	let start = c.now();
+	let e = ElectrumXBuilder::default()
+		.base_uris("http://127.0.0.1:1")
+		.clock(c.clone())
+		.backoff(Arc::new(backoff::Exponential {
+			base: Duration::from_secs(1),
+			factor: 2,
+			max: Duration::from_secs(60),
+		}))
+		.build()
+		.unwrap();
+	let f = e.get_block_height();
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => c.advance(Duration::from_millis(100)),
+		}
+	};
+	let elapsed = c.now() - start;
+
+	assert!(r.is_err());
+	// 1, 2 and 4 seconds.
+	assert!(elapsed >= Duration::from_secs(7) && elapsed < Duration::from_secs(8), "{elapsed:?}");
+}
+
+#[test]
+fn error_of_should_classify_atomicals_errors() {
+	let e = serde_json::from_str::<u8>("").unwrap_err();
+
+	assert!(matches!(
+		error_of(r#"{"success":false,"code":1,"message":"Mint closed for ticker"}"#, e)
+			.downcast::<error::Error>(),
+		Ok(error::Error::Atomicals(error::AtomicalsError::MintClosed, _))
+	));
+
+	let e = serde_json::from_str::<u8>("").unwrap_err();
+
+	assert!(matches!(
+		error_of(r#"{"success":false,"code":1,"message":"mint_height not reached"}"#, e)
+			.downcast::<error::Error>(),
+		Ok(error::Error::Atomicals(error::AtomicalsError::MintNotStarted, _))
+	));
+
+	let e = serde_json::from_str::<u8>("").unwrap_err();
+
+	assert!(matches!(
+		error_of(r#"{"success":false,"error":{"code":-32600,"message":"bad request"}}"#, e)
+			.downcast::<error::Error>(),
+		Ok(error::Error::Rpc { code: -32600, .. })
+	));
+}
+
+#[test]
+fn only_transport_errors_should_be_transient() {
+	let unreachable = ElectrumXError::AllUrisExhausted {
+		cause: Exhaustion::Unreachable,
+		errors: vec![("https://a".into(), "connection refused".into())],
+	};
+
+	assert!(is_transient(&Error::from(unreachable).context("failed to broadcast")));
+
+	for e in [
+		ElectrumXError::SpendCapExceeded { spent: 0, amount: 2, limit: 1 },
+		ElectrumXError::TransactionTooLarge { size: 2, limit: 1 },
+		ElectrumXError::AllUrisExhausted { cause: Exhaustion::Mixed, errors: Vec::new() },
+	] {
+		assert!(!is_transient(&e.into()));
+	}
+}
+
+#[test]
+fn only_definite_refusals_should_be_rejections() {
+	let rejection = || ElectrumXError::Rpc { code: -26, message: "min relay fee not met".into() };
+
+	assert!(is_rejected(&rejection().into()));
+	assert!(is_rejected(&Error::from(rejection()).context("all 2 base URIs rejected the request")));
+
+	// The transaction might have reached the mempool despite these.
+	for e in [
+		ElectrumXError::AllUrisExhausted {
+			cause: Exhaustion::Unreachable,
+			errors: vec![("https://a".into(), "operation timed out".into())],
+		},
+		ElectrumXError::AllUrisExhausted { cause: Exhaustion::Mixed, errors: Vec::new() },
+		ElectrumXError::Rpc { code: -27, message: "txn-already-known".into() },
+	] {
+		assert!(!is_rejected(&e.into()));
+	}
+	assert!(!is_rejected(&anyhow::anyhow!("error decoding response body")));
+}
+#[tokio::test]
+async fn select_unspent_address_should_skip_unconfirmed_utxos() {
+	let address = "tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec";
+	let mut confirmed = unspent("aa", 0, 1_000);
+
+	confirmed["height"] = json!(100);
+
+	let unspents = json!([confirmed, unspent("bb", 1, 5_000)]);
+	let e =
+		MockApi { base_uris: vec!["a".into()], funding_min_confirmations: 1, ..Default::default() }
+			.respond("blockchain.headers.subscribe", json!({ "height": 100, "hex": "" }))
+			.respond("blockchain.headers.subscribe", json!({ "height": 100, "hex": "" }))
+			.respond("blockchain.scripthash.listunspent", unspents.clone())
+			.respond("blockchain.scripthash.listunspent", unspents);
+	let us = e
+		.select_unspent_address(address, 500, selection::UtxoSelection::LargestFirst)
+		.await
+		.unwrap();
+
+	assert_eq!(us.iter().map(|u| u.txid.as_str()).collect::<Vec<_>>(), ["aa"]);
+	assert!(matches!(
+		e.select_unspent_address(address, 2_000, selection::UtxoSelection::LargestFirst)
+			.await
+			.unwrap_err()
+			.downcast::<error::Error>(),
+		Ok(error::Error::InsufficientFunds { needed: 2_000, available: 1_000 })
+	));
+}
+
+#[tokio::test]
+async fn funding_pool_should_hand_out_each_utxo_once() {
+	let e = MockApi::default()
+		.respond("blockchain.scripthash.listunspent", json!([unspent("aa", 0, 1000)]))
+		// Not spent yet.
+		.respond("blockchain.scripthash.listunspent", json!([unspent("aa", 0, 1000)]))
+		.respond(
+			"blockchain.scripthash.listunspent",
+			json!([unspent("aa", 0, 1000), unspent("bb", 0, 1000)]),
+		);
+	let mut p = funding::FundingPool::new([(
+		"tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec",
+		2,
+	)]);
+
+	assert_eq!(p.next(&e, 1000).await.unwrap().1.txid, "aa");
+
+	let f = p.next(&e, 1000);
+
+	tokio::pin!(f);
+
+	let (_, u) = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r.unwrap(),
+			_ = tokio::task::yield_now() => e.clock.advance(Duration::from_secs(1)),
+		}
+	};
+
+	assert_eq!(u.txid, "bb");
+}
+
+#[tokio::test]
+async fn funding_scanner_should_only_report_new_utxos() {
+	let a = MockApi::default()
+		.respond("blockchain.scripthash.listunspent", json!([unspent("aa", 0, 1000)]))
+		.respond(
+			"blockchain.scripthash.listunspent",
+			json!([unspent("aa", 0, 1000), unspent("bb", 1, 10), unspent("cc", 2, 2000)]),
+		);
+	let mut s = funding::FundingScanner::new(
+		"tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec",
+		1000,
+	);
+
+	assert_eq!(s.scan(&a).await.unwrap().iter().map(|u| &*u.txid).collect::<Vec<_>>(), ["aa"]);
+	assert_eq!(s.scan(&a).await.unwrap().iter().map(|u| &*u.txid).collect::<Vec<_>>(), ["cc"]);
+}
+
+#[tokio::test]
+async fn funding_matcher_should_poll_on_the_interval() {
+	let e = MockApi { poll_interval: Some(Duration::from_secs(1)), ..Default::default() }
+		.respond("blockchain.scripthash.listunspent", json!([]))
+		.respond("blockchain.scripthash.listunspent", json!([unspent("aa", 0, 1000)]));
+	let m = funding::FundingMatcher::new([1000]);
+	let start = e.clock.now();
+	let f = m.wait(&e, "tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec");
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => e.clock.advance(Duration::from_millis(100)),
+		}
+	};
+	let elapsed = e.clock.now() - start;
+
+	assert!(r.unwrap().is_complete());
+	assert!(elapsed >= Duration::from_secs(1) && elapsed < DEFAULT_POLL_INTERVAL, "{elapsed:?}");
+}
+
+#[test]
+fn build_should_validate_base_uris() {
+	assert!(matches!(
+		ElectrumXBuilder::default()
+			.base_uris("https://ep.atomicals.xyz/proxy,ep.atomicals.xyz")
+			.build()
+			.unwrap_err()
+			.downcast::<error::Error>(),
+		Ok(error::Error::InvalidBaseUri { uri, .. }) if uri == "ep.atomicals.xyz"
+	));
+	assert_eq!(
+		ElectrumXBuilder::default()
+			.base_uris("https://ep.atomicals.xyz/proxy/, http://[::1]:50001/proxy")
+			.build()
+			.unwrap()
+			.base_uris,
+		["https://ep.atomicals.xyz/proxy", "http://[::1]:50001/proxy"]
+	);
+}
+
+#[test]
+fn trailing_slash_should_not_double_the_separator() {
+	let e = ElectrumXBuilder::default()
+		.base_uris("https://ep.atomicals.xyz/proxy/,https://ep2.atomicals.xyz/proxy")
+		.build()
+		.unwrap();
+
+	for u in e.base_uris() {
+		assert_eq!(
+			uri_of(u, "blockchain.atomicals.get_by_ticker").matches("proxy/blockchain").count(),
+			1
+		);
+	}
+
+	assert_eq!(
+		uri_of("https://ep.atomicals.xyz/proxy/", "blockchain.atomicals.get_by_ticker"),
+		"https://ep.atomicals.xyz/proxy/blockchain.atomicals.get_by_ticker"
+	);
+}
+
+#[test]
+fn method_alias_should_work() {
+	let e = ElectrumXBuilder::testnet()
+		.method_alias("blockchain.atomicals.get_by_ticker", "v1/atomicals.get_by_ticker")
+		.build()
+		.unwrap();
+
+	assert_eq!(e.method_of("blockchain.atomicals.get_by_ticker"), "v1/atomicals.get_by_ticker");
+	assert_eq!(e.method_of("blockchain.atomicals.get_ft_info"), "blockchain.atomicals.get_ft_info");
+}
+
+#[test]
+fn request_interceptor_should_rewrite_the_body() {
+	let e = ElectrumXBuilder::testnet()
+		.request_interceptor(|b| b["params"] = json!([b["params"][0], "extra"]))
+		.build()
+		.unwrap();
+
+	assert_eq!(e.body_of(Params::new(["atom"])).unwrap(), json!({ "params": ["atom", "extra"] }));
+	assert_eq!(
+		ElectrumXBuilder::testnet().build().unwrap().body_of(Params::new(["atom"])).unwrap(),
+		json!({ "params": ["atom"] })
+	);
+}
+
+#[tokio::test]
+async fn broadcast_should_reject_mismatched_txid() {
+	let tx = tx();
+	let (txid, hex) = (tx.txid(), bitcoin::consensus::encode::serialize_hex(&tx));
+	let api = MockApi::default()
+		.respond("blockchain.transaction.get", json!(""))
+		.respond("blockchain.transaction.get", json!(""))
+		.respond("blockchain.transaction.broadcast", json!(txid.to_string()))
+		.respond("blockchain.transaction.broadcast", json!(Txid::all_zeros().to_string()));
+
+	assert_eq!(api.broadcast(&hex).await.unwrap(), txid.to_string());
+	assert!(matches!(
+		api.broadcast(&hex).await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::TxidMismatch { expected, got })
+			if expected == txid && got == Txid::all_zeros().to_string()
+	));
+}
+
+#[tokio::test]
+async fn broadcast_should_reject_absurd_fee_rates() {
+	let tx = tx();
+	let (txid, hex) = (tx.txid(), bitcoin::consensus::encode::serialize_hex(&tx));
+	// Funds the 546 sats output with 100,000 sats, which is over 1,000 sat/vB.
+	let parent = json!({
+		"txid": Txid::all_zeros().to_string(),
+		"hex": "00",
+		"vsize": 1,
+		"vin": [],
+		"vout": [{ "value": 0.001, "n": u32::MAX, "scriptPubKey": { "hex": "51" } }]
+	});
+	let api = |max_fee_rate| {
+		MockApi { max_fee_rate, ..Default::default() }
+			.respond("blockchain.transaction.get", json!(""))
+			.respond("blockchain.transaction.get", parent.clone())
+			.respond("blockchain.transaction.broadcast", json!(txid.to_string()))
+	};
+
+	assert!(matches!(
+		api(Some(FeeRate::from_sat_per_vb(1_000.)))
+			.broadcast(&hex)
+			.await
+			.unwrap_err()
+			.downcast::<error::Error>(),
+		Ok(error::Error::FeeRateAbsurd { txid: t, fee_rate, .. })
+			if t == txid && fee_rate > FeeRate::from_sat_per_vb(1_000.)
+	));
+	assert!(api(Some(FeeRate::from_sat_per_vb(5_000.))).broadcast(&hex).await.is_ok());
+	// Overridden.
+	assert!(api(None).broadcast(&hex).await.is_ok());
+}
+
+#[tokio::test]
+async fn broadcast_spending_should_price_the_known_prevouts() {
+	let tx = tx();
+	let (txid, hex) = (tx.txid(), bitcoin::consensus::encode::serialize_hex(&tx));
+	let prevout = |sats| bitcoin::TxOut {
+		value: bitcoin::Amount::from_sat(sats),
+		script_pubkey: Default::default(),
+	};
+	// No parent to fetch, only the network check looks up the input.
+	let api = || {
+		MockApi { max_fee_rate: Some(FeeRate::from_sat_per_vb(1_000.)), ..Default::default() }
+			.respond("blockchain.transaction.get", json!(""))
+			.respond("blockchain.transaction.broadcast", json!(txid.to_string()))
+	};
+
+	assert!(matches!(
+		api()
+			.broadcast_spending(&hex, &[prevout(100_000)])
+			.await
+			.unwrap_err()
+			.downcast::<error::Error>(),
+		Ok(error::Error::FeeRateAbsurd { .. })
+	));
+	assert!(api().broadcast_spending(&hex, &[prevout(1_000)]).await.is_ok());
+}
+
+#[tokio::test]
+async fn broadcast_stream_should_yield_each_result() {
+	let tx = tx();
+	let (txid, hex) = (tx.txid(), bitcoin::consensus::encode::serialize_hex(&tx));
+	let api = MockApi::default()
+		.respond("blockchain.transaction.get", json!(""))
+		.respond("blockchain.transaction.get", json!(""))
+		.respond("blockchain.transaction.broadcast", json!(Txid::all_zeros().to_string()))
+		.respond("blockchain.transaction.broadcast", json!(txid.to_string()));
+	// Too large to even be sent.
+	let oversized = "00".repeat(DEFAULT_MAX_BROADCAST_SIZE);
+	let mut rs = api.broadcast_stream([&hex, &oversized, &hex]).collect::<Vec<_>>().await;
+
+	rs.sort_by_key(|(i, _)| *i);
+
+	assert!(matches!(
+		rs[0].1.as_ref().unwrap_err().downcast_ref::<error::Error>(),
+		Some(error::Error::TxidMismatch { .. })
+	));
+	assert!(matches!(
+		rs[1].1.as_ref().unwrap_err().downcast_ref::<error::Error>(),
+		Some(error::Error::TransactionTooLarge { .. })
+	));
+	assert_eq!(*rs[2].1.as_ref().unwrap(), txid);
+}
+
+#[test]
+fn build_should_default_base_uris_by_network() {
+	assert_eq!(
+		ElectrumXBuilder::default().network(Network::Testnet).build().unwrap().base_uris,
+		["https://eptestnet.atomicals.xyz/proxy"]
+	);
+	assert_eq!(
+		ElectrumXBuilder::default().build().unwrap().base_uris,
+		["https://ep.atomicals.xyz/proxy"]
+	);
+	assert!(matches!(
+		ElectrumXBuilder::default()
+			.network(Network::Regtest)
+			.build()
+			.unwrap_err()
+			.downcast::<error::Error>(),
+		Ok(error::Error::NoDefaultBaseUri(Network::Regtest))
+	));
+	assert!(ElectrumXBuilder::default()
+		.network(Network::Regtest)
+		.base_uris("http://127.0.0.1:8080/proxy")
+		.build()
+		.is_ok());
+}
+
+#[tokio::test]
+async fn race_should_skip_junk_responses() {
+	let junk = serve(Duration::ZERO, "<html>ok</html>");
+	let valid = serve(Duration::from_millis(200), r#"{"success":true,"response":42}"#);
+	let e = ElectrumXBuilder::default()
+		.base_uris(format!("{junk},{valid}"))
+		.strategy(Strategy::Race)
+		.build()
+		.unwrap();
+
+	// Afterwards the junk base URI joins late, so the race is over before it responds.
+	for _ in 0..2 {
+		assert_eq!(
+			e.post::<_, _, Response<u64>>("blockchain.x", Params::empty()).await.unwrap().response,
+			42
+		);
+		assert_eq!(e.race_penalties.lock().unwrap().get(&junk), Some(&1));
+	}
+}
+
+#[tokio::test]
+async fn ensure_ready_should_count_healthy_base_uris() {
+	let healthy = serve(Duration::ZERO, r#"{"success":true,"response":{"height":1,"hex":""}}"#);
+	let e = ElectrumXBuilder::default()
+		.base_uris(format!("{healthy},http://127.0.0.1:1"))
+		.require_healthy(2)
+		.build()
+		.unwrap();
+
+	assert!(matches!(
+		e.ensure_ready().await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::NotEnoughHealthy { healthy: 1, required: 2 })
+	));
+
+	let e = ElectrumXBuilder { require_healthy: 1, ..ElectrumXBuilder::default() }
+		.base_uris(format!("{healthy},http://127.0.0.1:1"))
+		.build()
+		.unwrap();
+
+	e.ensure_ready().await.unwrap();
+}
+
+#[tokio::test]
+async fn scenario_should_replay() {
+	let header = serve(Duration::ZERO, r#"{"success":true,"response":{"height":7,"hex":""}}"#);
+	let e = ElectrumXBuilder::default().base_uris(header).record().build().unwrap();
+
+	assert_eq!(e.get_block_height().await.unwrap(), 7);
+
+	let s = serde_json::to_string(&e.scenario(Err("mint closed".into()))).unwrap();
+	let s = serde_json::from_str::<Scenario>(&s).unwrap();
+
+	assert_eq!(s.network, Network::Bitcoin);
+	assert_eq!(s.outcome, Err("mint closed".into()));
+	assert_eq!(s.exchanges[0].method, "blockchain.headers.subscribe");
+	assert_eq!(s.get_block_height().await.unwrap(), 7);
+	assert!(s.get_block_height().await.is_err());
+}
+
+#[tokio::test]
+async fn broadcast_should_reject_txs_of_another_network() {
+	let tx = tx();
+	let broadcast = |base_uri: String| {
+		let tx = bitcoin::consensus::encode::serialize_hex(&tx);
+
+		async move {
+			ElectrumXBuilder::default()
+				.base_uris(base_uri)
+				.max_retries(1)
+				.build()
+				.unwrap()
+				.broadcast(tx)
+				.await
+				.unwrap_err()
+		}
+	};
+	let unknown = serve(
+		Duration::ZERO,
+		r#"{"success":false,"error":{"code":2,"message":"daemon error: DaemonError({'code': -5, 'message': 'No such mempool or blockchain transaction. Use gettransaction for wallet transactions.'})"}}"#,
+	);
+
+	assert!(matches!(
+		broadcast(unknown).await.downcast_ref::<error::Error>(),
+		Some(error::Error::NetworkMismatch { network: Network::Bitcoin, txid }) if *txid == tx.txid()
+	));
+
+	// Failing to look up the input says nothing about the network.
+	let e = broadcast("http://127.0.0.1:1".into()).await;
+
+	assert!(!matches!(
+		e.downcast_ref::<error::Error>(),
+		Some(error::Error::NetworkMismatch { .. })
+	));
+	assert!(is_transient(&e));
+}
+
+#[tokio::test]
+async fn retry_on_empty_should_be_bounded() {
+	let method = "blockchain.scripthash.listunspent";
+	let c = Arc::new(clock::TestClock::new());
+
+	// Indexed after two empty responses.
+	for (retries, expected) in [(0, 0), (1, 0), (2, 1)] {
+		let uri = serve_all(
+			Duration::ZERO,
+			vec![
+				r#"{"success":true,"response":[]}"#,
+				r#"{"success":true,"response":[]}"#,
+				r#"{"success":true,"response":[1]}"#,
+			],
+		);
+		let e = ElectrumXBuilder::default()
+			.base_uris(uri)
+			.clock(c.clone())
+			.retry_on_empty(method, retries)
+			.build()
+			.unwrap();
+		let f = e.post::<_, _, Response<Vec<u8>>>(method, Params::empty());
+
+		tokio::pin!(f);
+
+		let r = loop {
+			tokio::select! {
+				biased;
+				r = &mut f => break r,
+				_ = tokio::task::yield_now() => c.advance(Duration::from_millis(100)),
+			}
+		};
+
+		assert_eq!(r.unwrap().response.len(), expected);
+	}
+}
+
+#[test]
+fn rate_limits_should_be_tracked_per_base_uri() {
+	let c = Arc::new(clock::TestClock::new());
+	let e = ElectrumXBuilder::default()
+		.base_uris("https://ep.atomicals.xyz/proxy,https://ep2.atomicals.xyz/proxy")
+		.clock(c.clone())
+		.build()
+		.unwrap();
+	let headers = |remaining: &str, reset: &str| {
+		let mut h = reqwest::header::HeaderMap::new();
+
+		h.insert("x-ratelimit-remaining", remaining.parse().unwrap());
+		h.insert("x-ratelimit-reset", reset.parse().unwrap());
+
+		h
+	};
+	let (a, b) = (&e.base_uris[0], &e.base_uris[1]);
+
+	e.track_rate_limit(a, &headers("0", "10"));
+	e.track_rate_limit(b, &headers("5", "10"));
+
+	assert_eq!(e.rate_limited_for(a), Some(Duration::from_secs(10)));
+	assert_eq!(e.rate_limited_for(b), None);
+
+	c.advance(Duration::from_secs(10));
+
+	assert_eq!(e.rate_limited_for(a), None);
+
+	// A unix timestamp in the past.
+	e.track_rate_limit(a, &headers("0", "1700000000"));
+
+	assert_eq!(e.rate_limited_for(a), None);
+}
+
+#[tokio::test]
+async fn atomical_mints_should_be_paginated() {
+	let tx = |i: u64| {
+		json!({
+			"txid": format!("{i:064x}"),
+			"hex": "00",
+			"vsize": 1,
+			"vin": [],
+			"vout": [{
+				"value": if i == 7 { 0.00000546 } else { 0.00001 },
+				"n": 0,
+				"scriptPubKey": { "hex": "51", "address": "tb1pxx" },
+			}],
+		})
+	};
+	let page = |r: std::ops::Range<u64>| {
+		json!({
+			"result": r.map(|i| json!({ "txid": format!("{i:064x}"), "height": i })).collect::<Vec<_>>(),
+		})
+	};
+	let method = "blockchain.atomicals.transaction_by_atomical_id";
+	let api = (0..HISTORY_PAGE_SIZE + 3).fold(
+		MockApi::default()
+			.respond(method, page(0..HISTORY_PAGE_SIZE))
+			.respond(method, page(HISTORY_PAGE_SIZE..HISTORY_PAGE_SIZE + 3)),
+		|api, i| api.respond("blockchain.transaction.get", tx(i)),
+	);
+	let mints = api.get_atomical_mints("atom", 1_000).await.unwrap();
+
+	assert_eq!(mints.len() as u64, HISTORY_PAGE_SIZE + 3);
+	assert_eq!(mints.iter().filter(|m| !m.valid).map(|m| m.height).collect::<Vec<_>>(), [7]);
+	assert_eq!(mints[0].recipient.as_deref(), Some("tb1pxx"));
+}
+
+#[tokio::test]
+async fn first_ok_should_skip_failures() {
+	let api = MockApi::default()
+		.respond("blockchain.headers.subscribe", json!({ "height": 7, "hex": "" }));
+	let r = api
+		.first_ok::<_, _, _, _, Response<Header>>([
+			("https://ep.atomicals.xyz/proxy", "blockchain.unknown", Params::empty()),
+			("https://ep2.atomicals.xyz/proxy", "blockchain.headers.subscribe", Params::empty()),
+		])
+		.await
+		.unwrap();
+
+	assert_eq!(r.response.height, 7);
+	assert!(api.first_ok::<_, &str, &str, Params<[(); 0]>, Response<Header>>([]).await.is_err());
+}
+
+#[tokio::test]
+async fn broadcast_should_be_verified_by_another_backend() {
+	let tx = tx();
+	let (txid, hex) = (tx.txid(), bitcoin::consensus::encode::serialize_hex(&tx));
+	let api = |seen: bool| {
+		let api = MockApi {
+			base_uris: vec!["https://ep.atomicals.xyz/proxy".into()],
+			broadcast_verify_window: Some(Duration::from_secs(3)),
+			..Default::default()
+		}
+		.respond("blockchain.transaction.get", json!(""))
+		.respond("blockchain.transaction.broadcast", json!(txid.to_string()));
+
+		if seen {
+			api.respond("blockchain.transaction.get", json!(""))
+		} else {
+			api
+		}
+	};
+
+	api(true).broadcast(&hex).await.unwrap();
+
+	let api = api(false);
+	let f = api.broadcast(&hex);
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => api.clock.advance(Duration::from_millis(100)),
+		}
+	};
+
+	assert!(matches!(
+		r.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::BroadcastUnconfirmed { txid: t, .. }) if t == txid
+	));
+}
+
+#[tokio::test]
+async fn relay_fee_should_work() {
+	let f = MockApi::default()
+		.respond("blockchain.relayfee", json!(0.00001))
+		.relay_fee()
+		.await
+		.unwrap();
+
+	assert_eq!(f.sat_per_vb_ceil(), 1);
+	assert_eq!(f.to_string(), "1.0 sat/vB");
+}
+
+#[test]
+fn fee_rate_should_convert_units() {
+	assert_eq!(FeeRate::from_btc_per_kvb(0.000015).sat_per_vb_ceil(), 2);
+	assert_eq!(FeeRate::from_sat_per_vb(1.5).fee_of(111), 167);
+	assert_eq!(
+		FeeRate::from_sat_per_vb(2.).max(FeeRate::from_sat_per_vb(1.)).to_string(),
+		"2.0 sat/vB"
+	);
+}
+
+#[tokio::test]
+async fn redirects_should_be_limited() {
+	let moved = serve(Duration::ZERO, r#"{"success":true,"response":42}"#);
+	let redirect = |status: &str| {
+		serve_raw(
+			Duration::ZERO,
+			vec![format!(
+				"HTTP/1.1 {status}\r\nLocation: {moved}/blockchain.x\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+			)],
+		)
+	};
+	let post = |base_uri: String, max_redirects: usize| async move {
+		ElectrumXBuilder::default()
+			.base_uris(base_uri)
+			.max_redirects(max_redirects)
+			.no_failover()
+			.build()
+			.unwrap()
+			.post::<_, _, Response<u64>>("blockchain.x", Params::empty())
+			.await
+	};
+
+	assert_eq!(post(redirect("307 Temporary Redirect"), 1).await.unwrap().response, 42);
+
+	for (status, max_redirects) in [("307 Temporary Redirect", 0), ("301 Moved Permanently", 1)] {
+		let base_uri = redirect(status);
+
+		assert!(matches!(
+			post(base_uri.clone(), max_redirects).await.unwrap_err().downcast::<error::Error>(),
+			Ok(error::Error::Redirected { uri, location })
+				if uri == format!("{base_uri}/blockchain.x") && location == format!("{moved}/blockchain.x")
+		));
+	}
+}
+
+#[tokio::test]
+async fn exhaustion_should_be_classified() {
+	let rejecting = || {
+		serve(
+			Duration::ZERO,
+			r#"{"success":false,"error":{"code":-32600,"message":"bad request"}}"#,
+		)
+	};
+	let post = |base_uris: String| async move {
+		ElectrumXBuilder::default()
+			.base_uris(base_uris)
+			.strategy(Strategy::Race)
+			.build()
+			.unwrap()
+			.post::<_, _, Response<u64>>("blockchain.x", Params::empty())
+			.await
+			.unwrap_err()
+			.downcast::<error::Error>()
+	};
+
+	assert!(matches!(
+		post(format!("{},{}", rejecting(), rejecting())).await,
+		Ok(error::Error::Rpc { code: -32600, .. })
+	));
+	assert!(matches!(
+		post("http://127.0.0.1:1,http://127.0.0.1:2".into()).await,
+		Ok(error::Error::AllUrisExhausted { cause: error::Exhaustion::Unreachable, errors })
+			if errors.len() == 2
+	));
+	assert!(matches!(
+		post(format!("{},http://127.0.0.1:1", rejecting())).await,
+		Ok(error::Error::AllUrisExhausted { cause: error::Exhaustion::Mixed, .. })
+	));
+}
+
+#[tokio::test]
+async fn get_tx_fees_should_fetch_each_parent_once() {
+	let vout =
+		|value: f64, n: u32| json!({ "value": value, "n": n, "scriptPubKey": { "hex": "" } });
+	let tx = json!({
+		"txid": "b",
+		"hex": "",
+		"vsize": 150,
+		"vin": [{ "txid": "a", "vout": 0 }, { "txid": "a", "vout": 1 }],
+		"vout": [vout(0.00001, 0), vout(0.00000700, 1)],
+	});
+	let parent = json!({
+		"txid": "a",
+		"hex": "",
+		"vsize": 100,
+		"vin": [{ "txid": "0", "vout": 0 }],
+		"vout": [vout(0.00001, 0), vout(0.00001, 1)],
+	});
+	let e = MockApi::default()
+		.respond("blockchain.transaction.get", tx)
+		.respond("blockchain.transaction.get", parent);
+	let (fee, rate) = e.get_tx_fees(&["b"]).await.unwrap()["b"];
+
+	assert_eq!(fee, 300);
+	assert_eq!(rate, FeeRate::from_sat_per_vb(2.));
+}
+
+#[tokio::test]
+async fn broadcasts_should_be_spaced_out() {
+	let c = Arc::new(clock::TestClock::new());
+	let start = c.now();
+	let e = ElectrumXBuilder::default()
+		.broadcast_throttle(2, Duration::from_secs(1))
+		.clock(c.clone())
+		.build()
+		.unwrap();
+
+	assert!(e.throttle("blockchain.headers.subscribe").await.unwrap().is_none());
+
+	let f = futures::future::join3(
+		e.throttle("blockchain.transaction.broadcast"),
+		e.throttle("blockchain.transaction.broadcast"),
+		e.throttle("blockchain.headers.subscribe"),
+	);
+
+	tokio::pin!(f);
+
+	let (a, b, _) = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => c.advance(Duration::from_millis(100)),
+		}
+	};
+
+	let (a, b) = (a.unwrap(), b.unwrap());
+
+	assert!(a.is_some() && b.is_some());
+	assert!(c.now() - start >= Duration::from_secs(1));
+	// Both permits are in use.
+	assert!(e.broadcast_semaphore.try_acquire().is_err());
+}
+
+#[tokio::test]
+async fn wait_for_confirmations_should_survive_reorgs() {
+	let tx = |confirmations: u64, blockhash: &str| {
+		json!({
+			"txid": "a",
+			"hex": "",
+			"vsize": 1,
+			"vin": [],
+			"vout": [],
+			"confirmations": confirmations,
+			"blockhash": blockhash,
+		})
+	};
+	let e = MockApi::default()
+		.respond(
+			"blockchain.transaction.get",
+			json!({ "txid": "a", "hex": "", "vsize": 1, "vin": [], "vout": [] }),
+		)
+		.respond("blockchain.transaction.get", tx(1, "b0"))
+		// Reorged into another block.
+		.respond("blockchain.transaction.get", tx(1, "b1"))
+		.respond("blockchain.transaction.get", tx(3, "b1"))
+		.respond("blockchain.transaction.get", tx(3, "b1"))
+		.respond("blockchain.transaction.get", tx(3, "b1"));
+	let start = e.clock.now();
+	let f = e.wait_for_confirmations("a", 2);
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => e.clock.advance(Duration::from_secs(1)),
+		}
+	};
+	let tx = r.unwrap();
+
+	assert_eq!((tx.confirmations, tx.blockhash.as_deref()), (3, Some("b1")));
+	assert!(e.clock.now() - start >= Duration::from_secs(15));
+
+	e.ensure_confirmed_in("a", "b1").await.unwrap();
+
+	assert!(matches!(
+		e.ensure_confirmed_in("a", "b0").await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::ReorgDetected { blockhash, .. }) if blockhash == "b0"
+	));
+}
+
+#[tokio::test]
+async fn get_atomicals_at_address_should_group_by_id() {
+	let with = |mut u: Value, atomicals: Value| {
+		u["atomicals"] = atomicals;
+
+		u
+	};
+	let e = MockApi::default()
+		.respond(
+			"blockchain.scripthash.listunspent",
+			json!([
+				with(unspent("a", 0, 1_000), json!(["ai0"])),
+				unspent("b", 0, 5_000),
+				with(unspent("c", 0, 2_001), json!(["ai0", "bi0"])),
+				with(
+					unspent("d", 0, 3_000),
+					json!({ "ai0": { "value": 2_400 }, "bi0": { "value": 600 } })
+				),
+			]),
+		)
+		.respond(
+			"blockchain.atomicals.get",
+			json!({ "result": { "atomical_id": "ai0", "atomical_number": 1, "type": "FT", "$ticker": "quark" } }),
+		)
+		.respond(
+			"blockchain.atomicals.get",
+			json!({ "result": { "atomical_id": "bi0", "atomical_number": 2, "type": "NFT" } }),
+		);
+	let hs = e
+		.get_atomicals_at_address("tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec")
+		.await
+		.unwrap();
+
+	assert_eq!(
+		hs.iter()
+			.map(|h| (
+				h.atomical_id.as_str(),
+				h.ticker.as_deref(),
+				h.amount,
+				h.ambiguous_amount,
+				h.utxos
+			))
+			.collect::<Vec<_>>(),
+		// The value of a shared UTXO is only split as the proxy reports it.
+		[("ai0", Some("quark"), 3_400, 2_001, 3), ("bi0", None, 600, 2_001, 2)]
+	);
+	assert_eq!(hs[1].r#type, "NFT");
+}
+
+#[tokio::test]
+async fn portfolio_snapshot_should_be_sorted_and_totaled() {
+	let (p2tr, p2wpkh) = (
+		"tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec",
+		"tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+	);
+	let holding = |txid, value, ids: &[&str]| {
+		let mut u = unspent(txid, 0, value);
+
+		u["atomicals"] = json!(ids);
+
+		u
+	};
+	let atomical =
+		|id| json!({ "result": { "atomical_id": id, "atomical_number": 1, "type": "FT" } });
+	// The addresses are snapshotted in order, the P2TR one first.
+	let e = MockApi::default()
+		.respond(
+			"blockchain.scripthash.listunspent",
+			json!([
+				holding("a", 1_000, &["bi0"]),
+				holding("b", 2_000, &["ai0"]),
+				holding("d", 500, &["ai0", "bi0"]),
+			]),
+		)
+		.respond("blockchain.scripthash.listunspent", json!([holding("c", 3_000, &["bi0"])]))
+		.respond("blockchain.atomicals.get", atomical("bi0"))
+		.respond("blockchain.atomicals.get", atomical("ai0"))
+		.respond("blockchain.atomicals.get", atomical("bi0"));
+	let s = e.portfolio_snapshot(&[p2wpkh, p2tr, p2wpkh]).await.unwrap();
+	let s = serde_json::to_value(s).unwrap();
+
+	assert_eq!(s["network"], "testnet");
+	assert_eq!(
+		s["totals"],
+		json!([
+			{
+				"atomical_id": "ai0",
+				"type": "FT",
+				"ticker": null,
+				"amount": 2_000,
+				"ambiguous_amount": 500,
+				"utxos": 2,
+			},
+			{
+				"atomical_id": "bi0",
+				"type": "FT",
+				"ticker": null,
+				"amount": 4_000,
+				"ambiguous_amount": 500,
+				"utxos": 3,
+			},
+		])
+	);
+	assert_eq!(s["addresses"][0]["address"], p2tr);
+	assert_eq!(s["addresses"][0]["holdings"][0]["atomical_id"], "ai0");
+	assert_eq!(s["addresses"][1]["holdings"][0]["amount"], 3_000);
+}
+
+#[tokio::test]
+async fn signed_psbt_should_be_broadcast() {
+	// crates.io
+	use bitcoin::{
+		key::TapTweak,
+		secp256k1::{Keypair, Message, Secp256k1},
+		sighash::{Prevouts, SighashCache, TapSighashType},
+		taproot::Signature,
+		Amount, OutPoint, ScriptBuf, TxOut,
+	};
+
+	let secp = Secp256k1::new();
+	let kp = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+	let xpk = kp.x_only_public_key().0;
+	let prevout = TxOut {
+		value: Amount::from_sat(10_000),
+		script_pubkey: ScriptBuf::new_p2tr(&secp, xpk, None),
+	};
+	let output = vec![TxOut {
+		value: Amount::from_sat(9_000),
+		script_pubkey: prevout.script_pubkey.clone(),
+	}];
+	let mut psbt = util::build_unsigned_psbt(
+		OutPoint::new(Txid::all_zeros(), 0),
+		prevout.clone(),
+		xpk,
+		output,
+		Some("ab"),
+	)
+	.unwrap();
+	let txid = psbt.unsigned_tx.txid();
+
+	assert!(util::matches_bitwork(&txid, "ab"));
+
+	let e = MockApi::default()
+		.respond("blockchain.transaction.get", json!(""))
+		.respond("blockchain.transaction.broadcast", json!(txid.to_string()));
+
+	assert!(e.broadcast_signed_psbts(vec![psbt.clone()]).await.is_err());
+
+	// What the external wallet does.
+	let h = SighashCache::new(&psbt.unsigned_tx)
+		.taproot_key_spend_signature_hash(0, &Prevouts::All(&[prevout]), TapSighashType::Default)
+		.unwrap();
+	let sig = secp.sign_schnorr(
+		&Message::from_digest(h.to_byte_array()),
+		&kp.tap_tweak(&secp, None).to_inner(),
+	);
+
+	psbt.inputs[0].tap_key_sig = Some(Signature { sig, hash_ty: TapSighashType::Default });
+
+	assert_eq!(e.broadcast_signed_psbts(vec![psbt]).await.unwrap().remove(0).unwrap(), txid);
+}
+
+#[tokio::test]
+async fn method_timeouts_should_override_the_default() {
+	let e = ElectrumXBuilder::default()
+		.base_uris(serve(Duration::from_millis(300), r#"{"success":true,"response":42}"#))
+		.method_timeout("blockchain.slow", Duration::from_millis(100))
+		.no_failover()
+		.build()
+		.unwrap();
+
+	assert!(e
+		.post::<_, _, Response<u64>>("blockchain.slow", Params::empty())
+		.await
+		.unwrap_err()
+		.downcast::<reqwest::Error>()
+		.unwrap()
+		.is_timeout());
+	assert_eq!(
+		e.post::<_, _, Response<u64>>("blockchain.x", Params::empty()).await.unwrap().response,
+		42
+	);
+}
+
+#[tokio::test]
+async fn verify_utxo_unspent_should_catch_spent_utxos() {
+	let address = "tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec";
+	let txid = Txid::all_zeros();
+	let e = MockApi::default()
+		.respond("blockchain.scripthash.listunspent", json!([unspent(&txid.to_string(), 1, 1_000)]))
+		.respond("blockchain.scripthash.listunspent", json!([]));
+	let outpoint = bitcoin::OutPoint::new(txid, 1);
+
+	e.verify_utxo_unspent(address, &outpoint).await.unwrap();
+
+	assert!(matches!(
+		e.verify_utxo_unspent(address, &outpoint).await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::UtxoSpent(o)) if o == outpoint
+	));
+}
+
+#[tokio::test]
+async fn verify_commit_should_catch_commits_which_did_not_land() {
+	let address = "tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec";
+	let txid = Txid::all_zeros();
+	let e = MockApi::default()
+		.respond("blockchain.scripthash.listunspent", json!([unspent(&txid.to_string(), 0, 1_000)]))
+		.respond("blockchain.scripthash.listunspent", json!([]));
+	let outpoint = bitcoin::OutPoint::new(txid, 0);
+
+	e.verify_commit(address, &outpoint).await.unwrap();
+
+	assert!(matches!(
+		e.verify_commit(address, &outpoint).await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::CommitNotFound(o)) if o == outpoint
+	));
+}
+
+#[tokio::test]
+async fn rejected_responses_should_fail_over() {
+	let e = ElectrumXBuilder::default()
+		.base_uris(format!(
+			"{},{}",
+			serve(Duration::ZERO, r#"{"success":true,"response":0}"#),
+			serve(Duration::ZERO, r#"{"success":true,"response":42}"#)
+		))
+		.validate_response("blockchain.height", |v| {
+			if v["response"].as_u64().unwrap_or_default() == 0 {
+				Err(anyhow::anyhow!("height is zero"))?;
+			}
+
+			Ok(())
+		})
+		.build()
+		.unwrap();
+
+	assert_eq!(
+		e.post::<_, _, Response<u64>>("blockchain.height", Params::empty()).await.unwrap().response,
+		42
+	);
+	// The others are not validated.
+	assert_eq!(
+		e.post_to::<_, _, Response<u64>>(&e.base_uris[0], "blockchain.x", Params::empty())
+			.await
+			.unwrap()
+			.response,
+		0
+	);
+	assert!(e
+		.post_to::<_, _, Response<u64>>(&e.base_uris[0], "blockchain.height", Params::empty())
+		.await
+		.unwrap_err()
+		.to_string()
+		.contains("failed validation"));
+}
+
+#[tokio::test]
+async fn wait_for_index_should_wait_for_the_index_to_catch_up() {
+	let global = |height| {
+		json!({ "global": {
+			"atomical_count": 0,
+			"atomicals_block_hashes": {},
+			"atomicals_block_tip": "",
+			"block_tip": "",
+			"coin": "BitcoinTestnet",
+			"height": height,
+			"network": "testnet",
+			"server_time": ""
+		} })
+	};
+	let e = MockApi::default()
+		.respond("blockchain.headers.subscribe", json!({ "height": 110, "hex": "" }))
+		.respond("blockchain.headers.subscribe", json!({ "height": 111, "hex": "" }))
+		.respond("blockchain.headers.subscribe", json!({ "height": 111, "hex": "" }))
+		.respond("blockchain.atomicals.get_global", global(100))
+		.respond("blockchain.atomicals.get_global", global(108))
+		.respond("blockchain.atomicals.get_global", global(111));
+
+	assert_eq!(e.index_lag().await.unwrap(), 10);
+
+	let f = e.wait_for_index(1);
+
+	tokio::pin!(f);
+
+	loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r.unwrap(),
+			_ = tokio::task::yield_now() => e.clock.advance(Duration::from_secs(1)),
+		}
+	}
+
+	// Both were drained, the lag of 3 wasn't good enough.
+	assert!(e.index_height().await.is_err());
+}
+
+// `listunspent` of an atomicals electrumx proxy.
+const ATOMICALS_PROXY_UNSPENT: &str = r#"{"success":true,"response":[
+	{"txid":"9b5e2b1e8bfbdfa2b2d2d4a1f7c2db4d1f3c5b8d58e9a0e0f70f3cbd0fa1c002","tx_hash":"9b5e2b1e8bfbdfa2b2d2d4a1f7c2db4d1f3c5b8d58e9a0e0f70f3cbd0fa1c002","index":1,"tx_pos":1,"vout":1,"height":2571012,"value":20000,"atomicals":[]},
+	{"txid":"5d1c4e0a3f3c6c7b3e2a1e0f9f8e7d6c5b4a39281706f5e4d3c2b1a098765432","tx_hash":"5d1c4e0a3f3c6c7b3e2a1e0f9f8e7d6c5b4a39281706f5e4d3c2b1a098765432","index":0,"tx_pos":0,"vout":0,"height":0,"value":1000,"atomicals":["5d1c4e0a3f3c6c7b3e2a1e0f9f8e7d6c5b4a39281706f5e4d3c2b1a098765432i0"]}
+]}"#;
+// `listunspent` of a plain electrumx behind an older proxy, nested atomicals and no txid.
+const LEGACY_PROXY_UNSPENT: &str = r#"{"success":true,"response":[
+	{"tx_hash":"9b5e2b1e8bfbdfa2b2d2d4a1f7c2db4d1f3c5b8d58e9a0e0f70f3cbd0fa1c002","tx_pos":1,"height":-1,"value":20000},
+	{"tx_hash":"5d1c4e0a3f3c6c7b3e2a1e0f9f8e7d6c5b4a39281706f5e4d3c2b1a098765432","output_index":0,"value":1000,"atomicals":[{"atomical_id":"5d1c4e0a3f3c6c7b3e2a1e0f9f8e7d6c5b4a39281706f5e4d3c2b1a098765432i0","type":"FT"}]}
+]}"#;
+
+#[test]
+fn unspents_of_every_proxy_should_parse() {
+	for f in [ATOMICALS_PROXY_UNSPENT, LEGACY_PROXY_UNSPENT] {
+		let utxos = serde_json::from_str::<Response<Vec<Unspent>>>(f)
+			.unwrap()
+			.response
+			.into_iter()
+			.map(Utxo::from)
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			utxos.iter().map(|u| (&u.txid[..4], u.vout, u.value, u.height)).collect::<Vec<_>>(),
+			[
+				("9b5e", 1, 20_000, if f == ATOMICALS_PROXY_UNSPENT { 2571012 } else { 0 }),
+				("5d1c", 0, 1_000, 0)
+			]
+		);
+		assert!(utxos[0].atomicals.is_empty());
+		assert!(utxos[1].atomicals[0].ends_with("i0"));
+	}
+
+	assert!(serde_json::from_str::<Unspent>(r#"{"value":1,"tx_pos":0}"#).is_err());
+}
+
+#[tokio::test]
+async fn wait_until_utxo_matching_should_pick_any_criteria() {
+	let holding = |txid, id: &str| {
+		let mut u = unspent(txid, 0, 1_000);
+
+		u["atomicals"] = json!([id]);
+
+		u
+	};
+	let a = MockApi::default().respond(
+		"blockchain.scripthash.listunspent",
+		json!([unspent("aa", 0, 5_000), holding("bb", "xi0"), holding("cc", "yi0")]),
+	);
+	let (i, u) = a
+		.wait_until_utxo_matching(
+			&["tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec"],
+			|u| u.atomicals == ["yi0"],
+		)
+		.await
+		.unwrap();
+
+	assert_eq!((i, &*u.txid), (0, "cc"));
+}
+
+#[tokio::test]
+async fn only_funding_waits_should_require_confirmations() {
+	let address = "tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec";
+	let mut confirmed = unspent("aa", 0, 5_000);
+
+	confirmed["height"] = json!(100);
+
+	let unspents = json!([confirmed, unspent("bb", 1, 1_000)]);
+	let e = MockApi { funding_min_confirmations: 1, ..Default::default() }
+		.respond("blockchain.headers.subscribe", json!({ "height": 100, "hex": "" }))
+		.respond("blockchain.scripthash.listunspent", unspents.clone())
+		.respond("blockchain.scripthash.listunspent", unspents);
+
+	// E.g. the commit output, which the reveal may spend unconfirmed.
+	assert_eq!(e.wait_until_utxo(address, 500).await.unwrap().txid, "bb");
+	assert_eq!(e.wait_until_funding(address, 500).await.unwrap().txid, "aa");
+}
+
+#[tokio::test]
+async fn wait_until_funding_repriced_should_follow_the_fee_rate() {
+	let a = MockApi::default()
+		.respond("blockchain.scripthash.listunspent", json!([unspent("aa", 0, 4_000)]))
+		.respond("blockchain.scripthash.listunspent", json!([unspent("bb", 0, 6_000)]))
+		.respond("blockchain.scripthash.listunspent", json!([unspent("cc", 0, 8_000)]));
+	// The fee rate rises while waiting, 6,000 would have been enough at first.
+	let satsbytes = Mutex::new(VecDeque::from([5, 7, 7]));
+	let f = a.wait_until_funding_repriced(
+		"tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec",
+		|| async {
+			let satsbyte = satsbytes.lock().unwrap().pop_front().unwrap();
+
+			Ok((satsbyte * 1_000 - 500, satsbyte))
+		},
+	);
+
+	tokio::pin!(f);
+
+	let r = loop {
+		tokio::select! {
+			biased;
+			r = &mut f => break r,
+			_ = tokio::task::yield_now() => a.clock.advance(Duration::from_secs(1)),
+		}
+	};
+	let (u, satsbyte) = r.unwrap();
+
+	assert_eq!((&*u.txid, satsbyte), ("cc", 7));
+}
+
+// `get_ft_info` of a proxy which leaves most of the descriptive fields out.
+const SPARSE_FT_INFO: &str = r#"{"success":true,"response":{"global":null,"result":{
+	"$max_mints":10000,"$mint_amount":1000,"$mint_bitworkc":"aabbcc","$mint_height":0,
+	"$ticker":"quark","atomical_id":"6ef4ca2d7b4e9bc6a9c8fcd7b7d5a1bb1e0d8f5b2b6a4d1d1b2e3edc4c1b0c00i0",
+	"dft_info":{"mint_count":21},"subtype":"decentralized"
+}}}"#;
+// `get_ft_info` of a proxy which nulls them, with a `mint_info` of another shape.
+const NULLED_FT_INFO: &str = r#"{"success":true,"response":{"global":null,"result":{
+	"$bitwork":null,"$max_mints":10000,"$max_supply":null,"$mint_amount":1000,
+	"$mint_bitworkc":null,"$mint_bitworkr":null,"$mint_height":0,"$request_ticker":null,
+	"$request_ticker_status":null,"$ticker":"quark","$ticker_candidates":null,
+	"atomical_id":"6ef4ca2d7b4e9bc6a9c8fcd7b7d5a1bb1e0d8f5b2b6a4d1d1b2e3edc4c1b0c00i0",
+	"atomical_number":null,"atomical_ref":null,"confirmed":null,"dft_info":{"mint_count":21},
+	"location_summary":null,"mint_data":null,"mint_info":{"commit_height":"unknown"},
+	"subtype":"decentralized","type":null
+}}}"#;
+
+#[test]
+fn ft_info_of_every_proxy_should_parse() {
+	for f in [SPARSE_FT_INFO, NULLED_FT_INFO] {
+		let ft = serde_json::from_str::<Response<ResponseResult<Ft>>>(f).unwrap().response.result;
+
+		assert_eq!((&*ft.ticker, ft.remaining_mints()), ("quark", 9_979));
+		assert_eq!(ft.mint_bitworkc, if f == SPARSE_FT_INFO { "aabbcc" } else { "" });
+		assert!(ft.mint_info.is_none());
+	}
+
+	let t = serde_json::from_str::<Ticker>(
+		r#"{"atomical_id":"6ef4ca2d7b4e9bc6a9c8fcd7b7d5a1bb1e0d8f5b2b6a4d1d1b2e3edc4c1b0c00i0","candidates":null}"#,
+	)
+	.unwrap();
+
+	assert!(t.candidates.is_empty() && t.candidate_atomical_id.is_none());
+}
+
+#[tokio::test]
+async fn wait_until_atomical_indexed_should_time_out() {
+	let ft = serde_json::from_str::<Value>(SPARSE_FT_INFO).unwrap()["response"].clone();
+	let e = MockApi::default()
+		.respond("blockchain.atomicals.get_ft_info", json!({ "result": null }))
+		.respond("blockchain.atomicals.get_ft_info", json!({ "result": null }))
+		.respond("blockchain.atomicals.get_ft_info", ft)
+		.respond("blockchain.atomicals.get_ft_info", json!({ "result": null }))
+		.respond("blockchain.atomicals.get_ft_info", json!({ "result": null }));
+	let wait = |timeout| {
+		let f = e.wait_until_atomical_indexed("xi0", timeout);
+
+		async {
+			tokio::pin!(f);
+
+			loop {
+				tokio::select! {
+					biased;
+					r = &mut f => break r,
+					_ = tokio::task::yield_now() => e.clock.advance(Duration::from_secs(1)),
+				}
+			}
+		}
+	};
+
+	assert_eq!(wait(Duration::from_secs(60)).await.unwrap().result.ticker, "quark");
+	assert!(matches!(
+		wait(Duration::from_secs(5)).await.unwrap_err().downcast::<error::Error>(),
+		Ok(error::Error::NotIndexed { atomical_id, .. }) if atomical_id == "xi0"
+	));
+}
+
+#[tokio::test]
+async fn tickers_status_should_tolerate_failures() {
+	let ft = serde_json::from_str::<Value>(SPARSE_FT_INFO).unwrap()["response"].clone();
+	let e = MockApi::default()
+		.respond(
+			"blockchain.atomicals.get_by_ticker",
+			json!({ "result": { "atomical_id": "xi0" } }),
+		)
+		.respond("blockchain.atomicals.get_by_ticker", json!({ "result": null }))
+		.respond("blockchain.atomicals.get_by_ticker", json!({ "result": null }))
+		.respond("blockchain.atomicals.get_ft_info", ft);
+	let statuses = e.tickers_status(&["quark", "gone"]).await.unwrap();
+
+	assert_eq!(statuses.len(), 1);
+
+	let (t, s) = &statuses[0];
+
+	assert_eq!(t, "quark");
+	assert!(s.open);
+	assert_eq!((s.remaining_mints, s.remaining_supply), (9_979, 9_979_000));
+	// Nothing to report at all.
+	assert!(e.tickers_status(&["gone"]).await.is_err());
+}
+
+#[tokio::test]
+async fn unspents_of_proxies_out_of_sync_should_be_reconciled() {
+	// `aa` was spent, `cc` was just received and the third proxy is down.
+	let e = MockApi { base_uris: vec!["a".into(), "b".into(), "c".into()], ..Default::default() }
+		.respond(
+			"blockchain.scripthash.listunspent",
+			json!([unspent("aa", 0, 1_000), unspent("bb", 0, 2_000)]),
+		)
+		.respond(
+			"blockchain.scripthash.listunspent",
+			json!([unspent("bb", 0, 2_000), unspent("cc", 1, 3_000)]),
+		);
+	let utxos = e
+		.get_unspent_address_reconciled(
+			"tb1pemen3j4wvlryktkqsew8ext7wnsgqhmuzl7267rm3xk0th3gh04qr9wcec",
+		)
+		.await
+		.unwrap();
+
+	assert_eq!(utxos.iter().map(|u| &*u.txid).collect::<Vec<_>>(), ["bb"]);
+
+	let lists = || {
+		[["aa", "bb"], ["bb", "cc"]]
+			.map(|l| l.map(|t| serde_json::from_value::<Unspent>(unspent(t, 0, 1)).unwrap().into()))
+			.map(Vec::from)
+			.into()
+	};
+	let txids = |us: Vec<Utxo>| us.into_iter().map(|u| u.txid).collect::<Vec<_>>();
+
+	assert_eq!(txids(Reconciliation::Conservative.reconcile(lists())), ["bb"]);
+	assert_eq!(txids(Reconciliation::Optimistic.reconcile(lists())), ["aa", "bb", "cc"]);
+}
@@ -1,7 +1,13 @@
 // std
-use std::collections::HashMap;
+use std::{
+	collections::HashMap,
+	fmt::{Display, Formatter, Result as FmtResult},
+};
 // crates.io
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+// atomicalsir
+use super::error::Error;
 
 #[derive(Debug, Serialize)]
 pub struct Params<P>
@@ -18,6 +24,11 @@ where
 		Self { params }
 	}
 }
+impl Params<[(); 0]> {
+	pub fn empty() -> Self {
+		Self { params: [] }
+	}
+}
 
 // TODO: Handle errors.
 #[derive(Debug, Deserialize)]
@@ -26,11 +37,50 @@ pub struct Response<R> {
 	pub response: R,
 }
 
+// The proxy reports failures as `{"success":false,"code":..,"message":..}`, some wrap them into
+// an `error` object instead.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ErrorResponse {
+	Flat { success: bool, code: i64, message: String },
+	Wrapped { success: bool, error: RpcError },
+}
+impl ErrorResponse {
+	pub fn into_rpc_error(self) -> Option<RpcError> {
+		match self {
+			Self::Flat { success: false, code, message } => Some(RpcError { code, message }),
+			Self::Wrapped { success: false, error } => Some(error),
+			_ => None,
+		}
+	}
+}
+#[derive(Debug, Deserialize)]
+pub struct RpcError {
+	pub code: i64,
+	pub message: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ResponseResult<R> {
 	pub global: Option<Global>,
 	pub result: R,
 }
+impl<R> ResponseResult<Option<R>> {
+	// Some servers answer a lookup with `"result": null` if nothing matches.
+	pub fn found<S>(self, what: S) -> Result<ResponseResult<R>, Error>
+	where
+		S: Into<String>,
+	{
+		Ok(ResponseResult {
+			global: self.global,
+			result: self.result.ok_or_else(|| Error::NotFound(what.into()))?,
+		})
+	}
+}
+#[derive(Debug, Deserialize)]
+pub struct GlobalResponse {
+	pub global: Global,
+}
 #[derive(Debug, Deserialize)]
 pub struct Global {
 	pub atomical_count: u64,
@@ -43,12 +93,16 @@ pub struct Global {
 	pub server_time: String,
 }
 
+// Besides the ids, the fields are left out or `null` by some proxies.
 #[derive(Debug, Deserialize)]
 pub struct Ticker {
+	#[serde(default, deserialize_with = "null_as_default")]
 	pub status: String,
-	pub candidate_atomical_id: String,
+	pub candidate_atomical_id: Option<String>,
 	pub atomical_id: String,
+	#[serde(default, deserialize_with = "null_as_default")]
 	pub candidates: Vec<Candidate>,
+	#[serde(default, deserialize_with = "null_as_default")]
 	pub r#type: String,
 }
 #[derive(Debug, Deserialize)]
@@ -59,41 +113,121 @@ pub struct Candidate {
 	pub reveal_location_height: u64,
 }
 
+// Only the fields a mint depends on are required, the descriptive ones are left out or `null`
+// by some proxies and are dropped if malformed.
 #[derive(Debug, Deserialize)]
 pub struct Ft {
-	#[serde(rename = "$bitwork")]
-	pub bitwork: Bitwork,
+	#[serde(rename = "$bitwork", default, deserialize_with = "ok_or_none")]
+	pub bitwork: Option<Bitwork>,
 	#[serde(rename = "$max_mints")]
 	pub max_mints: u64,
-	#[serde(rename = "$max_supply")]
+	#[serde(rename = "$max_supply", default, deserialize_with = "null_as_default")]
 	pub max_supply: u64,
 	#[serde(rename = "$mint_amount")]
 	pub mint_amount: u64,
-	#[serde(rename = "$mint_bitworkc")]
+	#[serde(rename = "$mint_bitworkc", default, deserialize_with = "null_as_default")]
 	pub mint_bitworkc: String,
-	#[serde(rename = "$mint_bitworkr")]
+	#[serde(rename = "$mint_bitworkr", default)]
 	pub mint_bitworkr: Option<String>,
 	#[serde(rename = "$mint_height")]
 	pub mint_height: u64,
-	#[serde(rename = "$request_ticker")]
-	pub request_ticker: String,
-	#[serde(rename = "$request_ticker_status")]
-	pub request_ticker_status: TickerStatus,
+	#[serde(rename = "$request_ticker", default)]
+	pub request_ticker: Option<String>,
+	#[serde(rename = "$request_ticker_status", default, deserialize_with = "ok_or_none")]
+	pub request_ticker_status: Option<TickerStatus>,
 	#[serde(rename = "$ticker")]
 	pub ticker: String,
-	#[serde(rename = "$ticker_candidates")]
+	#[serde(rename = "$ticker_candidates", default, deserialize_with = "null_as_default")]
 	pub ticker_candidates: Vec<TickerCandidate>,
 	pub atomical_id: String,
+	#[serde(default, deserialize_with = "null_as_default")]
 	pub atomical_number: u64,
-	pub atomical_ref: String,
+	pub atomical_ref: Option<String>,
+	#[serde(default, deserialize_with = "null_as_default")]
 	pub confirmed: bool,
 	pub dft_info: DftInfo,
-	pub location_summary: LocationSummary,
-	pub mint_data: MintData,
-	pub mint_info: MintInfo,
+	#[serde(default, deserialize_with = "ok_or_none")]
+	pub location_summary: Option<LocationSummary>,
+	#[serde(default, deserialize_with = "ok_or_none")]
+	pub mint_data: Option<MintData>,
+	#[serde(default, deserialize_with = "ok_or_none")]
+	pub mint_info: Option<MintInfo>,
 	pub subtype: String,
+	#[serde(default, deserialize_with = "null_as_default")]
+	pub r#type: String,
+}
+impl Ft {
+	pub fn remaining_mints(&self) -> u64 {
+		self.max_mints.saturating_sub(self.dft_info.mint_count)
+	}
+}
+
+// A `null` is as good as a missing field.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: Default + Deserialize<'de>,
+{
+	Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// Rather than failing the whole response on a field nothing depends on.
+fn ok_or_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: DeserializeOwned,
+{
+	Ok(serde_json::from_value(Value::deserialize(deserializer)?).ok())
+}
+
+/// The common fields of any atomical.
+#[derive(Debug, Deserialize)]
+pub struct Atomical {
+	pub atomical_id: String,
+	pub r#type: String,
+	// Only set for the FTs.
+	#[serde(rename = "$ticker")]
+	pub ticker: Option<String>,
+}
+
+/// An atomical held by an address.
+#[derive(Clone, Debug, Serialize)]
+pub struct AtomicalHolding {
+	pub atomical_id: String,
 	pub r#type: String,
+	pub ticker: Option<String>,
+	/// The value of the UTXOs carrying it, which is the amount held for an FT.
+	///
+	/// Only the amount the proxy reports for it, if a UTXO carries other atomicals too.
+	pub amount: u64,
+	/// The value of the UTXOs it shares with other atomicals without the proxy reporting how
+	/// much of it is theirs.
+	///
+	/// Counted in full for each of them, so it doesn't add up across the atomicals.
+	pub ambiguous_amount: u64,
+	pub utxos: usize,
 }
+
+/// The atomicals held across a set of addresses at a point in time.
+///
+/// Everything is sorted, so that two snapshots diff cleanly.
+#[derive(Debug, Serialize)]
+pub struct PortfolioSnapshot {
+	/// Seconds since the Unix epoch.
+	pub timestamp: u64,
+	pub network: String,
+	/// Of all the addresses together, by atomical id.
+	pub totals: Vec<AtomicalHolding>,
+	/// By address.
+	pub addresses: Vec<AddressHoldings>,
+}
+#[derive(Debug, Serialize)]
+pub struct AddressHoldings {
+	pub address: String,
+	/// By atomical id.
+	pub holdings: Vec<AtomicalHolding>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Bitwork {
 	pub bitworkc: String,
@@ -189,16 +323,254 @@ pub struct MintInfo {
 pub struct Ctx {}
 
 #[derive(Debug, Deserialize)]
+pub struct Header {
+	pub height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Balance {
+	pub confirmed: u64,
+	// Could be negative while spending confirmed outputs.
+	pub unconfirmed: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MempoolTx {
+	pub tx_hash: String,
+	// `-1` if any of its inputs is unconfirmed.
+	pub height: i64,
+	pub fee: u64,
+}
+
+// Verbose transaction, as returned by bitcoind.
+#[derive(Debug, Deserialize)]
+pub struct Tx {
+	pub txid: String,
+	pub vsize: u64,
+	pub vin: Vec<Vin>,
+	pub vout: Vec<Vout>,
+	// Absent while the transaction is in the mempool.
+	#[serde(default)]
+	pub confirmations: u64,
+	pub blockhash: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+pub struct Vin {
+	// Absent for coinbase inputs.
+	pub txid: Option<String>,
+	pub vout: Option<u32>,
+}
+#[derive(Debug, Deserialize)]
+pub struct Vout {
+	// In BTC.
+	pub value: f64,
+	pub n: u32,
+	#[serde(rename = "scriptPubKey")]
+	pub script_pubkey: ScriptPubKey,
+}
+impl Vout {
+	pub fn sats(&self) -> u64 {
+		(self.value * 100_000_000.).round() as u64
+	}
+}
+#[derive(Debug, Deserialize)]
+pub struct ScriptPubKey {
+	pub hex: String,
+	pub address: Option<String>,
+}
+
+/// A page of the transactions of an atomical.
+#[derive(Debug, Deserialize)]
+pub struct AtomicalHistory {
+	pub result: Vec<AtomicalTx>,
+	// Not reported by every server.
+	#[serde(default)]
+	pub total: u64,
+}
+#[derive(Debug, Deserialize)]
+pub struct AtomicalTx {
+	pub txid: String,
+	pub height: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MintEntry {
+	pub txid: String,
+	pub height: u64,
+	pub recipient: Option<String>,
+	pub amount: u64,
+	// Whether the amount matches the ticker's mint amount.
+	pub valid: bool,
+}
+impl MintEntry {
+	/// The mint is the first output of the reveal transaction.
+	pub fn of(tx: &Tx, height: u64, mint_amount: u64) -> Self {
+		let mint = tx.vout.iter().find(|o| o.n == 0);
+		let amount = mint.map(Vout::sats).unwrap_or_default();
+
+		Self {
+			txid: tx.txid.clone(),
+			height,
+			recipient: mint.and_then(|o| o.script_pubkey.address.clone()),
+			amount,
+			valid: mint.is_some() && amount == mint_amount,
+		}
+	}
+}
+
+/// The mint progress of a ticker alone, see [`MintStatus`] for the funding address's as well.
+#[derive(Debug)]
+pub struct MintStatusLite {
+	pub atomical_id: String,
+	pub open: bool,
+	pub mint_count: u64,
+	pub max_mints: u64,
+	pub remaining_mints: u64,
+	/// The satoshis which are left to mint, `remaining_mints` times the mint amount.
+	pub remaining_supply: u64,
+}
+
+#[derive(Debug)]
+pub struct MintStatus {
+	pub atomical_id: String,
+	pub open: bool,
+	pub mint_count: u64,
+	pub max_mints: u64,
+	pub remaining_mints: u64,
+	pub balance: Balance,
+	pub pending: Vec<MempoolTx>,
+}
+
+/// Electrum protocol script hash, the reversed SHA256 of a script pubkey in hex.
+///
+/// Use [`crate::util::address2scripthash`] to get one from an address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct ScriptHash(pub(crate) String);
+impl AsRef<str> for ScriptHash {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+impl Display for ScriptHash {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		f.write_str(&self.0)
+	}
+}
+
+/// A fee rate, in sat/vB.
+///
+/// Use the constructors to convert from the other units, e.g. the node's BTC/kvB.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct FeeRate(f64);
+impl FeeRate {
+	pub fn from_sat_per_vb(sat_per_vb: f64) -> Self {
+		Self(sat_per_vb)
+	}
+
+	pub fn from_btc_per_kvb(btc_per_kvb: f64) -> Self {
+		Self(btc_per_kvb * 100_000.)
+	}
+
+	/// Rounded up to a whole sat/vB, ignoring the float noise of the conversions.
+	pub fn sat_per_vb_ceil(self) -> u64 {
+		((self.0 * 1_000.).round() / 1_000.).ceil() as u64
+	}
+
+	/// The fee of a transaction of `vsize` vbytes.
+	pub fn fee_of(self, vsize: u64) -> u64 {
+		((self.0 * vsize as f64 * 1_000.).round() / 1_000.).ceil() as u64
+	}
+
+	pub fn max(self, other: Self) -> Self {
+		if self >= other {
+			self
+		} else {
+			other
+		}
+	}
+}
+impl Display for FeeRate {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		write!(f, "{:.1} sat/vB", self.0)
+	}
+}
+
+/// An entry of `listunspent`, whichever shape the proxy returns it in.
+///
+/// The proxy versions disagree on the field names, e.g. `tx_hash` or `txid` and `tx_pos` or
+/// `vout`, some leave the height out and the atomicals come as ids or as objects.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawUnspent")]
 pub struct Unspent {
 	pub txid: String,
-	pub tx_hash: String,
-	pub index: u32,
-	pub tx_pos: u32,
 	pub vout: u32,
 	pub height: u64,
 	pub value: u64,
-	// TODO: Check the real type.
-	pub atomicals: Vec<()>,
+	// The ids of the atomicals carried by the output.
+	pub atomicals: Vec<String>,
+	// The amount of each of them, if the proxy reports it.
+	pub atomical_values: HashMap<String, u64>,
+}
+impl TryFrom<RawUnspent> for Unspent {
+	type Error = String;
+
+	fn try_from(v: RawUnspent) -> Result<Self, Self::Error> {
+		let atomicals = v.atomicals.map(atomicals_of).unwrap_or_default();
+
+		Ok(Self {
+			txid: v.tx_hash.or(v.txid).ok_or("unspent without a txid")?,
+			vout: v
+				.tx_pos
+				.or(v.vout)
+				.or(v.index)
+				.or(v.output_index)
+				.ok_or("unspent without an output index")?,
+			// Unconfirmed parents are reported as `-1`.
+			height: v.height.unwrap_or_default().max(0) as _,
+			value: v.value,
+			atomical_values: atomicals
+				.iter()
+				.filter_map(|(id, value)| Some((id.to_owned(), (*value)?)))
+				.collect(),
+			atomicals: atomicals.into_iter().map(|(id, _)| id).collect(),
+		})
+	}
+}
+#[derive(Debug, Deserialize)]
+struct RawUnspent {
+	txid: Option<String>,
+	tx_hash: Option<String>,
+	vout: Option<u32>,
+	tx_pos: Option<u32>,
+	index: Option<u32>,
+	output_index: Option<u32>,
+	height: Option<i64>,
+	#[serde(alias = "satoshis")]
+	value: u64,
+	atomicals: Option<Value>,
+}
+// Either a list of ids, a list of objects with an `atomical_id` or a map keyed by the ids.
+//
+// Along with the amount of each, if an object has a `value` or the map is of the amounts.
+fn atomicals_of(v: Value) -> Vec<(String, Option<u64>)> {
+	let value_of = |v: &Value| v.as_u64().or_else(|| v.get("value")?.as_u64());
+
+	match v {
+		Value::Array(vs) => vs
+			.into_iter()
+			.filter_map(|v| match v {
+				Value::String(id) => Some((id, None)),
+				Value::Object(mut o) => match o.remove("atomical_id") {
+					Some(Value::String(id)) => Some((id, o.get("value").and_then(Value::as_u64))),
+					_ => None,
+				},
+				_ => None,
+			})
+			.collect(),
+		Value::Object(o) => o.into_iter().map(|(id, v)| (id, value_of(&v))).collect(),
+		_ => Vec::new(),
+	}
 }
 
 #[derive(Clone, Debug)]
@@ -207,10 +579,29 @@ pub struct Utxo {
 	// The same as `output_index` and `index`.
 	pub vout: u32,
 	pub value: u64,
-	pub atomicals: Vec<()>,
+	// `0` while the output is still in the mempool.
+	pub height: u64,
+	pub atomicals: Vec<String>,
+	pub atomical_values: HashMap<String, u64>,
+}
+impl Utxo {
+	pub fn confirmations(&self, tip: u64) -> u64 {
+		if self.height == 0 {
+			0
+		} else {
+			tip.saturating_sub(self.height) + 1
+		}
+	}
 }
 impl From<Unspent> for Utxo {
 	fn from(v: Unspent) -> Self {
-		Self { txid: v.tx_hash, vout: v.tx_pos, value: v.value, atomicals: v.atomicals }
+		Self {
+			txid: v.txid,
+			vout: v.vout,
+			value: v.value,
+			height: v.height,
+			atomicals: v.atomicals,
+			atomical_values: v.atomical_values,
+		}
 	}
 }
@@ -1,16 +1,17 @@
 // std
 use std::{
+	collections::HashSet,
 	fs::{self, OpenOptions},
-	io::Write,
+	io::{ErrorKind, Write},
 	ops::Range,
 	path::Path,
 	str::FromStr,
 	sync::{
-		atomic::{AtomicBool, Ordering},
-		Arc, Mutex,
+		atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+		Arc, LazyLock, Mutex,
 	},
-	thread::{self, sleep, JoinHandle},
-	time::{Duration, SystemTime, UNIX_EPOCH},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use chrono::Utc;
@@ -24,64 +25,738 @@ use bitcoin::{
 	psbt::Input,
 	secp256k1::{All, Keypair, Message, Secp256k1, XOnlyPublicKey},
 	sighash::{Prevouts, SighashCache},
-	taproot::{LeafVersion, Signature, TaprootBuilder, TaprootSpendInfo},
+	taproot::{LeafVersion, Signature, TaprootSpendInfo},
 	transaction::Version,
 	Address, Amount, Network, OutPoint, Psbt, ScriptBuf, Sequence, TapSighashType, Transaction,
-	TxIn, TxOut, Witness,
+	TxIn, TxOut, Txid, Witness,
 };
-use serde::Serialize;
+use futures::{stream, StreamExt};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
 // atomicalsir
 use crate::{
-	electrumx::{r#type::Utxo, Api, ElectrumX, ElectrumXBuilder},
+	electrumx::{
+		self,
+		config::ClientConfig,
+		error::Error as ElectrumXError,
+		funding::{FundingMatcher, FundingPool, FundingScanner},
+		r#type::{
+			FeeRate, Ft, MintEntry, MintStatus, MintStatusLite, PortfolioSnapshot, Response, Utxo,
+		},
+		registry::ClientRegistry,
+		scenario::Scenario,
+		selection::UtxoSelection,
+		Api, Config, ElectrumX, ElectrumXBuilder, Http,
+	},
 	prelude::*,
-	util,
+	util::{self, InputKind},
 	wallet::Wallet as RawWallet,
 };
 
+// The miners of a process configured alike share their electrumx client.
+static CLIENTS: LazyLock<ClientRegistry> = LazyLock::new(Default::default);
+
+/// Close the clients of every entry point, logging their summary, whichever way they exited.
+pub async fn shutdown() {
+	CLIENTS.shutdown().await;
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
-	network: Network,
-	electrumx: &str,
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
 	wallet_dir: &Path,
 	ticker: &str,
 	max_fee: u64,
 	max_mints: usize,
+	consolidation: Option<Consolidation>,
+	commit_confirmations: u64,
+	persist_state: bool,
+	reprice_funding: bool,
+	verify_commit: bool,
+	workers: Option<usize>,
+	hash_rate_interval: Option<Duration>,
+	checkpoint_interval: Option<Duration>,
+	priority: WorkerPriority,
+	stop: StopCondition,
+	spend_cap: Option<u64>,
+	verify_broadcast: Option<Duration>,
+	require_healthy: usize,
+	funding_quota: Option<usize>,
+	scenario: Option<&Path>,
 ) -> Result<()> {
-	let m = MinerBuilder { network, electrumx, wallet_dir, ticker, max_fee }.build()?;
+	let m = MinerBuilder {
+		ticker,
+		max_fee,
+		consolidation,
+		commit_confirmations,
+		persist_state,
+		reprice_funding,
+		verify_commit,
+		workers,
+		hash_rate_interval,
+		checkpoint_interval,
+		priority,
+		stop,
+		spend_cap,
+		verify_broadcast,
+		require_healthy,
+		record: scenario.is_some(),
+		..MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir)
+	}
+	.build()
+	.await?;
+	let mut pool = funding_quota
+		.map(|q| FundingPool::new(m.wallets.iter().map(|w| (w.funding.address.to_string(), q))));
+	let r = async {
+		let mut mints = 0;
+		while mints < max_mints {
+			for w in &m.wallets {
+				if m.api.remaining_mints(&m.ticker).await? == 0 {
+					tracing::info!("no mints left for {}", m.ticker);
+
+					return Ok(());
+				}
+
+				tracing::info!("mining {}/{}", mints + 1, max_mints);
 
-	let mut mints = 0;
-	while mints < max_mints {
-		for w in &m.wallets {
-			tracing::info!("mining {}/{}", mints + 1, max_mints);
+				// With quotas, the wallet which gets funded first takes the turn.
+				let (w, funding) = match &mut pool {
+					Some(p) => m.draw_funding(p).await?,
+					None => (w, None),
+				};
 
-			// TODO mints 在 mine 成功时 +1，失败时继续
-			// 但需要确认失败时是否可以安全地继续
-			m.mine(w).await?;
+				// TODO mints 在 mine 成功时 +1，失败时继续
+				// 但需要确认失败时是否可以安全地继续
+				if let Err(e) = m.mine(w, funding).await {
+					// Given up on purpose, there is no point to go on.
+					if let Some(ElectrumXError::MiningAborted { .. }) = e.downcast_ref() {
+						tracing::warn!("{e}");
 
-			mints += 1;
-			if mints >= max_mints {
-				tracing::info!("max mints reached");
-				break;
+						return Ok(());
+					}
+
+					Err(e)?;
+				}
+				m.consolidate(w).await?;
+
+				mints += 1;
+				if mints >= max_mints {
+					tracing::info!("max mints reached");
+					break;
+				}
 			}
 		}
+
+		Ok::<_, Error>(())
+	}
+	.await;
+
+	if let Some(p) = scenario {
+		m.api.scenario(r.as_ref().map(|_| ()).map_err(|e| format!("{e:#}"))).save(p)?;
+
+		tracing::info!("scenario saved to {}", p.display());
+	}
+
+	r
+}
+
+/// Replay the electrumx traffic of the scenario at `path`, as recorded by [`run`], offline.
+///
+/// Reports each response the way the client sees it, errors included, then the outcome of the
+/// recorded run.
+pub async fn replay(path: &Path) -> Result<Scenario> {
+	let s = Scenario::load(path)?;
+
+	tracing::info!(
+		"replaying {} exchanges of a run on {} against {}",
+		s.exchanges.len(),
+		s.network,
+		s.base_uris.join(", ")
+	);
+
+	for e in &s.exchanges {
+		match s.post::<_, _, Response<serde_json::Value>>(&e.method, &e.request).await {
+			Ok(r) => tracing::info!(method = e.method, "{}", r.response),
+			Err(err) => tracing::warn!(method = e.method, "{err:#}"),
+		}
+	}
+
+	match &s.outcome {
+		Ok(()) => tracing::info!("the recorded run succeeded"),
+		Err(e) => tracing::warn!("the recorded run failed: {e}"),
+	}
+
+	Ok(s)
+}
+
+/// Report what minting `ticker` with each wallet would cost and pay, without spending anything.
+pub async fn simulate(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	ticker: &str,
+	max_fee: u64,
+) -> Result<Vec<MintSimulation>> {
+	let m = MinerBuilder {
+		ticker,
+		max_fee,
+		..MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir)
+	}
+	.build()
+	.await?;
+	let mut simulations = Vec::new();
+
+	for w in &m.wallets {
+		let s = m.simulate_mint(w).await?;
+
+		tracing::info!(
+			"minting {} sats of {} ({}) would cost {} sats of fees at {} sat/vB, with bitworkc \
+			{} and bitworkr {:?}",
+			s.mint_amount,
+			s.ticker,
+			s.ticker_id,
+			s.commit_fee + s.reveal_fee,
+			s.satsbyte,
+			s.bitworkc,
+			s.bitworkr
+		);
+
+		match &s.funding_utxo {
+			Some(u) => tracing::info!(
+				"{} would fund the {} sats from {}:{}, the tokens would go to {}",
+				s.funding_address,
+				s.required,
+				u.txid,
+				u.vout,
+				s.stash_address
+			),
+			None => tracing::warn!(
+				"{} holds {} sats but the mint needs a UTXO of {} sats",
+				s.funding_address,
+				s.balance,
+				s.required
+			),
+		}
+
+		simulations.push(s);
+	}
+
+	Ok(simulations)
+}
+
+/// Write a [`PortfolioSnapshot`] of the atomicals held by the wallets of `wallet_dir` to `path`.
+pub async fn snapshot(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	path: &Path,
+) -> Result<PortfolioSnapshot> {
+	let m = MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir).build().await?;
+	let addresses = m
+		.wallets
+		.iter()
+		.flat_map(|w| [w.funding.address.to_string(), w.stash.address.to_string()])
+		.collect::<Vec<_>>();
+	let s = m.api.portfolio_snapshot(&addresses).await?;
+
+	fs::write(path, serde_json::to_vec_pretty(&s)?)?;
+
+	for t in s.totals.iter().filter(|t| t.ambiguous_amount > 0) {
+		tracing::warn!(
+			"{} sats of {} share their UTXOs with other atomicals, left out of its amount",
+			t.ambiguous_amount,
+			t.atomical_id
+		);
+	}
+
+	tracing::info!(
+		"snapshot of {} atomicals across {} addresses saved to {}",
+		s.totals.len(),
+		s.addresses.len(),
+		path.display()
+	);
+
+	Ok(s)
+}
+
+/// Report the mint progress of `ticker` along with the balance and the pending transactions of
+/// the funding address of each wallet of `wallet_dir`.
+pub async fn status(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	ticker: &str,
+) -> Result<Vec<MintStatus>> {
+	let m = MinerBuilder {
+		ticker,
+		..MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir)
+	}
+	.build()
+	.await?;
+	// Warns if the index is lagging, which would make the figures below stale.
+	let lag = m.api.index_lag().await?;
+
+	tracing::info!("atomicals index lags the chain tip by {lag} blocks");
+
+	let mut statuses = Vec::new();
+
+	for w in &m.wallets {
+		let s = m.api.mint_status(ticker, w.funding.address.to_string()).await?;
+
+		tracing::info!(
+			"{ticker} ({}) is {}, {}/{} minted, {} mints left",
+			s.atomical_id,
+			if s.open { "open" } else { "closed" },
+			s.mint_count,
+			s.max_mints,
+			s.remaining_mints
+		);
+		tracing::info!(
+			"{} holds {} confirmed and {} unconfirmed sats",
+			w.funding.address,
+			s.balance.confirmed,
+			s.balance.unconfirmed
+		);
+
+		for t in &s.pending {
+			tracing::info!(
+				"pending tx {} at height {} pays {} sats of fee",
+				t.tx_hash,
+				t.height,
+				t.fee
+			);
+		}
+
+		statuses.push(s);
+	}
+
+	Ok(statuses)
+}
+
+/// Audit every mint of `ticker`, reporting the ones which went to the wallets of `wallet_dir`.
+///
+/// Walks the whole history of the ticker, so it's slow for tickers with many mints.
+pub async fn mint_history(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	ticker: &str,
+) -> Result<Vec<MintEntry>> {
+	let m = MinerBuilder {
+		ticker,
+		..MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir)
+	}
+	.build()
+	.await?;
+	let mints = m.api.get_ticker_mints(ticker).await?;
+	let stashes = m.wallets.iter().map(|w| w.stash.address.to_string()).collect::<HashSet<_>>();
+
+	tracing::info!(
+		"{ticker} has {} mints, {} of them invalid",
+		mints.len(),
+		mints.iter().filter(|e| !e.valid).count()
+	);
+
+	let own = mints
+		.iter()
+		.filter(|e| e.recipient.as_ref().is_some_and(|r| stashes.contains(r)))
+		.collect::<Vec<_>>();
+	let txids = own.iter().map(|e| &e.txid).collect::<Vec<_>>();
+	let confirmations = m.api.get_confirmations(&txids).await?;
+	let fees = m.api.get_tx_fees(&txids).await?;
+
+	for e in own {
+		let (fee, fee_rate) = fees[&e.txid];
+
+		tracing::info!(
+			"{} minted {} sats in {} at height {}, {} confirmations, {fee} sats of fee at \
+			 {fee_rate}{}",
+			e.recipient.as_deref().unwrap_or_default(),
+			e.amount,
+			e.txid,
+			e.height,
+			confirmations[&e.txid],
+			if e.valid { "" } else { ", invalid" }
+		);
+	}
+
+	Ok(mints)
+}
+
+/// Report the mint progress of each of the `tickers` at once, without any wallet involved.
+///
+/// A ticker which can't be fetched is left out, this only fails if none can.
+pub async fn tickers_status(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	tickers: &[String],
+) -> Result<Vec<(String, MintStatusLite)>> {
+	let m = MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir).build().await?;
+	let tickers = tickers.iter().map(|t| t.to_lowercase()).collect::<Vec<_>>();
+	let statuses =
+		m.api.tickers_status(&tickers.iter().map(String::as_str).collect::<Vec<_>>()).await?;
+
+	for (t, s) in &statuses {
+		tracing::info!(
+			"{t} ({}) is {}, {}/{} minted, {} mints and {} sats left",
+			s.atomical_id,
+			if s.open { "open" } else { "closed" },
+			s.mint_count,
+			s.max_mints,
+			s.remaining_mints,
+			s.remaining_supply
+		);
+	}
+
+	Ok(statuses)
+}
+
+/// Watch the funding address of each wallet of `wallet_dir`, reporting every new clean UTXO of
+/// at least `satoshis` as it comes in.
+///
+/// Runs until interrupted, the UTXOs which are already there are reported first.
+pub async fn watch_funding(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	satoshis: u64,
+) -> Result<()> {
+	let m = MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir).build().await?;
+	let mut utxos = stream::select_all(m.wallets.iter().map(|w| {
+		let address = w.funding.address.to_string();
+
+		FundingScanner::new(&address, satoshis)
+			.into_stream(&*m.api)
+			.map(move |r| r.map(|u| (address.clone(), u)))
+			.boxed()
+	}));
+
+	while let Some(r) = utxos.next().await {
+		let (address, u) = r?;
+
+		tracing::info!("{address} received {}:{} of {} sats", u.txid, u.vout, u.value);
 	}
 
 	Ok(())
 }
 
+/// Split the clean funding UTXOs of each wallet of `wallet_dir` into `count` outputs of `value`
+/// satoshis, so that several mints can be funded at once.
+///
+/// A wallet which can't afford the split is skipped.
+pub async fn split_funding(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	count: usize,
+	value: u64,
+) -> Result<Vec<Utxo>> {
+	let m = MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir).build().await?;
+	let mut split = Vec::new();
+
+	for w in &m.wallets {
+		let us = match m.split_funding(w, count, value).await {
+			Err(e)
+				if matches!(
+					e.downcast_ref::<ElectrumXError>(),
+					Some(ElectrumXError::InsufficientFunds { .. })
+				) =>
+			{
+				tracing::warn!("{} can't afford the split, {e}", w.funding.address);
+
+				continue;
+			},
+			r => r?,
+		};
+
+		tracing::info!("{} split into {} UTXOs", w.funding.address, us.len());
+
+		split.extend(us);
+	}
+
+	Ok(split)
+}
+
+/// Export the commit of a mint of `ticker` with each wallet of `wallet_dir`, as an unsigned PSBT
+/// for an external wallet to sign, e.g. a hardware wallet.
+///
+/// The PSBTs are logged as hex, to be signed and then passed to [`broadcast_psbts`]. The reveal is
+/// not part of them.
+pub async fn export_psbt(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	ticker: &str,
+	max_fee: u64,
+) -> Result<Vec<Psbt>> {
+	let m = MinerBuilder {
+		ticker,
+		max_fee,
+		..MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir)
+	}
+	.build()
+	.await?;
+	let mut psbts = Vec::new();
+
+	for w in &m.wallets {
+		let psbt = m.export_psbt(w).await?;
+
+		tracing::info!("unsigned commit of {}: {}", w.funding.address, psbt.serialize_hex());
+
+		psbts.push(psbt);
+	}
+
+	Ok(psbts)
+}
+
+/// Broadcast the hex `psbts`, once signed by an external wallet.
+///
+/// They all go out even if some fail, which fails the whole once they are done.
+pub async fn broadcast_psbts(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	psbts: &[String],
+) -> Result<Vec<Txid>> {
+	let m = MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir).build().await?;
+	let rs = m
+		.api
+		.broadcast_signed_psbts(psbts.iter().map(util::psbt_from_hex).collect::<Result<_>>()?)
+		.await?;
+
+	let mut txids = Vec::new();
+
+	for (i, r) in rs.into_iter().enumerate() {
+		match r {
+			Ok(txid) => {
+				tracing::info!("✅ Successfully sent tx {txid}");
+
+				txids.push(txid);
+			},
+			Err(e) => tracing::warn!("❌ Failed to send PSBT {i}: {e:#}"),
+		}
+	}
+
+	if txids.len() < psbts.len() {
+		Err(anyhow::anyhow!(
+			"{} of {} PSBTs failed to broadcast",
+			psbts.len() - txids.len(),
+			psbts.len()
+		))?;
+	}
+
+	Ok(txids)
+}
+
+/// Mine against the synthetic `bitwork` for `duration`, to measure the hash rate of the machine.
+///
+/// Signs and matches the commits just like a real search, every solution is checked again once
+/// found. No wallet nor network is involved.
+pub fn benchmark(
+	bitwork: &str,
+	duration: Duration,
+	workers: Option<usize>,
+	hash_rate_interval: Option<Duration>,
+) -> Result<Benchmark> {
+	// The attempts a solution takes on average.
+	let attempts = util::bitwork_attempts(bitwork)?;
+
+	let workers = workers.unwrap_or_else(num_cpus::get).max(1);
+	let secp = Secp256k1::new();
+	let funding = Keypair::new(&secp, &mut bitcoin::secp256k1::rand::thread_rng());
+	let funding_xpk = funding.x_only_public_key().0;
+	let funding_kp = funding.tap_tweak(&secp, None).to_inner();
+	let funding_spk = ScriptBuf::new_p2tr(&secp, funding_xpk, None);
+	let out = |value| TxOut { value: Amount::from_sat(value), script_pubkey: funding_spk.clone() };
+	// Shaped like the commit of a mint, paying the reveal and a refund.
+	let template = Transaction {
+		version: Version::ONE,
+		lock_time: LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: OutPoint::new(Txid::all_zeros(), 0),
+			..Default::default()
+		}],
+		output: vec![out(10_000), out(89_000)],
+	};
+	let prevouts = [out(100_000)];
+	let deadline = Instant::now() + duration;
+	let solutions = Arc::new(Mutex::new(Vec::new()));
+	let hash_rate = HashRate::start("benchmark", workers as _, hash_rate_interval);
+	let ts = Miner::sequence_ranges(workers as _)
+		.into_iter()
+		.map(|r| {
+			let secp = secp.clone();
+			let bitwork = bitwork.to_owned();
+			let template = template.clone();
+			let prevouts = prevouts.clone();
+			let solutions = solutions.clone();
+			let hashes = hash_rate.counter();
+
+			thread::spawn(move || {
+				for s in r {
+					// Reading the clock on every hash would slow the search down.
+					if s % 1_000 == 0 && Instant::now() >= deadline {
+						break;
+					}
+
+					let tx = Miner::commit_tx_of(
+						&secp,
+						&funding_kp,
+						funding_xpk,
+						&template,
+						&prevouts,
+						s,
+					)?;
+
+					hashes.fetch_add(1, Ordering::Relaxed);
+
+					if util::matches_bitwork(&tx.txid(), &bitwork) {
+						solutions.lock().unwrap().push(tx);
+					}
+				}
+
+				Ok(())
+			})
+		})
+		.collect::<Vec<JoinHandle<Result<()>>>>();
+
+	for t in ts {
+		t.join().unwrap()?;
+	}
+
+	let hashes = hash_rate.hashes.load(Ordering::Relaxed);
+	let elapsed = hash_rate.started.elapsed();
+
+	hash_rate.finish();
+
+	let solutions = solutions.lock().unwrap().drain(..).collect::<Vec<_>>();
+
+	for tx in &solutions {
+		Miner::ensure_bitwork(tx, bitwork)?;
+	}
+
+	let b = Benchmark {
+		bitwork: bitwork.into(),
+		workers,
+		hashes,
+		elapsed,
+		solutions: solutions.iter().map(|tx| tx.txid()).collect(),
+	};
+
+	tracing::info!(
+		"benchmark of {} at {:.0} H/s across {} workers found {} solutions, about {:.1} were \
+		expected, one takes {:.1?} on average",
+		b.bitwork,
+		b.hash_rate(),
+		b.workers,
+		b.solutions.len(),
+		b.hashes as f64 / attempts,
+		util::estimate_bitwork_time(&b.bitwork, b.hash_rate())?
+	);
+
+	for txid in &b.solutions {
+		tracing::debug!("solution {txid}");
+	}
+
+	Ok(b)
+}
+
+/// Mint the file at `path` as an NFT, with the first wallet of `wallet_dir`.
+#[allow(clippy::too_many_arguments)]
+pub async fn mint_file(
+	network: Option<Network>,
+	electrumx: Option<&str>,
+	electrumx_config: Option<&Path>,
+	wallet_dir: &Path,
+	path: &Path,
+	bitworkc: Option<&str>,
+	max_fee: u64,
+	commit_confirmations: u64,
+	persist_state: bool,
+	reprice_funding: bool,
+	verify_commit: bool,
+	workers: Option<usize>,
+	hash_rate_interval: Option<Duration>,
+	checkpoint_interval: Option<Duration>,
+	priority: WorkerPriority,
+	stop: StopCondition,
+	spend_cap: Option<u64>,
+	verify_broadcast: Option<Duration>,
+	require_healthy: usize,
+) -> Result<()> {
+	let file = FilePayload::load(path)?;
+	let m = MinerBuilder {
+		// Only labels the pending mint.
+		ticker: &file.name,
+		max_fee,
+		commit_confirmations,
+		persist_state,
+		reprice_funding,
+		verify_commit,
+		workers,
+		hash_rate_interval,
+		checkpoint_interval,
+		priority,
+		// An NFT has no mints to run out of.
+		stop: StopCondition { mint_check_interval: None, ..stop },
+		spend_cap,
+		verify_broadcast,
+		require_healthy,
+		..MinerBuilder::new(network, electrumx, electrumx_config, wallet_dir)
+	}
+	.build()
+	.await?;
+	let w = m.wallets.first().ok_or_else(|| anyhow::anyhow!("no wallet found"))?;
+
+	m.mint_file(w, &file, bitworkc.map(Into::into)).await
+}
+
 #[derive(Debug)]
 struct Miner {
 	network: Network,
-	api: ElectrumX,
+	api: Arc<ElectrumX>,
 	wallets: Vec<Wallet>,
 	ticker: String,
 	max_fee: u64,
+	consolidation: Option<Consolidation>,
+	// Wait for the commit to have this many confirmations before broadcasting the reveal.
+	commit_confirmations: u64,
+	// Keep a `MintState` while a mint is in progress, to resume it after a crash.
+	persist_state: bool,
+	// Re-estimate the fees on each poll of the funding wait.
+	reprice_funding: bool,
+	// Make sure the commit output is unspent right before broadcasting the reveal.
+	verify_commit: bool,
+	// Mining threads of each stage.
+	workers: usize,
+	// Between the hash rate reports while mining, `None` only reports once done.
+	hash_rate_interval: Option<Duration>,
+	// Between the saves of a `MiningCheckpoint` while mining the commit, `None` never saves one.
+	checkpoint_interval: Option<Duration>,
+	priority: WorkerPriority,
+	stop: StopCondition,
 }
 impl Miner {
 	const BASE_BYTES: f64 = 10.5;
 	const BROADCAST_SLEEP_SECONDS: u32 = 15;
+	// A ticker deployed lately may resolve a while before the index knows its DFT.
+	const INDEX_TIMEOUT: Duration = Duration::from_secs(120);
 	const INPUT_BYTES_BASE: f64 = 57.5;
 	const MAX_BROADCAST_NUM: u32 = 20;
 	const MAX_SEQUENCE: u32 = u32::MAX;
+	// Held by the output carrying the NFT, as atomicals-js does.
+	const NFT_OUTPUT_VALUE: u64 = 1_000;
 	// OP_RETURN size
 	// 8-bytes value(roughly estimate), a one-byte script’s size
 	// actual value size depends precisely on final nonce
@@ -90,11 +765,58 @@ impl Miner {
 	const REVEAL_INPUT_BYTES_BASE: f64 = 66.;
 	const SEQ_RANGE_BUCKET: u32 = 100_000_000;
 
-	async fn mine(&self, wallet: &Wallet) -> Result<()> {
-		let concurrency: u32 = num_cpus::get() as u32;
-		let seq_range_per_revealer: u32 = Self::SEQ_RANGE_BUCKET / concurrency;
+	// Mint with `funding` if it still covers the fees, otherwise wait for a funding UTXO.
+	async fn mine(&self, wallet: &Wallet, funding: Option<Utxo>) -> Result<()> {
+		if self.persist_state {
+			if let Some(s) = MintState::load(wallet)? {
+				return self.resume(wallet, s).await;
+			}
+		}
+
+		let d = self.prepare_data(wallet, funding).await?;
+
+		self.commit_and_reveal(wallet, d).await
+	}
+
+	// Draw the funding UTXO of the next mint from the `pool`, along with the wallet it belongs
+	// to.
+	async fn draw_funding(&self, pool: &mut FundingPool) -> Result<(&Wallet, Option<Utxo>)> {
+		// The fees only depend on the sizes, which are the same for every wallet.
+		let w = self.wallets.first().ok_or_else(|| anyhow::anyhow!("no wallet to mint with"))?;
+		let (ft, satsbyte, additional_outputs, payload) = self.dmt_of(w).await?;
+		let (_, reveal_script, _) = self.reveal_of(w, "dmt", &payload, &additional_outputs)?;
+		let required = Self::fees_of(
+			satsbyte,
+			reveal_script.len(),
+			&additional_outputs,
+			ft.mint_bitworkr.is_some(),
+		)
+		.commit_and_reveal_and_outputs;
+		let (i, u) = pool.next(&*self.api, required).await?;
+
+		Ok((&self.wallets[i], Some(u)))
+	}
+
+	/// Mint `file` as an NFT, its content being the payload of the reveal.
+	async fn mint_file(
+		&self,
+		wallet: &Wallet,
+		file: &FilePayload,
+		bitworkc: Option<String>,
+	) -> Result<()> {
+		if self.persist_state {
+			if let Some(s) = MintState::load(wallet)? {
+				return self.resume(wallet, s).await;
+			}
+		}
+
+		let d = self.prepare_file_data(wallet, file, bitworkc).await?;
+
+		self.commit_and_reveal(wallet, d).await
+	}
 
-		let d = self.prepare_data(wallet).await?;
+	async fn commit_and_reveal(&self, wallet: &Wallet, d: Data) -> Result<()> {
+		let concurrency = self.workers as u32;
 
 		tracing::info!("attempt to find a solution based on {d:#?}");
 		tracing::info!("\nStarting commit stage mining now...\n");
@@ -103,6 +825,8 @@ impl Miner {
 		let Data {
 			secp,
 			satsbyte,
+			op_type,
+			payload,
 			bitworkc,
 			bitworkr,
 			additional_outputs,
@@ -111,111 +835,116 @@ impl Miner {
 			fees,
 			funding_utxo,
 		} = d.clone();
-		let reveal_spk = ScriptBuf::new_p2tr(
-			&secp,
-			reveal_spend_info.internal_key(),
-			reveal_spend_info.merkle_root(),
-		);
+		let commit_address = util::commit_address(
+			&wallet.funding.x_only_public_key,
+			&op_type,
+			&payload,
+			self.network,
+		)?;
+		let reveal_spk = commit_address.script_pubkey();
 		let funding_spk = wallet.funding.address.script_pubkey();
 		let commit_input = vec![TxIn {
 			previous_output: OutPoint::new(funding_utxo.txid.parse()?, funding_utxo.vout),
 			..Default::default()
 		}];
-		let commit_output = {
-			let spend = TxOut {
-				value: Amount::from_sat(fees.reveal_and_outputs),
-				script_pubkey: reveal_spk.clone(),
-			};
-			let refund = {
-				let r = funding_utxo.value.saturating_sub(fees.reveal_and_outputs).saturating_sub(
-					fees.commit + (Self::OUTPUT_BYTES_BASE * satsbyte as f64).floor() as u64,
-				);
-
-				if r > 0 {
-					Some(TxOut { value: Amount::from_sat(r), script_pubkey: funding_spk.clone() })
-				} else {
-					None
-				}
-			};
-
-			if let Some(r) = refund {
-				vec![spend, r]
-			} else {
-				vec![spend]
-			}
-		};
+		let commit_output = Self::commit_output_of(&d, reveal_spk.clone(), funding_spk.clone());
 		let commit_prevouts = [TxOut {
 			value: Amount::from_sat(funding_utxo.value),
 			script_pubkey: funding_spk.clone(),
 		}];
-		let commit_hty = TapSighashType::Default;
+		let commit_template = Transaction {
+			version: Version::ONE,
+			lock_time: LockTime::ZERO,
+			input: commit_input,
+			output: commit_output.clone(),
+		};
+		// Identifies the commit being mined, whatever its sequence.
+		let template = commit_template.txid().to_string();
+		let ranges = match self.checkpoint_of(wallet)?.filter(|c| c.template == template) {
+			Some(c) => {
+				tracing::info!(
+					"resuming the commit search of {} with {} sequences left",
+					MiningCheckpoint::path_of(wallet),
+					c.ranges.iter().map(|r| r.len() as u64).sum::<u64>()
+				);
+
+				c.ranges
+			},
+			None => Self::sequence_ranges(concurrency),
+		};
+		// The sequence each worker is at.
+		let progress = Arc::new(ranges.iter().map(|r| AtomicU32::new(r.start)).collect::<Vec<_>>());
+		let checkpointer = self.checkpoint_interval.map(|i| {
+			let mut checkpoint = MiningCheckpoint {
+				ticker: self.ticker.clone(),
+				op_type: op_type.clone(),
+				bitworkc: bitworkc.clone(),
+				payload: array_bytes::bytes2hex("", &payload),
+				satsbyte,
+				template: template.clone(),
+				ranges: Vec::new(),
+			};
+			let ranges = ranges.clone();
+			let progress = progress.clone();
+			let wallet = wallet.clone();
+
+			Periodic::start(i, move || {
+				checkpoint.ranges = ranges
+					.iter()
+					.zip(progress.iter())
+					.map(|(r, p)| p.load(Ordering::Relaxed)..r.end)
+					.filter(|r| !r.is_empty())
+					.collect();
+
+				if let Err(e) = checkpoint.save(&wallet) {
+					tracing::warn!("failed to save the mining checkpoint: {e:#}");
+				}
+			})
+		});
 		let mut ts = <Vec<JoinHandle<Result<()>>>>::new();
+		// Raised once a solution is found, or to give up the search.
 		let solution_found = Arc::new(AtomicBool::new(false));
 		let maybe_commit_tx = Arc::new(Mutex::new(None));
+		let hash_rate = HashRate::start("commit", ranges.len() as _, self.hash_rate_interval);
 
-		Self::sequence_ranges().into_iter().enumerate().for_each(|(i, r)| {
+		ranges.into_iter().enumerate().for_each(|(i, r)| {
 			tracing::info!("spawning commit worker thread {i} for sequence range {r:?}");
 
 			let secp = secp.clone();
 			let bitworkc = bitworkc.clone();
 			let funding_kp = wallet.funding.pair.tap_tweak(&secp, None).to_inner();
 			let funding_xpk = wallet.funding.x_only_public_key;
-			let input = commit_input.clone();
-			let output = commit_output.clone();
+			let commit_template = commit_template.clone();
 			let prevouts = commit_prevouts.clone();
 			let solution_found = solution_found.clone();
 			let maybe_tx = maybe_commit_tx.clone();
+			let hashes = hash_rate.counter();
+			let progress = progress.clone();
+			let priority = self.priority.clone();
 
 			ts.push(thread::spawn(move || {
+				priority.apply(i);
+
 				for s in r {
 					if solution_found.load(Ordering::Relaxed) {
 						return Ok(());
 					}
 
-					let mut psbt = Psbt::from_unsigned_tx(Transaction {
-						version: Version::ONE,
-						lock_time: LockTime::ZERO,
-						input: {
-							let mut i = input.clone();
-
-							i[0].sequence = Sequence(s);
-
-							i
-						},
-						output: output.clone(),
-					})?;
-					let tap_key_sig = {
-						let h = SighashCache::new(&psbt.unsigned_tx)
-							.taproot_key_spend_signature_hash(
-								0,
-								&Prevouts::All(&prevouts),
-								commit_hty,
-							)?;
-						let m = Message::from_digest(h.to_byte_array());
-
-						Signature { sig: secp.sign_schnorr(&m, &funding_kp), hash_ty: commit_hty }
-					};
-
-					psbt.inputs[0] = Input {
-						witness_utxo: Some(prevouts[0].clone()),
-						final_script_witness: {
-							let mut w = Witness::new();
-
-							w.push(tap_key_sig.to_vec());
-
-							Some(w)
-						},
-						tap_key_sig: Some(tap_key_sig),
-						tap_internal_key: Some(funding_xpk),
-						..Default::default()
-					};
-
-					tracing::trace!("{psbt:#?}");
+					progress[i].store(s, Ordering::Relaxed);
 
-					let tx = psbt.extract_tx_unchecked_fee_rate();
+					let tx = Self::commit_tx_of(
+						&secp,
+						&funding_kp,
+						funding_xpk,
+						&commit_template,
+						&prevouts,
+						s,
+					)?;
 					let txid = tx.txid();
 
-					if txid.to_string().trim_start_matches("0x").starts_with(&bitworkc) {
+					hashes.fetch_add(1, Ordering::Relaxed);
+
+					if util::matches_bitwork(&txid, &bitworkc) {
 						tracing::info!("solution found for commit step");
 						tracing::info!("commit sequence {s}");
 						tracing::info!("commit txid {txid}");
@@ -233,12 +962,23 @@ impl Miner {
 		});
 
 		tracing::info!("\nStay calm and grab a drink! Commit workers have started mining...\n");
-		for t in ts {
-			t.join().unwrap()?;
+		self.mine_until_stopped("commit", solution_found, move || {
+			ts.into_iter().try_for_each(|t| t.join().unwrap())
+		})
+		.await?;
+
+		drop(checkpointer);
+		hash_rate.finish();
+
+		if self.checkpoint_interval.is_some() {
+			MiningCheckpoint::clear(wallet)?;
 		}
 
-		// TODO: If no solution found.
-		let commit_tx = maybe_commit_tx.lock().unwrap().take().unwrap();
+		let commit_tx = maybe_commit_tx.lock().unwrap().take().ok_or_else(|| {
+			anyhow::anyhow!("no commit sequence satisfies the bitworkc {bitworkc}")
+		})?;
+
+		Self::ensure_bitwork(&commit_tx, &bitworkc)?;
 
 		let file_path = format!("{}.csv", wallet.funding.address);
 		let mut file = OpenOptions::new().write(true).append(true).create(true).open(&file_path)?;
@@ -249,40 +989,78 @@ impl Miner {
 		}
 
 		let commit_txid = commit_tx.txid();
+
+		tracing::info!("commit stage done, {commit_txid} satisfies the bitworkc {bitworkc}");
+
+		// Without a bitworkr the reveal is ready right away. A persisted state needs it before the
+		// commit goes out, otherwise the bitworkr is mined while the commit confirms.
+		let reveal_upfront =
+			bitworkr.is_none() || self.persist_state || self.commit_confirmations == 0;
+		// A job mining the reveal, for a thread of its own, given up once `stop` is raised.
+		let reveal_of = |stop| {
+			let (secp, funding_kp) = (secp.clone(), wallet.funding.pair);
+			let (reveal_script, reveal_spend_info) =
+				(reveal_script.clone(), reveal_spend_info.clone());
+			let (commit_output, additional_outputs) =
+				(commit_output.clone(), additional_outputs.clone());
+			let (bitworkr, hash_rate_interval) = (bitworkr.clone(), self.hash_rate_interval);
+			let priority = self.priority.clone();
+
+			move || {
+				Self::reveal_tx_of(
+					secp,
+					funding_kp,
+					reveal_script,
+					reveal_spend_info,
+					commit_output,
+					commit_txid,
+					additional_outputs,
+					bitworkr,
+					concurrency,
+					hash_rate_interval,
+					priority,
+					stop,
+				)
+			}
+		};
+		let reveal_tx = if reveal_upfront {
+			let stop = Arc::new(AtomicBool::new(false));
+
+			Some(self.mine_until_stopped("reveal", stop.clone(), reveal_of(stop)).await?)
+		} else {
+			None
+		};
+
+		// Mining takes a while, another user of the wallet might have spent the funding meanwhile.
+		self.api
+			.verify_utxo_unspent(
+				wallet.funding.address.to_string(),
+				&commit_tx.input[0].previous_output,
+			)
+			.await?;
+
+		// Build the reveal upfront, a crash in between then leaves everything to resume.
+		if let Some(reveal_tx) = reveal_tx.as_ref().filter(|_| self.persist_state) {
+			MintState {
+				ticker: self.ticker.clone(),
+				commit_txid: commit_txid.to_string(),
+				commit_tx: encode::serialize_hex(&commit_tx),
+				reveal_tx: encode::serialize_hex(reveal_tx),
+			}
+			.save(wallet)?;
+		}
+
+		// Make sure the reveal goes to the same backend as the commit.
+		let sticky = self.api.with_sticky_uri();
 		// tracing::info!("commit txid {}", commit_txid);
 		tracing::info!("Broadcasting commit tx...");
 		let raw_tx = encode::serialize_hex(&commit_tx);
 		tracing::info!("raw tx: {}", &raw_tx);
 
-		let mut attempts = 0;
-		while attempts < Self::MAX_BROADCAST_NUM {
-			if let Err(_) = self.api.broadcast(raw_tx.clone()).await {
-				tracing::info!(
-					"Network error, will retry to broadcast commit transaction in {} seconds...",
-					Self::BROADCAST_SLEEP_SECONDS
-				);
-				sleep(Duration::from_secs(15));
-				attempts += 1;
-				continue;
-			}
-			break;
-		}
-
+		let sent = self.broadcast_retrying(&sticky, "commit", &raw_tx, &commit_prevouts).await;
 		let commit_tx_time = Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string();
 
-		if attempts < Self::MAX_BROADCAST_NUM {
-			tracing::info!("Successfully sent commit tx {commit_txid}");
-
-			writeln!(
-				file,
-				"commitTx,{},{},{},{},{}",
-				"success",
-				commit_tx_time,
-				satsbyte,
-				commit_tx.txid(),
-				raw_tx
-			)?;
-		} else {
+		if let Err(e) = sent {
 			tracing::info!("❌ Failed to send commit tx {commit_txid}");
 
 			writeln!(
@@ -295,52 +1073,1018 @@ impl Miner {
 				raw_tx
 			)?;
 
-			return Ok(());
+			// Only a rejection means nothing was committed, after a timeout the commit might be
+			// in the mempool and the state is needed to resume it.
+			if self.persist_state && electrumx::is_rejected(&e) {
+				MintState::clear(wallet)?;
+			}
+
+			return Err(e);
 		}
 
+		tracing::info!("Successfully sent commit tx {commit_txid}");
+
+		writeln!(
+			file,
+			"commitTx,{},{},{},{},{}",
+			"success",
+			commit_tx_time,
+			satsbyte,
+			commit_tx.txid(),
+			raw_tx
+		)?;
+
 		tracing::info!("\nCommit workers have completed their tasks for the commit transaction.\n");
 
-		let commit_txid = commit_tx.txid();
-		let commit_txid_ = self
-			.api
-			.wait_until_utxo(
-				Address::from_script(&reveal_spk, self.network)?.to_string(),
-				fees.reveal_and_outputs,
-			)
-			.await?
-			.txid;
+		let commit_txid_ =
+			sticky.wait_until_utxo(commit_address.to_string(), fees.reveal_and_outputs).await?.txid;
 
 		assert_eq!(commit_txid, commit_txid_.parse()?);
 
-		// TODO: Move common code to a single function.
-		let reveal_hty = TapSighashType::SinglePlusAnyoneCanPay;
-		let reveal_lh = reveal_script.tapscript_leaf_hash();
-		let reveal_tx = if let Some(bitworkr) = bitworkr {
-			// exists bitworkr
-			tracing::info!("\nStarting reveal stage mining now...\n");
-			tracing::info!("Concurrency set to: {concurrency}");
-			let psbt = Psbt::from_unsigned_tx(Transaction {
-				version: Version::ONE,
-				lock_time: LockTime::ZERO,
-				input: vec![TxIn {
-					previous_output: OutPoint::new(commit_txid, 0),
-					sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-					..Default::default()
-				}],
-				output: additional_outputs,
-			})?;
-			let mut ts = <Vec<JoinHandle<Result<()>>>>::new();
-			let solution_found = Arc::new(AtomicBool::new(false));
-			let must_tx = Arc::new(Mutex::new(None));
+		let reveal_tx = match reveal_tx {
+			Some(tx) => {
+				if self.commit_confirmations > 0 {
+					self.api
+						.wait_for_confirmations(commit_txid.to_string(), self.commit_confirmations)
+						.await?;
+				}
+
+				tx
+			},
+			None => {
+				tracing::info!(
+					"mining the bitworkr while commit {commit_txid} waits for {} confirmations",
+					self.commit_confirmations
+				);
+
+				// The commit is out, there is no giving up anymore, unless the wait fails.
+				let stop = Arc::new(AtomicBool::new(false));
+				let (tx, commit) = tokio::try_join!(
+					async { tokio::task::spawn_blocking(reveal_of(stop.clone())).await? },
+					async {
+						self.api
+							.wait_for_confirmations(
+								commit_txid.to_string(),
+								self.commit_confirmations,
+							)
+							.await
+							// Otherwise the workers keep mining a reveal which is never sent.
+							.inspect_err(|_| stop.store(true, Ordering::Relaxed))
+					},
+				)?;
+
+				// The bitworkr may take longer, the commit has to still be in the block whose
+				// confirmations were counted.
+				if let Some(b) = &commit.blockhash {
+					match self.api.ensure_confirmed_in(commit_txid.to_string(), b).await {
+						Err(e)
+							if matches!(
+								e.downcast_ref::<ElectrumXError>(),
+								Some(ElectrumXError::ReorgDetected { .. })
+							) =>
+						{
+							tracing::warn!("{e}, waiting for the confirmations again");
+
+							self.api
+								.wait_for_confirmations(
+									commit_txid.to_string(),
+									self.commit_confirmations,
+								)
+								.await?;
+						},
+						r => r?,
+					}
+				}
+
+				tx
+			},
+		};
+		let reveal_txid = reveal_tx.txid();
+
+		if let Some(bitworkr) = &bitworkr {
+			tracing::info!("reveal stage done, {reveal_txid} satisfies the bitworkr {bitworkr}");
+		}
+
+		if self.verify_commit {
+			self.api
+				.verify_commit(commit_address.to_string(), &reveal_tx.input[0].previous_output)
+				.await?;
+		}
+
+		tracing::info!("Broadcasting reveal tx...");
+		let raw_tx = encode::serialize_hex(&reveal_tx);
+		tracing::info!("raw tx: {}", &raw_tx);
+		let sent =
+			self.broadcast_retrying(&sticky, "reveal", &raw_tx, &commit_tx.output[..1]).await;
+		let reveal_tx_time = Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+
+		if sent.is_ok() {
+			tracing::info!("✅ Successfully sent reveal tx {reveal_txid}");
+
+			// A file mint creates a new atomical, on the first output of its reveal.
+			if op_type == "nft" {
+				match self.api.atomical_id_of(&reveal_txid, 0).await {
+					Ok(id) => tracing::info!("minted atomical {id}"),
+					Err(e) => tracing::warn!(
+						"minted atomical {}, not indexed yet: {e}",
+						util::atomical_id_of(&reveal_txid, 0)
+					),
+				}
+			}
+
+			tracing::info!("✨Congratulations! Mission completed.✨");
+
+			if self.persist_state {
+				MintState::clear(wallet)?;
+			}
+
+			writeln!(
+				file,
+				"revealTx,{},{},{},{},{}",
+				"success",
+				reveal_tx_time,
+				satsbyte,
+				reveal_tx.txid(),
+				raw_tx
+			)?;
+		} else {
+			tracing::info!("❌ Failed to send reveal tx {reveal_txid}");
+
+			writeln!(
+				file,
+				"revealTx,{},{},{},{},{}",
+				"fail",
+				reveal_tx_time,
+				satsbyte,
+				reveal_tx.txid(),
+				raw_tx
+			)?;
+		}
+
+		// A persisted state is kept, to resume the reveal later.
+		sent
+	}
+
+	// The output paying the reveal, and the refund of the funding UTXO if anything is left.
+	fn commit_output_of(d: &Data, reveal_spk: ScriptBuf, funding_spk: ScriptBuf) -> Vec<TxOut> {
+		let Data { satsbyte, fees, funding_utxo, .. } = d;
+		let spend =
+			TxOut { value: Amount::from_sat(fees.reveal_and_outputs), script_pubkey: reveal_spk };
+		let r = funding_utxo.value.saturating_sub(fees.reveal_and_outputs).saturating_sub(
+			fees.commit + (Self::OUTPUT_BYTES_BASE * *satsbyte as f64).floor() as u64,
+		);
+
+		if r > 0 {
+			vec![spend, TxOut { value: Amount::from_sat(r), script_pubkey: funding_spk }]
+		} else {
+			vec![spend]
+		}
+	}
+
+	/// The commit of a mint of the ticker with `wallet`, as an unsigned PSBT for an external
+	/// wallet to sign.
+	///
+	/// The input sequence is mined meanwhile, so the commit satisfies the bitworkc.
+	async fn export_psbt(&self, wallet: &Wallet) -> Result<Psbt> {
+		let d = self.prepare_data(wallet, None).await?;
+		let commit_address = util::commit_address(
+			&wallet.funding.x_only_public_key,
+			&d.op_type,
+			&d.payload,
+			self.network,
+		)?;
+		let funding_spk = wallet.funding.address.script_pubkey();
+
+		util::build_unsigned_psbt(
+			OutPoint::new(d.funding_utxo.txid.parse()?, d.funding_utxo.vout),
+			TxOut {
+				value: Amount::from_sat(d.funding_utxo.value),
+				script_pubkey: funding_spk.clone(),
+			},
+			wallet.funding.x_only_public_key,
+			Self::commit_output_of(&d, commit_address.script_pubkey(), funding_spk),
+			Some(&d.bitworkc),
+		)
+	}
+
+	// Broadcast `raw_tx`, retrying only on the transport errors. Refusals of the backend, or of
+	// the client such as the spend cap, won't go away and are returned right away.
+	async fn broadcast_retrying<A>(
+		&self,
+		api: &A,
+		kind: &str,
+		raw_tx: &str,
+		prevouts: &[TxOut],
+	) -> Result<()>
+	where
+		A: Api,
+	{
+		let mut attempts = 1;
+
+		loop {
+			match api.broadcast_spending(raw_tx, prevouts).await {
+				Ok(_) => return Ok(()),
+				// An earlier attempt made it after all.
+				Err(e) if attempts > 1 && electrumx::is_already_known(&e) => return Ok(()),
+				Err(e) if electrumx::is_transient(&e) && attempts < Self::MAX_BROADCAST_NUM => {
+					tracing::info!(
+						"Network error, will retry to broadcast {kind} transaction in {} seconds: \
+						{e:#}",
+						Self::BROADCAST_SLEEP_SECONDS
+					);
+
+					api.clock()
+						.sleep(Duration::from_secs(Self::BROADCAST_SLEEP_SECONDS as _))
+						.await;
+
+					attempts += 1;
+				},
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	// Finish a mint which was interrupted after its state was saved.
+	async fn resume(&self, wallet: &Wallet, state: MintState) -> Result<()> {
+		tracing::info!(
+			"resuming the pending {} mint of commit {}",
+			state.ticker,
+			state.commit_txid
+		);
+
+		// Either of them might have made it already, or only to some of the base URIs before the
+		// crash, so they are pushed to all of them.
+		for tx in [&state.commit_tx, &state.reveal_tx] {
+			// Sent, or already known, by one of them is enough.
+			let sent = self.api.try_broadcast_all(tx).await.and_then(|rs| {
+				let mut last_error = None;
+
+				for (_, r) in rs {
+					match r {
+						Ok(_) => return Ok(()),
+						Err(e) => last_error = Some(e),
+					}
+				}
+
+				Err(last_error.unwrap_or_else(|| anyhow::anyhow!("no base URI to broadcast to")))
+			});
+
+			if let Err(e) = sent {
+				return Err(e.context(format!(
+					"failed to resume the pending mint, its state is kept in {}",
+					MintState::path_of(wallet)
+				)));
+			}
+
+			if tx == &state.commit_tx && self.commit_confirmations > 0 {
+				self.api
+					.wait_for_confirmations(
+						state.commit_txid.to_string(),
+						self.commit_confirmations,
+					)
+					.await?;
+			}
+		}
+
+		MintState::clear(wallet)?;
+
+		tracing::info!("✅ Resumed the pending mint of commit {}", state.commit_txid);
+
+		Ok(())
+	}
+
+	async fn prepare_data(&self, wallet: &Wallet, funding: Option<Utxo>) -> Result<Data> {
+		let (ft, satsbyte, additional_outputs, payload) = self.dmt_of(wallet).await?;
+
+		self.data_of(
+			wallet,
+			satsbyte,
+			"dmt",
+			&payload,
+			ft.mint_bitworkc,
+			ft.mint_bitworkr,
+			additional_outputs,
+			funding,
+		)
+		.await
+	}
+
+	/// Walk through a mint of the ticker with `wallet`, without mining or broadcasting anything.
+	async fn simulate_mint(&self, wallet: &Wallet) -> Result<MintSimulation> {
+		let (ft, satsbyte, additional_outputs, payload) = self.dmt_of(wallet).await?;
+		let (_, reveal_script, _) = self.reveal_of(wallet, "dmt", &payload, &additional_outputs)?;
+		let fees = Self::fees_of(
+			satsbyte,
+			reveal_script.len(),
+			&additional_outputs,
+			ft.mint_bitworkr.is_some(),
+		);
+		let required = fees.commit_and_reveal_and_outputs;
+		let funding_address = wallet.funding.address.to_string();
+		let utxos = self
+			.api
+			.get_unspent_address(&funding_address)
+			.await?
+			.into_iter()
+			.filter(|u| u.atomicals.is_empty())
+			.collect::<Vec<_>>();
+
+		Ok(MintSimulation {
+			ticker: ft.ticker,
+			ticker_id: ft.atomical_id,
+			mint_amount: ft.mint_amount,
+			bitworkc: ft.mint_bitworkc,
+			bitworkr: ft.mint_bitworkr,
+			satsbyte,
+			commit_fee: fees.commit,
+			reveal_fee: fees.reveal_and_outputs - ft.mint_amount,
+			required,
+			balance: utxos.iter().map(|u| u.value).sum(),
+			funding_utxo: utxos.into_iter().find(|u| u.value >= required),
+			funding_address,
+			stash_address: wallet.stash.address.to_string(),
+		})
+	}
+
+	// The ticker to mint after checking it's still mintable, with the payload of a mint.
+	async fn dmt_of(&self, wallet: &Wallet) -> Result<(Ft, u64, Vec<TxOut>, Vec<u8>)> {
+		let id = match self.api.get_by_ticker(&self.ticker).await {
+			// A lagging index might not know about a ticker deployed lately, look again once it
+			// caught up.
+			Err(e)
+				if matches!(
+					e.downcast_ref::<ElectrumXError>(),
+					Some(ElectrumXError::NotFound(_))
+				) =>
+			{
+				self.api.wait_for_index(electrumx::MAX_INDEX_LAG).await?;
+				self.api.get_by_ticker(&self.ticker).await?
+			},
+			r => r?,
+		}
+		.atomical_id;
+		tracing::info!("ticker {ticker}, id {id}", ticker = self.ticker, id = id);
+		let response = self.api.wait_until_atomical_indexed(&id, Self::INDEX_TIMEOUT).await?;
+		let global = response.global.unwrap();
+		let ft = response.result;
+
+		if ft.ticker != self.ticker {
+			Err(anyhow::anyhow!("ticker mismatch"))?;
+		}
+		if ft.subtype != "decentralized" {
+			Err(anyhow::anyhow!("not decentralized"))?;
+		}
+		if ft.mint_height > global.height + 1 {
+			Err(anyhow::anyhow!("mint height mismatch"))?;
+		}
+		if ft.mint_amount == 0 || ft.mint_amount >= 100_000_000 {
+			Err(anyhow::anyhow!("mint amount mismatch"))?;
+		}
+		if ft.dft_info.mint_count >= ft.max_mints {
+			Err(anyhow::anyhow!("max mints reached"))?;
+		}
+
+		let satsbyte = self.satsbyte().await?;
+		let additional_outputs = vec![TxOut {
+			value: Amount::from_sat(ft.mint_amount),
+			script_pubkey: wallet.stash.address.script_pubkey(),
+		}];
+		let payload = PayloadWrapper {
+			args: {
+				let (time, nonce) = util::time_nonce();
+				tracing::info!("payload time: {time}, payload nonce: {nonce}");
+
+				Payload {
+					bitworkc: ft.mint_bitworkc.clone(),
+					mint_ticker: ft.ticker.clone(),
+					nonce,
+					time,
+				}
+			},
+		};
+
+		Ok((ft, satsbyte, additional_outputs, util::cbor(&payload)?))
+	}
+
+	async fn prepare_file_data(
+		&self,
+		wallet: &Wallet,
+		file: &FilePayload,
+		bitworkc: Option<String>,
+	) -> Result<Data> {
+		let satsbyte = self.satsbyte().await?;
+		let additional_outputs = vec![TxOut {
+			value: Amount::from_sat(Self::NFT_OUTPUT_VALUE),
+			script_pubkey: wallet.stash.address.script_pubkey(),
+		}];
+		let (time, nonce) = util::time_nonce();
+
+		tracing::info!(
+			"minting {} of {} bytes as {}",
+			file.name,
+			file.data.len(),
+			file.content_type
+		);
+
+		let payload = util::cbor(&NftPayload {
+			args: NftArgs { bitworkc: bitworkc.clone(), nonce, time },
+			file,
+		})?;
+
+		self.data_of(
+			wallet,
+			satsbyte,
+			"nft",
+			&payload,
+			bitworkc.unwrap_or_default(),
+			None,
+			additional_outputs,
+			None,
+		)
+		.await
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	async fn data_of(
+		&self,
+		wallet: &Wallet,
+		satsbyte: u64,
+		op_type: &str,
+		payload: &[u8],
+		bitworkc: String,
+		bitworkr: Option<String>,
+		additional_outputs: Vec<TxOut>,
+		funding: Option<Utxo>,
+	) -> Result<Data> {
+		// The progress of a checkpoint only holds for the same payload at the same fee rate.
+		let checkpoint = self
+			.checkpoint_of(wallet)?
+			.filter(|c| c.ticker == self.ticker && c.op_type == op_type && c.bitworkc == bitworkc);
+		let (satsbyte, payload) = match &checkpoint {
+			Some(c) => {
+				tracing::info!(
+					"resuming the payload and the fee rate of {}",
+					MiningCheckpoint::path_of(wallet)
+				);
+
+				(c.satsbyte, c.payload()?)
+			},
+			None => (satsbyte, payload.to_vec()),
+		};
+		let (secp, reveal_script, reveal_spend_info) =
+			self.reveal_of(wallet, op_type, &payload, &additional_outputs)?;
+		let perform_bitworkr = bitworkr.is_some();
+		let reveal_script_len = reveal_script.as_bytes().len();
+		let outputs = &additional_outputs;
+		let fees = Self::fees_of(satsbyte, reveal_script_len, outputs, perform_bitworkr);
+		let (funding_utxo, (satsbyte, fees)) = match funding {
+			// Drawn beforehand, e.g. from a funding pool.
+			Some(u) if u.value >= fees.commit_and_reveal_and_outputs => (u, (satsbyte, fees)),
+			_ if self.reprice_funding && checkpoint.is_none() => {
+				// Fees may rise while waiting, the funding has to cover them at the latest rate.
+				self.api
+					.wait_until_funding_repriced(
+						wallet.funding.address.to_string(),
+						|| async move {
+							let satsbyte = self.satsbyte().await?;
+							let fees = Self::fees_of(
+								satsbyte,
+								reveal_script_len,
+								outputs,
+								perform_bitworkr,
+							);
+
+							Ok((fees.commit_and_reveal_and_outputs, (satsbyte, fees)))
+						},
+					)
+					.await?
+			},
+			_ => {
+				let funding_utxo = self
+					.api
+					.wait_until_funding(
+						wallet.funding.address.to_string(),
+						fees.commit_and_reveal_and_outputs,
+					)
+					.await?;
+
+				(funding_utxo, (satsbyte, fees))
+			},
+		};
+
+		Ok(Data {
+			secp,
+			satsbyte,
+			op_type: op_type.into(),
+			payload,
+			bitworkc,
+			bitworkr,
+			additional_outputs,
+			reveal_script,
+			reveal_spend_info,
+			fees,
+			funding_utxo,
+		})
+	}
+
+	// Run the mining `job` on a thread of its own, raising the `stop` of its workers once the
+	// stop condition holds.
+	async fn mine_until_stopped<F, T>(
+		&self,
+		stage: &str,
+		stop: Arc<AtomicBool>,
+		job: F,
+	) -> Result<T>
+	where
+		F: 'static + Send + FnOnce() -> Result<T>,
+		T: 'static + Send,
+	{
+		let started = Instant::now();
+		let mut mints_checked = started;
+		let mut job = tokio::task::spawn_blocking(job);
+
+		loop {
+			let reason = tokio::select! {
+				r = &mut job => return r?,
+				_ = tokio::time::sleep(StopCondition::POLL_INTERVAL) =>
+					self.stop_reason(started, &mut mints_checked).await,
+			};
+
+			if let Some(reason) = reason {
+				stop.store(true, Ordering::Relaxed);
+				// Whatever the workers found by now is dropped.
+				let _ = job.await;
+
+				return Err(ElectrumXError::MiningAborted { stage: stage.into(), reason }.into());
+			}
+		}
+	}
+
+	// Why a search started at `started` should be given up, `None` to go on.
+	async fn stop_reason(&self, started: Instant, mints_checked: &mut Instant) -> Option<String> {
+		let s = &self.stop;
+
+		if s.cancel.load(Ordering::Relaxed) {
+			return Some("cancelled".into());
+		}
+		if let Some(m) = s.max_duration.filter(|&m| started.elapsed() >= m) {
+			return Some(format!("still searching after {m:?}"));
+		}
+		if s.mint_check_interval.is_some_and(|i| mints_checked.elapsed() >= i) {
+			*mints_checked = Instant::now();
+
+			match self.api.remaining_mints(&self.ticker).await {
+				Ok(0) => return Some(format!("no mints left for {}", self.ticker)),
+				Ok(_) => (),
+				Err(e) =>
+					tracing::warn!("failed to check the mints left for {}: {e:#}", self.ticker),
+			}
+		}
+
+		None
+	}
+
+	// The saved commit search of `wallet`, if checkpointing.
+	fn checkpoint_of(&self, wallet: &Wallet) -> Result<Option<MiningCheckpoint>> {
+		if self.checkpoint_interval.is_none() {
+			return Ok(None);
+		}
+
+		MiningCheckpoint::load(wallet)
+	}
+
+	fn reveal_of(
+		&self,
+		wallet: &Wallet,
+		op_type: &str,
+		payload: &[u8],
+		additional_outputs: &[TxOut],
+	) -> Result<(Secp256k1<All>, ScriptBuf, TaprootSpendInfo)> {
+		let secp = Secp256k1::new();
+		let reveal_script =
+			util::build_reval_script(&wallet.funding.x_only_public_key, op_type, payload);
+		let reveal_spend_info =
+			util::reveal_spend_info(&secp, &wallet.funding.x_only_public_key, &reveal_script)?;
+
+		// Refuse before committing any funds, a reveal which can't be broadcast would strand them.
+		let size = Self::reveal_hex_len_of(reveal_script.len(), additional_outputs);
+		let limit = self.api.max_broadcast_size();
+
+		if size > limit {
+			Err(ElectrumXError::TransactionTooLarge { size, limit })?;
+		}
+
+		Ok((secp, reveal_script, reveal_spend_info))
+	}
+
+	/// Fan the clean funding UTXOs out into `count` outputs of `value` satoshis each, paid back to
+	/// the funding address.
+	///
+	/// Spends the UTXO with the funding confirmations of the client which covers the outputs and
+	/// the fee by the least, or else the largest ones, as many as it takes.
+	///
+	/// Returns the new UTXOs once the split transaction has the funding confirmations of the
+	/// client, right away if none are required.
+	async fn split_funding(&self, wallet: &Wallet, count: usize, value: u64) -> Result<Vec<Utxo>> {
+		if count == 0 {
+			Err(anyhow::anyhow!("nothing to split the funding UTXOs into"))?;
+		}
+
+		let secp = Secp256k1::new();
+		let fee_rate = FeeRate::from_sat_per_vb(self.satsbyte().await? as f64);
+		let funding_spk = wallet.funding.address.script_pubkey();
+		// The outputs and the change, whose value is set once the fee is known.
+		let mut output = (0..=count)
+			.map(|_| TxOut { value: Amount::from_sat(value), script_pubkey: funding_spk.clone() })
+			.collect::<Vec<_>>();
+		let fee_of = |inputs| {
+			util::estimate_vsize_and_fee(
+				&vec![InputKind::TaprootKeySpend; inputs],
+				&output,
+				fee_rate,
+			)
+			.1
+		};
+		let utxos = self
+			.api
+			.select_unspent_address(
+				wallet.funding.address.to_string(),
+				value * count as u64,
+				|us: &[Utxo], satoshis| {
+					// The single UTXO leaving the least change, if any covers it all.
+					if let Some(u) = UtxoSelection::ClosestMatch
+						.select(us, satoshis + fee_of(1))
+						.filter(|s| s.len() == 1)
+					{
+						return Some(u);
+					}
+
+					// Otherwise each input adds to the fee, keep taking them until they cover it
+					// as well.
+					let mut sum = 0;
+					let mut selected = Vec::new();
+
+					for u in UtxoSelection::LargestFirst.sort(us, satoshis) {
+						if sum >= satoshis + fee_of(selected.len()) {
+							break;
+						}
+
+						sum += u.value;
+
+						selected.push(u);
+					}
+
+					(sum >= satoshis + fee_of(selected.len())).then_some(selected)
+				},
+			)
+			.await?;
+		let total = utxos.iter().map(|u| u.value).sum::<u64>();
+		let change = total - value * count as u64 - fee_of(utxos.len());
+
+		// The nodes refuse dust, so it goes to the fee instead.
+		match util::change_of(change) {
+			Some(c) => output[count].value = Amount::from_sat(c),
+			None => {
+				output.pop();
+			},
+		}
+
+		let prevouts = utxos
+			.iter()
+			.map(|u| TxOut { value: Amount::from_sat(u.value), script_pubkey: funding_spk.clone() })
+			.collect::<Vec<_>>();
+
+		let tx = Self::sign_funding_inputs(
+			&secp,
+			wallet,
+			Transaction {
+				version: Version::ONE,
+				lock_time: LockTime::ZERO,
+				input: utxos
+					.iter()
+					.map(|u| {
+						Ok(TxIn {
+							previous_output: OutPoint::new(u.txid.parse()?, u.vout),
+							sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+							..Default::default()
+						})
+					})
+					.collect::<Result<_>>()?,
+				output,
+			},
+			&prevouts,
+		)?;
+		let txid = tx.txid().to_string();
+
+		tracing::info!("broadcasting split tx {txid} with {count} outputs of {value} sats");
+
+		self.api.broadcast_spending(encode::serialize_hex(&tx), &prevouts).await?;
+
+		let m = FundingMatcher::new(vec![value; count])
+			.of_tx(&txid)
+			.wait(&*self.api, &wallet.funding.address.to_string())
+			.await?;
+
+		tracing::info!(
+			"split tx {txid} is in, {} sats of change left",
+			m.leftover.iter().map(|u| u.value).sum::<u64>()
+		);
+
+		Ok(m.matched.into_iter().flatten().collect())
+	}
+
+	/// Sweep the small clean UTXOs of the funding address into a single one, if there are more
+	/// than the configured threshold of them.
+	async fn consolidate(&self, wallet: &Wallet) -> Result<()> {
+		let Some(c) = &self.consolidation else {
+			return Ok(());
+		};
+		let utxos = self
+			.api
+			.get_unspent_address(wallet.funding.address.to_string())
+			.await?
+			.into_iter()
+			.filter(|u| u.atomicals.is_empty() && u.value < Consolidation::MAX_VALUE)
+			.collect::<Vec<_>>();
+
+		if utxos.len() <= c.threshold {
+			return Ok(());
+		}
+
+		let funding_spk = wallet.funding.address.script_pubkey();
+		let total = utxos.iter().map(|u| u.value).sum::<u64>();
+		let (_, fee) = util::estimate_vsize_and_fee(
+			&vec![InputKind::TaprootKeySpend; utxos.len()],
+			&[TxOut { value: Amount::from_sat(total), script_pubkey: funding_spk.clone() }],
+			self.relayable(c.fee_rate).await?,
+		);
+
+		if total <= fee {
+			tracing::info!("skip consolidation, {total} sats can't cover the {fee} sats fee");
+
+			return Ok(());
+		}
+
+		let secp = Secp256k1::new();
+		let prevouts = utxos
+			.iter()
+			.map(|u| TxOut { value: Amount::from_sat(u.value), script_pubkey: funding_spk.clone() })
+			.collect::<Vec<_>>();
+		let tx = Self::sign_funding_inputs(
+			&secp,
+			wallet,
+			Transaction {
+				version: Version::ONE,
+				lock_time: LockTime::ZERO,
+				input: utxos
+					.iter()
+					.map(|u| {
+						Ok(TxIn {
+							previous_output: OutPoint::new(u.txid.parse()?, u.vout),
+							sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+							..Default::default()
+						})
+					})
+					.collect::<Result<_>>()?,
+				output: vec![TxOut {
+					value: Amount::from_sat(total - fee),
+					script_pubkey: funding_spk.clone(),
+				}],
+			},
+			&prevouts,
+		)?;
+
+		tracing::info!(
+			"consolidating {} UTXOs of {} into tx {}",
+			utxos.len(),
+			wallet.funding.address,
+			tx.txid()
+		);
+
+		self.api.broadcast_spending(encode::serialize_hex(&tx), &prevouts).await?;
+
+		Ok(())
+	}
+
+	// Key path spend all the inputs, which must belong to the funding address.
+	fn sign_funding_inputs(
+		secp: &Secp256k1<All>,
+		wallet: &Wallet,
+		tx: Transaction,
+		prevouts: &[TxOut],
+	) -> Result<Transaction> {
+		let hty = TapSighashType::Default;
+		let kp = wallet.funding.pair.tap_tweak(secp, None).to_inner();
+		let mut psbt = Psbt::from_unsigned_tx(tx)?;
+
+		for (i, prevout) in prevouts.iter().enumerate() {
+			let tap_key_sig = {
+				let h = SighashCache::new(&psbt.unsigned_tx).taproot_key_spend_signature_hash(
+					i,
+					&Prevouts::All(prevouts),
+					hty,
+				)?;
+				let m = Message::from_digest(h.to_byte_array());
+
+				Signature { sig: secp.sign_schnorr(&m, &kp), hash_ty: hty }
+			};
+
+			psbt.inputs[i] = Input {
+				witness_utxo: Some(prevout.clone()),
+				final_script_witness: {
+					let mut w = Witness::new();
+
+					w.push(tap_key_sig.to_vec());
+
+					Some(w)
+				},
+				tap_key_sig: Some(tap_key_sig),
+				tap_internal_key: Some(wallet.funding.x_only_public_key),
+				..Default::default()
+			};
+		}
+
+		Ok(psbt.extract_tx_unchecked_fee_rate())
+	}
+
+	async fn satsbyte(&self) -> Result<u64> {
+		let fee_rate = FeeRate::from_sat_per_vb(if self.network == Network::Bitcoin {
+			(util::query_fee().await? + 5).min(self.max_fee) as f64
+		} else {
+			2.
+		});
+
+		Ok(self.relayable(fee_rate).await?.sat_per_vb_ceil())
+	}
+
+	// Anything below the relay fee gets rejected by the nodes.
+	async fn relayable(&self, fee_rate: FeeRate) -> Result<FeeRate> {
+		let floor = self.api.relay_fee().await?;
+
+		if fee_rate < floor {
+			tracing::warn!("fee rate {fee_rate} is below the relay fee, using {floor}");
+		}
+
+		Ok(fee_rate.max(floor))
+	}
+
+	// The length of the hex of a reveal spending `reveal_script_len` bytes of script, with an
+	// OP_RETURN of the bitworkr solution at most.
+	fn reveal_hex_len_of(reveal_script_len: usize, additional_outputs: &[TxOut]) -> usize {
+		// The signature, the script and the control block.
+		let witness = 1 + 1 + 65 + 5 + reveal_script_len + 1 + 33;
+
+		(util::estimate_size(&[InputKind::Witness(witness)], additional_outputs)
+			+ Self::OP_RETURN_BYTES as usize)
+			* 2
+	}
+
+	fn fees_of(
+		satsbyte: u64,
+		reveal_script_len: usize,
+		additional_outputs: &[TxOut],
+		perform_bitworkr: bool,
+	) -> Fees {
+		let satsbyte = satsbyte as f64;
+		let commit = {
+			(satsbyte * (Self::BASE_BYTES + Self::INPUT_BYTES_BASE + Self::OUTPUT_BYTES_BASE))
+				.ceil() as u64
+		};
+		let op_return_size_bytes = if perform_bitworkr { Self::OP_RETURN_BYTES } else { 0. };
+		let reveal = {
+			let compact_input_bytes = if reveal_script_len <= 252 {
+				1.
+			} else if reveal_script_len <= 0xFFFF {
+				3.
+			} else if reveal_script_len <= 0xFFFFFFFF {
+				5.
+			} else {
+				9.
+			};
+
+			(satsbyte
+				* (Self::BASE_BYTES
+						+ Self::REVEAL_INPUT_BYTES_BASE
+						+ (compact_input_bytes + reveal_script_len as f64) / 4.
+						// + utxos.len() as f64 * Self::INPUT_BYTES_BASE
+						+ op_return_size_bytes
+						+ additional_outputs.len() as f64 * Self::OUTPUT_BYTES_BASE))
+				.ceil() as u64
+		};
+		let outputs = additional_outputs.iter().map(|o| o.value.to_sat()).sum::<u64>();
+		let commit_and_reveal = commit + reveal;
+		let commit_and_reveal_and_outputs = commit_and_reveal + outputs;
+
+		// While satsbyte at `150`.
+		// Fees {
+		// 	commit: 16650,
+		// 	commit_and_reveal: 38700,
+		// 	commit_and_reveal_and_outputs: 58700,
+		// 	reveal: 22050,
+		// 	reveal_and_outputs: 42050,
+		// };
+		Fees {
+			commit,
+			// commit_and_reveal,
+			commit_and_reveal_and_outputs,
+			// reveal,
+			reveal_and_outputs: reveal + outputs,
+		}
+	}
+
+	// The commit of `template` at `sequence`, signed with the tweaked `funding_kp`.
+	fn commit_tx_of(
+		secp: &Secp256k1<All>,
+		funding_kp: &Keypair,
+		funding_xpk: XOnlyPublicKey,
+		template: &Transaction,
+		prevouts: &[TxOut],
+		sequence: u32,
+	) -> Result<Transaction> {
+		let commit_hty = TapSighashType::Default;
+		let mut psbt = Psbt::from_unsigned_tx({
+			let mut tx = template.clone();
+
+			tx.input[0].sequence = Sequence(sequence);
+
+			tx
+		})?;
+		let tap_key_sig = {
+			let h = SighashCache::new(&psbt.unsigned_tx).taproot_key_spend_signature_hash(
+				0,
+				&Prevouts::All(prevouts),
+				commit_hty,
+			)?;
+			let m = Message::from_digest(h.to_byte_array());
+
+			Signature { sig: secp.sign_schnorr(&m, funding_kp), hash_ty: commit_hty }
+		};
+
+		psbt.inputs[0] = Input {
+			witness_utxo: Some(prevouts[0].clone()),
+			final_script_witness: {
+				let mut w = Witness::new();
+
+				w.push(tap_key_sig.to_vec());
+
+				Some(w)
+			},
+			tap_key_sig: Some(tap_key_sig),
+			tap_internal_key: Some(funding_xpk),
+			..Default::default()
+		};
+
+		tracing::trace!("{psbt:#?}");
+
+		Ok(psbt.extract_tx_unchecked_fee_rate())
+	}
+
+	// The reveal spending the commit `commit_txid`, mining its nonce if there is a `bitworkr`.
+	#[allow(clippy::too_many_arguments)]
+	fn reveal_tx_of(
+		secp: Secp256k1<All>,
+		funding_kp: Keypair,
+		reveal_script: ScriptBuf,
+		reveal_spend_info: TaprootSpendInfo,
+		commit_output: Vec<TxOut>,
+		commit_txid: Txid,
+		additional_outputs: Vec<TxOut>,
+		bitworkr: Option<String>,
+		concurrency: u32,
+		hash_rate_interval: Option<Duration>,
+		priority: WorkerPriority,
+		stop: Arc<AtomicBool>,
+	) -> Result<Transaction> {
+		let seq_range_per_revealer: u32 = Self::SEQ_RANGE_BUCKET / concurrency;
+		let reveal_hty = TapSighashType::SinglePlusAnyoneCanPay;
+		let reveal_lh = reveal_script.tapscript_leaf_hash();
+		let reveal_tx = if let Some(bitworkr) = bitworkr {
+			// exists bitworkr
+			tracing::info!("\nStarting reveal stage mining now...\n");
+			tracing::info!("Concurrency set to: {concurrency}");
+			let psbt = Psbt::from_unsigned_tx(Transaction {
+				version: Version::ONE,
+				lock_time: LockTime::ZERO,
+				input: vec![TxIn {
+					previous_output: OutPoint::new(commit_txid, 0),
+					sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+					..Default::default()
+				}],
+				output: additional_outputs,
+			})?;
+			let mut ts = <Vec<JoinHandle<Result<()>>>>::new();
+			// Raised once a solution is found, or to give up the search.
+			let solution_found = stop;
+			let must_tx = Arc::new(Mutex::new(None));
 			let solution_time = Arc::new(Mutex::<u64>::new(0));
 			let solution_nonce = Arc::new(Mutex::<u32>::new(0));
+			let hash_rate = HashRate::start("reveal", concurrency, hash_rate_interval);
 
 			for i in 0..concurrency {
 				tracing::info!("spawning reveal worker thread {i} for bitworkr");
 
 				let secp = secp.clone();
 				let bitworkr = bitworkr.clone();
-				let funding_kp = wallet.funding.pair;
 				let reveal_script = reveal_script.clone();
 				let reveal_spend_info = reveal_spend_info.clone();
 				let commit_output = commit_output.clone();
@@ -349,8 +2093,12 @@ impl Miner {
 				let must_tx = must_tx.clone();
 				let solution_time = solution_time.clone();
 				let solution_nonce = solution_nonce.clone();
+				let hashes = hash_rate.counter();
+				let priority = priority.clone();
 
 				ts.push(thread::spawn(move || {
+					priority.apply(i as _);
+
 					let mut seq_start = i * seq_range_per_revealer;
 					let mut seq = seq_start;
 					let mut seq_end = seq_start + seq_range_per_revealer - 1;
@@ -371,6 +2119,8 @@ impl Miner {
 							} else {
 								// reveal worker thread stop mining w/o soluton found
 								tracing::info!("reveal worker thread {i} traversed its range w/o solution found.");
+
+								return Ok(());
 							}
 						}
 						if seq % 10000 == 0 {
@@ -441,7 +2191,9 @@ impl Miner {
 						let tx = psbt.extract_tx_unchecked_fee_rate();
 						let txid = tx.txid();
 
-						if txid.to_string().trim_start_matches("0x").starts_with(&bitworkr) {
+						hashes.fetch_add(1, Ordering::Relaxed);
+
+						if util::matches_bitwork(&txid, &bitworkr) {
 							tracing::info!("solution found for reveal step");
 							tracing::info!("reveal sequence {seq}");
 							tracing::info!("solution at time: {unixtime}, solution nonce: {seq}");
@@ -469,7 +2221,13 @@ impl Miner {
 				t.join().unwrap()?;
 			}
 
-			let tx = must_tx.lock().unwrap().take().unwrap();
+			hash_rate.finish();
+
+			let tx = must_tx.lock().unwrap().take().ok_or_else(|| {
+				anyhow::anyhow!("no reveal nonce satisfies the bitworkr {bitworkr}")
+			})?;
+
+			Self::ensure_bitwork(&tx, &bitworkr)?;
 
 			tx
 		} else {
@@ -491,222 +2249,54 @@ impl Miner {
 					reveal_lh,
 					reveal_hty,
 				)?;
-				let m = Message::from_digest(h.to_byte_array());
-
-				Signature { sig: secp.sign_schnorr(&m, &wallet.funding.pair), hash_ty: reveal_hty }
-			};
-
-			psbt.inputs[0] = Input {
-				// TODO: Check.
-				witness_utxo: Some(commit_output[0].clone()),
-				tap_internal_key: Some(reveal_spend_info.internal_key()),
-				tap_merkle_root: reveal_spend_info.merkle_root(),
-				final_script_witness: {
-					let mut w = Witness::new();
-
-					w.push(tap_key_sig.to_vec());
-					w.push(reveal_script.as_bytes());
-					w.push(
-						reveal_spend_info
-							.control_block(&(reveal_script, LeafVersion::TapScript))
-							.unwrap()
-							.serialize(),
-					);
-
-					Some(w)
-				},
-				..Default::default()
-			};
-
-			psbt.extract_tx_unchecked_fee_rate()
-		};
-
-		let reveal_txid = reveal_tx.txid();
-		tracing::info!("reveal txid {}", reveal_txid);
-		tracing::info!("reveal tx {reveal_tx:#?}");
-
-		tracing::info!("Broadcasting reveal tx...");
-		let raw_tx = encode::serialize_hex(&reveal_tx);
-		tracing::info!("raw tx: {}", &raw_tx);
-		let mut attempts = 0;
-		while attempts < Self::MAX_BROADCAST_NUM {
-			if let Err(_) = self.api.broadcast(raw_tx.clone()).await {
-				tracing::info!(
-					"Network error, will retry to broadcast reveal transaction in {} seconds...",
-					Self::BROADCAST_SLEEP_SECONDS
-				);
-				sleep(Duration::from_secs(15));
-				attempts += 1;
-				continue;
-			}
-			break;
-		}
-
-		let reveal_tx_time = Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string();
-		if attempts < Self::MAX_BROADCAST_NUM {
-			tracing::info!("✅ Successfully sent reveal tx {reveal_txid}");
-			tracing::info!("✨Congratulations! Mission completed.✨");
-
-			writeln!(
-				file,
-				"revealTx,{},{},{},{},{}",
-				"success",
-				reveal_tx_time,
-				satsbyte,
-				reveal_tx.txid(),
-				raw_tx
-			)?;
-		} else {
-			tracing::info!("❌ Failed to send reveal tx {reveal_txid}");
-
-			writeln!(
-				file,
-				"revealTx,{},{},{},{},{}",
-				"fail",
-				reveal_tx_time,
-				satsbyte,
-				reveal_tx.txid(),
-				raw_tx
-			)?;
-		}
-
-		Ok(())
-	}
-
-	async fn prepare_data(&self, wallet: &Wallet) -> Result<Data> {
-		let id = self.api.get_by_ticker(&self.ticker).await?.atomical_id;
-		tracing::info!("ticker {ticker}, id {id}", ticker = self.ticker, id = id);
-		let response = self.api.get_ft_info(id).await?;
-		let global = response.global.unwrap();
-		let ft = response.result;
-
-		if ft.ticker != self.ticker {
-			Err(anyhow::anyhow!("ticker mismatch"))?;
-		}
-		if ft.subtype != "decentralized" {
-			Err(anyhow::anyhow!("not decentralized"))?;
-		}
-		if ft.mint_height > global.height + 1 {
-			Err(anyhow::anyhow!("mint height mismatch"))?;
-		}
-		if ft.mint_amount == 0 || ft.mint_amount >= 100_000_000 {
-			Err(anyhow::anyhow!("mint amount mismatch"))?;
-		}
-		if ft.dft_info.mint_count >= ft.max_mints {
-			Err(anyhow::anyhow!("max mints reached"))?;
-		}
+				let m = Message::from_digest(h.to_byte_array());
 
-		let secp = Secp256k1::new();
-		let satsbyte = if self.network == Network::Bitcoin {
-			(util::query_fee().await? + 5).min(self.max_fee)
-		} else {
-			2
-		};
-		let additional_outputs = vec![TxOut {
-			value: Amount::from_sat(ft.mint_amount),
-			script_pubkey: wallet.stash.address.script_pubkey(),
-		}];
-		let payload = PayloadWrapper {
-			args: {
-				let (time, nonce) = util::time_nonce();
-				tracing::info!("payload time: {time}, payload nonce: {nonce}");
+				Signature { sig: secp.sign_schnorr(&m, &funding_kp), hash_ty: reveal_hty }
+			};
 
-				Payload {
-					bitworkc: ft.mint_bitworkc.clone(),
-					mint_ticker: ft.ticker.clone(),
-					nonce,
-					time,
-				}
-			},
-		};
-		let payload_encoded = util::cbor(&payload)?;
-		// TODO: More op types.
-		let reveal_script =
-			util::build_reval_script(&wallet.funding.x_only_public_key, "dmt", &payload_encoded);
-		let reveal_spend_info = TaprootBuilder::new()
-			.add_leaf(0, reveal_script.clone())?
-			.finalize(&secp, wallet.funding.x_only_public_key)
-			.unwrap();
-		let perform_bitworkr = if ft.mint_bitworkr.is_some() { true } else { false };
-		let fees = Self::fees_of(
-			satsbyte,
-			reveal_script.as_bytes().len(),
-			&additional_outputs,
-			perform_bitworkr,
-		);
-		let funding_utxo = self
-			.api
-			.wait_until_utxo(wallet.funding.address.to_string(), fees.commit_and_reveal_and_outputs)
-			.await?;
+			psbt.inputs[0] = Input {
+				// TODO: Check.
+				witness_utxo: Some(commit_output[0].clone()),
+				tap_internal_key: Some(reveal_spend_info.internal_key()),
+				tap_merkle_root: reveal_spend_info.merkle_root(),
+				final_script_witness: {
+					let mut w = Witness::new();
 
-		Ok(Data {
-			secp,
-			satsbyte,
-			bitworkc: ft.mint_bitworkc,
-			bitworkr: ft.mint_bitworkr,
-			additional_outputs,
-			reveal_script,
-			reveal_spend_info,
-			fees,
-			funding_utxo,
-		})
-	}
+					w.push(tap_key_sig.to_vec());
+					w.push(reveal_script.as_bytes());
+					w.push(
+						reveal_spend_info
+							.control_block(&(reveal_script, LeafVersion::TapScript))
+							.unwrap()
+							.serialize(),
+					);
 
-	fn fees_of(
-		satsbyte: u64,
-		reveal_script_len: usize,
-		additional_outputs: &[TxOut],
-		perform_bitworkr: bool,
-	) -> Fees {
-		let satsbyte = satsbyte as f64;
-		let commit = {
-			(satsbyte * (Self::BASE_BYTES + Self::INPUT_BYTES_BASE + Self::OUTPUT_BYTES_BASE))
-				.ceil() as u64
-		};
-		let op_return_size_bytes = if perform_bitworkr { Self::OP_RETURN_BYTES } else { 0. };
-		let reveal = {
-			let compact_input_bytes = if reveal_script_len <= 252 {
-				1.
-			} else if reveal_script_len <= 0xFFFF {
-				3.
-			} else if reveal_script_len <= 0xFFFFFFFF {
-				5.
-			} else {
-				9.
+					Some(w)
+				},
+				..Default::default()
 			};
 
-			(satsbyte
-				* (Self::BASE_BYTES
-						+ Self::REVEAL_INPUT_BYTES_BASE
-						+ (compact_input_bytes + reveal_script_len as f64) / 4.
-						// + utxos.len() as f64 * Self::INPUT_BYTES_BASE
-						+ op_return_size_bytes
-						+ additional_outputs.len() as f64 * Self::OUTPUT_BYTES_BASE))
-				.ceil() as u64
+			psbt.extract_tx_unchecked_fee_rate()
 		};
-		let outputs = additional_outputs.iter().map(|o| o.value.to_sat()).sum::<u64>();
-		let commit_and_reveal = commit + reveal;
-		let commit_and_reveal_and_outputs = commit_and_reveal + outputs;
 
-		// While satsbyte at `150`.
-		// Fees {
-		// 	commit: 16650,
-		// 	commit_and_reveal: 38700,
-		// 	commit_and_reveal_and_outputs: 58700,
-		// 	reveal: 22050,
-		// 	reveal_and_outputs: 42050,
-		// };
-		Fees {
-			commit,
-			// commit_and_reveal,
-			commit_and_reveal_and_outputs,
-			// reveal,
-			reveal_and_outputs: reveal + outputs,
+		tracing::info!("reveal txid {}", reveal_tx.txid());
+		tracing::info!("reveal tx {reveal_tx:#?}");
+
+		Ok(reveal_tx)
+	}
+
+	// Double check the mined transaction, the network rejects it otherwise.
+	fn ensure_bitwork(tx: &Transaction, bitwork: &str) -> Result<()> {
+		let txid = tx.txid();
+
+		if !util::matches_bitwork(&txid, bitwork) {
+			Err(ElectrumXError::BitworkNotSatisfied { txid, bitwork: bitwork.into() })?;
 		}
+
+		Ok(())
 	}
 
-	fn sequence_ranges() -> Vec<Range<u32>> {
-		let concurrency: u32 = num_cpus::get() as u32;
+	fn sequence_ranges(concurrency: u32) -> Vec<Range<u32>> {
 		let step = (Sequence::MAX.0 as f64 / concurrency as f64).ceil() as u32;
 		let mut ranges = Vec::new();
 		let mut start = 0;
@@ -724,31 +2314,439 @@ impl Miner {
 }
 #[derive(Debug)]
 struct MinerBuilder<'a> {
-	network: Network,
-	electrumx: &'a str,
+	// The one of the electrumx config, or mainnet, by default.
+	network: Option<Network>,
+	electrumx: Option<&'a str>,
+	electrumx_config: Option<&'a Path>,
 	wallet_dir: &'a Path,
 	ticker: &'a str,
 	max_fee: u64,
+	consolidation: Option<Consolidation>,
+	commit_confirmations: u64,
+	persist_state: bool,
+	reprice_funding: bool,
+	verify_commit: bool,
+	// All the CPUs by default.
+	workers: Option<usize>,
+	hash_rate_interval: Option<Duration>,
+	checkpoint_interval: Option<Duration>,
+	priority: WorkerPriority,
+	stop: StopCondition,
+	spend_cap: Option<u64>,
+	verify_broadcast: Option<Duration>,
+	// No health check at all by default.
+	require_healthy: usize,
+	record: bool,
 }
 impl<'a> MinerBuilder<'a> {
-	fn build(self) -> Result<Miner> {
-		let api =
-			ElectrumXBuilder::default().network(self.network).base_uris(self.electrumx).build()?;
+	// Defaults fit for a run which mints nothing, the entry points override the rest.
+	fn new(
+		network: Option<Network>,
+		electrumx: Option<&'a str>,
+		electrumx_config: Option<&'a Path>,
+		wallet_dir: &'a Path,
+	) -> Self {
+		Self {
+			network,
+			electrumx,
+			electrumx_config,
+			wallet_dir,
+			ticker: "",
+			max_fee: 0,
+			consolidation: None,
+			commit_confirmations: 0,
+			persist_state: false,
+			reprice_funding: false,
+			verify_commit: false,
+			workers: None,
+			hash_rate_interval: None,
+			checkpoint_interval: None,
+			priority: Default::default(),
+			stop: Default::default(),
+			spend_cap: None,
+			verify_broadcast: None,
+			require_healthy: 0,
+			record: false,
+		}
+	}
+
+	async fn build(self) -> Result<Miner> {
+		let mut api = match self.electrumx_config {
+			Some(p) => ElectrumXBuilder::from_config(ClientConfig::load(p)?)
+				.map_err(|e| e.context(format!("invalid config {}", p.display())))?,
+			// The public proxies of the network unless one is given.
+			None => match self.electrumx {
+				Some(u) => ElectrumXBuilder::default().base_uris(u),
+				None => ElectrumXBuilder::default(),
+			},
+		};
+
+		if let Some(n) = self.network {
+			api = api.network(n);
+		}
+		if let Some(c) = self.spend_cap {
+			api = api.spend_cap(c);
+		}
+		if let Some(w) = self.verify_broadcast {
+			api = api.verify_broadcast(w);
+		}
+		if self.require_healthy > 0 {
+			api = api.require_healthy(self.require_healthy);
+		}
+		if self.record {
+			api = api.record();
+		}
+
+		if !self.priority.cores.is_empty() {
+			let available = core_affinity::get_core_ids()
+				.ok_or_else(|| anyhow::anyhow!("pinning the workers is not supported"))?;
+
+			if let Some(c) =
+				self.priority.cores.iter().find(|&&c| !available.iter().any(|a| a.id == c))
+			{
+				Err(anyhow::anyhow!("no CPU core {c}, the available ones are {available:?}"))?;
+			}
+		}
+
+		let api = CLIENTS.get(api)?;
+
+		if self.require_healthy > 0 {
+			api.ensure_ready().await?;
+		}
+
+		let network = *api.network();
 		let wallets = RawWallet::load_wallets(self.wallet_dir)
 			.into_iter()
-			.map(|rw| Wallet::from_raw_wallet(rw, self.network))
+			.map(|rw| Wallet::from_raw_wallet(rw, network))
 			.collect::<Result<_>>()?;
 
 		Ok(Miner {
-			network: self.network,
+			network,
 			api,
 			wallets,
 			ticker: self.ticker.into(),
 			max_fee: self.max_fee,
+			consolidation: self.consolidation,
+			commit_confirmations: self.commit_confirmations,
+			persist_state: self.persist_state,
+			reprice_funding: self.reprice_funding,
+			verify_commit: self.verify_commit,
+			workers: self.workers.unwrap_or_else(num_cpus::get).max(1),
+			hash_rate_interval: self.hash_rate_interval,
+			checkpoint_interval: self.checkpoint_interval,
+			priority: self.priority,
+			stop: self.stop,
 		})
 	}
 }
 
+/// What a mint would take and produce, see [`simulate`].
+#[derive(Debug)]
+pub struct MintSimulation {
+	pub ticker: String,
+	pub ticker_id: String,
+	/// Paid to the stash address by the reveal.
+	pub mint_amount: u64,
+	pub bitworkc: String,
+	pub bitworkr: Option<String>,
+	pub satsbyte: u64,
+	pub commit_fee: u64,
+	pub reveal_fee: u64,
+	/// The value the funding UTXO needs, the fees and the mint amount.
+	pub required: u64,
+	/// Of the clean UTXOs of the funding address.
+	pub balance: u64,
+	/// The UTXO which would fund the mint, `None` if the funding address has to be topped up.
+	pub funding_utxo: Option<Utxo>,
+	pub funding_address: String,
+	pub stash_address: String,
+}
+
+/// The outcome of a [`benchmark`].
+#[derive(Debug)]
+pub struct Benchmark {
+	pub bitwork: String,
+	pub workers: usize,
+	pub hashes: u64,
+	pub elapsed: Duration,
+	/// The txids satisfying the bitwork.
+	pub solutions: Vec<Txid>,
+}
+impl Benchmark {
+	pub fn hash_rate(&self) -> f64 {
+		self.hashes as f64 / self.elapsed.as_secs_f64()
+	}
+}
+
+/// Post-mint sweep of the funding address's leftover change.
+#[derive(Debug)]
+pub struct Consolidation {
+	/// Only sweep once there are more than this many small UTXOs.
+	pub threshold: usize,
+	pub fee_rate: FeeRate,
+}
+impl Consolidation {
+	// UTXOs below this value are considered leftover change.
+	const MAX_VALUE: u64 = 10_000;
+}
+
+/// When to give up a mining search, with an [`ElectrumXError::MiningAborted`].
+///
+/// Only the searches before the commit is broadcast are given up, never one of a reveal whose
+/// commit is out.
+#[derive(Clone, Debug, Default)]
+pub struct StopCondition {
+	/// Raised from the outside, e.g. by [`StopCondition::cancel_on_ctrl_c`].
+	pub cancel: Arc<AtomicBool>,
+	/// Give up a search still going on after this long.
+	pub max_duration: Option<Duration>,
+	/// Check the ticker still has mints left this often, giving up once it has none.
+	pub mint_check_interval: Option<Duration>,
+}
+impl StopCondition {
+	// Between the checks of `cancel` and `max_duration`.
+	const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+	/// Raise `cancel` on the first Ctrl-C, exit right away on the second.
+	pub fn cancel_on_ctrl_c(&self) {
+		let cancel = self.cancel.clone();
+
+		tokio::spawn(async move {
+			while tokio::signal::ctrl_c().await.is_ok() {
+				if cancel.swap(true, Ordering::Relaxed) {
+					std::process::exit(130);
+				}
+
+				tracing::warn!("giving up the mining, press Ctrl-C again to exit right away");
+			}
+		});
+	}
+}
+
+/// Where and how eagerly the mining workers run, left to the OS by default.
+#[derive(Clone, Debug, Default)]
+pub struct WorkerPriority {
+	/// Worker `i` is pinned to the `i % cores.len()`-th of these core ids, empty pins none.
+	pub cores: Vec<usize>,
+	/// Nice level of the workers, where the platform supports it.
+	pub nice: Option<i32>,
+}
+impl WorkerPriority {
+	// On the thread of worker `i`.
+	fn apply(&self, i: usize) {
+		if let Some(&c) = self.cores.get(i % self.cores.len().max(1)) {
+			if !core_affinity::set_for_current(core_affinity::CoreId { id: c }) {
+				tracing::warn!("failed to pin mining worker {i} to CPU core {c}");
+			}
+		}
+		if let Some(n) = self.nice {
+			Self::renice(i, n);
+		}
+	}
+
+	#[cfg(unix)]
+	fn renice(i: usize, nice: i32) {
+		// Linux takes `0` as the calling thread, the other platforms as the whole process.
+		if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+			tracing::warn!(
+				"failed to set the nice level of mining worker {i} to {nice}: {}",
+				std::io::Error::last_os_error()
+			);
+		}
+	}
+
+	#[cfg(not(unix))]
+	fn renice(i: usize, nice: i32) {
+		tracing::warn!("nice levels are not supported here, mining worker {i} ignores {nice}");
+	}
+}
+
+// Runs a task every `interval` on a thread of its own, until dropped.
+struct Periodic {
+	done: Arc<AtomicBool>,
+	thread: Option<JoinHandle<()>>,
+}
+impl Periodic {
+	fn start<F>(interval: Duration, mut task: F) -> Self
+	where
+		F: 'static + Send + FnMut(),
+	{
+		let done = Arc::new(AtomicBool::new(false));
+		let thread = {
+			let done = done.clone();
+
+			thread::spawn(move || {
+				let mut next = Instant::now() + interval;
+
+				loop {
+					thread::park_timeout(next.saturating_duration_since(Instant::now()));
+
+					if done.load(Ordering::Relaxed) {
+						return;
+					}
+					if Instant::now() < next {
+						continue;
+					}
+
+					task();
+
+					next += interval;
+				}
+			})
+		};
+
+		Self { done, thread: Some(thread) }
+	}
+}
+impl Drop for Periodic {
+	fn drop(&mut self) {
+		self.done.store(true, Ordering::Relaxed);
+
+		if let Some(t) = self.thread.take() {
+			t.thread().unpark();
+			let _ = t.join();
+		}
+	}
+}
+
+// Counts the hashes of the workers of a mining stage, reporting their rate every `interval` and
+// once more when they are done.
+struct HashRate {
+	stage: &'static str,
+	workers: u32,
+	hashes: Arc<AtomicU64>,
+	started: Instant,
+	_reporter: Option<Periodic>,
+}
+impl HashRate {
+	fn start(stage: &'static str, workers: u32, interval: Option<Duration>) -> Self {
+		let hashes = Arc::new(AtomicU64::new(0));
+		let started = Instant::now();
+		let reporter = interval.map(|i| {
+			let hashes = hashes.clone();
+
+			Periodic::start(i, move || {
+				Self::report(stage, workers, hashes.load(Ordering::Relaxed), started.elapsed())
+			})
+		});
+
+		Self { stage, workers, hashes, started, _reporter: reporter }
+	}
+
+	// For the workers to count their hashes on.
+	fn counter(&self) -> Arc<AtomicU64> {
+		self.hashes.clone()
+	}
+
+	fn finish(self) {
+		Self::report(
+			self.stage,
+			self.workers,
+			self.hashes.load(Ordering::Relaxed),
+			self.started.elapsed(),
+		);
+	}
+
+	fn report(stage: &str, workers: u32, hashes: u64, elapsed: Duration) {
+		tracing::info!(
+			"{stage} mining at {:.0} H/s across {workers} workers, {hashes} hashes in {elapsed:.1?}",
+			hashes as f64 / elapsed.as_secs_f64()
+		);
+	}
+}
+
+/// A mint whose commit is about to be or was broadcast, along with its ready to go reveal.
+#[derive(Debug, Serialize, Deserialize)]
+struct MintState {
+	ticker: String,
+	commit_txid: String,
+	commit_tx: String,
+	reveal_tx: String,
+}
+impl MintState {
+	fn path_of(wallet: &Wallet) -> String {
+		format!("{}.pending.json", wallet.funding.address)
+	}
+
+	fn load(wallet: &Wallet) -> Result<Option<Self>> {
+		match fs::read(Self::path_of(wallet)) {
+			Ok(s) => Ok(Some(serde_json::from_slice(&s)?)),
+			Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e)?,
+		}
+	}
+
+	// Through a temporary file, so a crash can't leave a truncated state behind.
+	fn save(&self, wallet: &Wallet) -> Result<()> {
+		let path = Self::path_of(wallet);
+		let tmp = format!("{path}.tmp");
+
+		fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+		fs::rename(tmp, path)?;
+
+		Ok(())
+	}
+
+	fn clear(wallet: &Wallet) -> Result<()> {
+		match fs::remove_file(Self::path_of(wallet)) {
+			Err(e) if e.kind() != ErrorKind::NotFound => Err(e)?,
+			_ => Ok(()),
+		}
+	}
+}
+
+/// The progress of a commit search, along with what it takes to rebuild the same commit.
+///
+/// `template` is the txid of the unsigned commit, a search only resumes the ranges of the very
+/// same commit.
+#[derive(Debug, Serialize, Deserialize)]
+struct MiningCheckpoint {
+	ticker: String,
+	op_type: String,
+	bitworkc: String,
+	payload: String,
+	satsbyte: u64,
+	template: String,
+	// The sequences left to try.
+	ranges: Vec<Range<u32>>,
+}
+impl MiningCheckpoint {
+	fn path_of(wallet: &Wallet) -> String {
+		format!("{}.checkpoint.json", wallet.funding.address)
+	}
+
+	fn load(wallet: &Wallet) -> Result<Option<Self>> {
+		match fs::read(Self::path_of(wallet)) {
+			Ok(s) => Ok(Some(serde_json::from_slice(&s)?)),
+			Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e)?,
+		}
+	}
+
+	// Through a temporary file, so a crash can't leave a truncated checkpoint behind.
+	fn save(&self, wallet: &Wallet) -> Result<()> {
+		let path = Self::path_of(wallet);
+		let tmp = format!("{path}.tmp");
+
+		fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+		fs::rename(tmp, path)?;
+
+		Ok(())
+	}
+
+	fn clear(wallet: &Wallet) -> Result<()> {
+		match fs::remove_file(Self::path_of(wallet)) {
+			Err(e) if e.kind() != ErrorKind::NotFound => Err(e)?,
+			_ => Ok(()),
+		}
+	}
+
+	fn payload(&self) -> Result<Vec<u8>> {
+		array_bytes::hex2bytes(&self.payload)
+			.map_err(|e| anyhow::anyhow!("invalid checkpoint payload: {e:?}"))
+	}
+}
+
 #[derive(Clone, Debug)]
 struct Wallet {
 	stash: Key,
@@ -795,10 +2793,100 @@ pub struct Payload {
 	pub time: u64,
 }
 
+/// A file to embed as the payload of an NFT mint.
+#[derive(Debug)]
+pub struct FilePayload {
+	pub name: String,
+	pub content_type: String,
+	pub data: Vec<u8>,
+}
+impl FilePayload {
+	pub fn load<P>(path: P) -> Result<Self>
+	where
+		P: AsRef<Path>,
+	{
+		let path = path.as_ref();
+		let name = path
+			.file_name()
+			.and_then(|n| n.to_str())
+			.ok_or_else(|| anyhow::anyhow!("invalid file name {}", path.display()))?;
+
+		Ok(Self {
+			name: name.into(),
+			content_type: Self::content_type_of(path).into(),
+			data: fs::read(path)?,
+		})
+	}
+
+	/// Guess the content type from the extension, `application/octet-stream` if unknown.
+	pub fn content_type_of(path: &Path) -> &'static str {
+		match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+			Some("png") => "image/png",
+			Some("jpg" | "jpeg") => "image/jpeg",
+			Some("gif") => "image/gif",
+			Some("webp") => "image/webp",
+			Some("avif") => "image/avif",
+			Some("svg") => "image/svg+xml",
+			Some("json") => "application/json",
+			Some("cbor") => "application/cbor",
+			Some("txt") => "text/plain",
+			Some("html" | "htm") => "text/html",
+			_ => "application/octet-stream",
+		}
+	}
+}
+
+// The payload of an NFT mint, the file sits next to the args under its own name.
+#[derive(Debug)]
+pub struct NftPayload<'a> {
+	pub args: NftArgs,
+	pub file: &'a FilePayload,
+}
+impl Serialize for NftPayload<'_> {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		#[derive(Serialize)]
+		struct File<'a> {
+			#[serde(rename = "$ct")]
+			content_type: &'a str,
+			#[serde(rename = "$d", serialize_with = "serialize_bytes")]
+			data: &'a [u8],
+		}
+
+		// As a CBOR byte string, rather than an array of integers.
+		fn serialize_bytes<S>(data: &&[u8], serializer: S) -> Result<S::Ok, S::Error>
+		where
+			S: Serializer,
+		{
+			serializer.serialize_bytes(data)
+		}
+
+		let mut m = serializer.serialize_map(Some(2))?;
+
+		m.serialize_entry("args", &self.args)?;
+		m.serialize_entry(
+			&self.file.name,
+			&File { content_type: &self.file.content_type, data: &self.file.data },
+		)?;
+		m.end()
+	}
+}
+#[derive(Debug, Serialize)]
+pub struct NftArgs {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub bitworkc: Option<String>,
+	pub nonce: u64,
+	pub time: u64,
+}
+
 #[derive(Clone, Debug)]
 struct Data {
 	secp: Secp256k1<All>,
 	satsbyte: u64,
+	op_type: String,
+	payload: Vec<u8>,
 	bitworkc: String,
 	bitworkr: Option<String>,
 	additional_outputs: Vec<TxOut>,
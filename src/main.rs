@@ -21,9 +21,15 @@ use clap::Parser;
 #[tokio::main]
 async fn main() -> Result<()> {
 	color_eyre::install().unwrap();
-	tracing_subscriber::fmt::init();
 
-	Cli::parse().run().await?;
+	let cli = Cli::parse();
 
-	Ok(())
+	cli.init_tracing();
+
+	let r = cli.run().await;
+
+	// Also on the errors, so the summary of the clients is never lost.
+	engine::rust::shutdown().await;
+
+	r
 }
@@ -5,20 +5,29 @@ use std::{
 };
 // crates.io
 use bitcoin::{
+	absolute::LockTime,
+	consensus::encode,
+	hashes::hex::FromHex,
 	opcodes::{
 		all::{OP_CHECKSIG, OP_ENDIF, OP_IF, OP_RETURN},
 		OP_0,
 	},
 	script::PushBytes,
-	secp256k1::Keypair,
-	Address, PrivateKey, Script, ScriptBuf, XOnlyPublicKey,
+	secp256k1::{Keypair, Secp256k1, Verification},
+	taproot::{TaprootBuilder, TaprootSpendInfo},
+	transaction::Version,
+	Address, Network, OutPoint, PrivateKey, Psbt, Script, ScriptBuf, Sequence, Transaction, TxIn,
+	TxOut, Txid, XOnlyPublicKey,
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::time;
 // atomicalsir
-use crate::prelude::*;
+use crate::{
+	electrumx::r#type::{FeeRate, ScriptHash},
+	prelude::*,
+};
 
 pub async fn loop_fut<F, Fut, T>(function: F, target: &str) -> T
 where
@@ -93,6 +102,23 @@ fn cbor_should_work() {
 		array_bytes::hex2bytes_unchecked("a16461726773a468626974776f726b63666161626263636b6d696e745f7469636b657265717561726b656e6f6e63651a0098967f6474696d651a6591da53")
 	);
 }
+#[test]
+fn nft_cbor_should_work() {
+	// atomicalsir
+	use crate::engine::rust::{FilePayload, NftArgs, NftPayload};
+
+	let file = FilePayload {
+		name: "a.png".into(),
+		content_type: FilePayload::content_type_of(std::path::Path::new("a.PNG")).into(),
+		data: vec![1, 2, 3],
+	};
+
+	// The data is a byte string.
+	assert_eq!(
+		cbor(&NftPayload { args: NftArgs { bitworkc: None, nonce: 9999999, time: 1704057427 }, file: &file }).unwrap(),
+		array_bytes::hex2bytes_unchecked("a26461726773a2656e6f6e63651a0098967f6474696d651a6591da5365612e706e67a26324637469696d6167652f706e6762246443010203")
+	);
+}
 
 pub fn keypair_from_wif<S>(wif: S) -> Result<Keypair>
 where
@@ -168,6 +194,73 @@ fn build_reval_script_should_work() {
 	);
 }
 
+/// The taproot tree committing to the `reveal_script`, spendable by `x_only_public_key`.
+pub fn reveal_spend_info<C>(
+	secp: &Secp256k1<C>,
+	x_only_public_key: &XOnlyPublicKey,
+	reveal_script: &ScriptBuf,
+) -> Result<TaprootSpendInfo>
+where
+	C: Verification,
+{
+	TaprootBuilder::new()
+		.add_leaf(0, reveal_script.clone())?
+		.finalize(secp, *x_only_public_key)
+		.map_err(|_| anyhow::anyhow!("reveal taproot tree is incomplete"))
+}
+
+/// The address the commit transaction has to pay to, for the reveal of `payload`.
+///
+/// The payload carries the time and the nonce, so each candidate payload has its own commit
+/// address. Use this to verify a commit address against the intended payload.
+pub fn commit_address(
+	x_only_public_key: &XOnlyPublicKey,
+	op_type: &str,
+	payload: &[u8],
+	network: Network,
+) -> Result<Address> {
+	let reveal_script = build_reval_script(x_only_public_key, op_type, payload);
+	let spend_info =
+		reveal_spend_info(&Secp256k1::verification_only(), x_only_public_key, &reveal_script)?;
+
+	Ok(Address::p2tr_tweaked(spend_info.output_key(), network))
+}
+#[test]
+fn commit_address_should_work() {
+	// atomicalsir
+	use crate::engine::rust::{Payload, PayloadWrapper};
+
+	let secp = Secp256k1::new();
+	let x_only_public_key =
+		keypair_from_wif("L4VgnxVoaPRaptd4yW19wwd7v9dzJvQn478AKwucbaQifPFBacrp")
+			.unwrap()
+			.x_only_public_key()
+			.0;
+	let payload = |nonce| {
+		cbor(&PayloadWrapper {
+			args: Payload {
+				bitworkc: "aabbcc".into(),
+				mint_ticker: "quark".into(),
+				nonce,
+				time: 1704057427,
+			},
+		})
+		.unwrap()
+	};
+	let reveal_script = build_reval_script(&x_only_public_key, "dmt", &payload(9999999));
+	let spend_info = reveal_spend_info(&secp, &x_only_public_key, &reveal_script).unwrap();
+	let a = commit_address(&x_only_public_key, "dmt", &payload(9999999), Network::Bitcoin).unwrap();
+
+	assert_eq!(
+		a,
+		Address::p2tr(&secp, x_only_public_key, spend_info.merkle_root(), Network::Bitcoin)
+	);
+	assert_ne!(
+		a,
+		commit_address(&x_only_public_key, "dmt", &payload(9999998), Network::Bitcoin).unwrap()
+	);
+}
+
 pub fn solution_tm_nonce_script(time: u64, nonce: u32) -> ScriptBuf {
 	Script::builder()
 		.push_opcode(OP_RETURN)
@@ -175,7 +268,267 @@ pub fn solution_tm_nonce_script(time: u64, nonce: u32) -> ScriptBuf {
 		.into_script()
 }
 
-pub fn address2scripthash(address: &Address) -> Result<String> {
+/// Whether the `txid` satisfies the `bitwork`, a hex prefix optionally followed by `.N`, which
+/// requires the next hex digit to be at least `N`.
+///
+/// E.g. `abcd.5` is satisfied by `abcd5…` up to `abcdf…`.
+pub fn matches_bitwork(txid: &Txid, bitwork: &str) -> bool {
+	let txid = txid.to_string();
+	let (prefix, min) = match bitwork.split_once('.') {
+		Some((p, m)) => (p, Some(m)),
+		None => (bitwork, None),
+	};
+
+	if !txid.starts_with(prefix) {
+		return false;
+	}
+
+	let Some(min) = min else { return true };
+
+	match (min.parse::<u32>(), txid[prefix.len()..].chars().next().and_then(|c| c.to_digit(16))) {
+		(Ok(min @ 0..=15), Some(d)) => d >= min,
+		_ => false,
+	}
+}
+#[test]
+fn matches_bitwork_should_work() {
+	let txid = "abcd7f0000000000000000000000000000000000000000000000000000000000".parse().unwrap();
+
+	assert!(matches_bitwork(&txid, "abcd"));
+	assert!(matches_bitwork(&txid, "abcd.5"));
+	assert!(matches_bitwork(&txid, "abcd.7"));
+	assert!(!matches_bitwork(&txid, "abcd.8"));
+	assert!(!matches_bitwork(&txid, "abce"));
+	assert!(!matches_bitwork(&txid, "abcd.16"));
+	assert!(!matches_bitwork(&txid, "abcd.x"));
+}
+
+/// How many attempts mining the `bitwork` takes on average, see [`matches_bitwork`].
+///
+/// Each hex digit of the prefix multiplies the attempts by 16, a `.N` by `16 / (16 - N)`. Fails on
+/// a bitwork which can't be satisfied.
+pub fn bitwork_attempts(bitwork: &str) -> Result<f64> {
+	let (prefix, min) = match bitwork.split_once('.') {
+		Some((p, m)) => (p, m.parse::<u32>().ok().filter(|m| *m <= 15)),
+		None => (bitwork, Some(0)),
+	};
+	let Some(min) = min.filter(|_| prefix.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')))
+	else {
+		Err(anyhow::anyhow!("invalid bitwork {bitwork}"))?
+	};
+
+	Ok(16_f64.powi(prefix.len() as _) * 16. / (16 - min) as f64)
+}
+#[test]
+fn bitwork_attempts_should_work() {
+	assert_eq!(bitwork_attempts("").unwrap(), 1.);
+	assert_eq!(bitwork_attempts("abcd").unwrap(), 65_536.);
+	assert_eq!(bitwork_attempts("abcd.8").unwrap(), 131_072.);
+	assert!(bitwork_attempts("abcd.16").is_err());
+	assert!(bitwork_attempts("xyz").is_err());
+}
+
+/// How long mining the `bitwork` takes on average at `hashes_per_sec`, see [`bitwork_attempts`].
+///
+/// Fails on a bitwork which can't be satisfied, or a hash rate which never gets there.
+pub fn estimate_bitwork_time(bitwork: &str, hashes_per_sec: f64) -> Result<Duration> {
+	let attempts = bitwork_attempts(bitwork)?;
+
+	Duration::try_from_secs_f64(attempts / hashes_per_sec)
+		.map_err(|e| anyhow::anyhow!("can't mine {bitwork} at {hashes_per_sec} H/s, {e}"))
+}
+#[test]
+fn estimate_bitwork_time_should_work() {
+	assert_eq!(estimate_bitwork_time("", 1.).unwrap(), Duration::from_secs(1));
+	assert_eq!(estimate_bitwork_time("abcd", 65_536.).unwrap(), Duration::from_secs(1));
+	assert_eq!(estimate_bitwork_time("abcd.8", 65_536.).unwrap(), Duration::from_secs(2));
+	assert_eq!(estimate_bitwork_time("abcd.15", 65_536.).unwrap(), Duration::from_secs(16));
+	assert!(estimate_bitwork_time("abcd.16", 65_536.).is_err());
+	assert!(estimate_bitwork_time("xyz", 65_536.).is_err());
+	assert!(estimate_bitwork_time("abcd", 0.).is_err());
+}
+
+/// An unsigned transaction spending the taproot `prevout` at `previous_output` to `output`, for
+/// an external wallet to sign, e.g. a hardware wallet.
+///
+/// With a `bitwork`, the input sequence is mined until the txid satisfies it. The witness is not
+/// part of the txid, so the signed transaction satisfies it as well.
+pub fn build_unsigned_psbt(
+	previous_output: OutPoint,
+	prevout: TxOut,
+	internal_key: XOnlyPublicKey,
+	output: Vec<TxOut>,
+	bitwork: Option<&str>,
+) -> Result<Psbt> {
+	let mut tx = Transaction {
+		version: Version::ONE,
+		lock_time: LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output,
+			sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+			..Default::default()
+		}],
+		output,
+	};
+
+	if let Some(b) = bitwork {
+		let found = (0..=Sequence::MAX.0).any(|s| {
+			tx.input[0].sequence = Sequence(s);
+
+			matches_bitwork(&tx.txid(), b)
+		});
+
+		if !found {
+			Err(anyhow::anyhow!("no sequence satisfies the bitwork {b}"))?;
+		}
+	}
+
+	let mut psbt = Psbt::from_unsigned_tx(tx)?;
+
+	psbt.inputs[0].witness_utxo = Some(prevout);
+	psbt.inputs[0].tap_internal_key = Some(internal_key);
+
+	Ok(psbt)
+}
+
+/// The spending kind of an input, which determines its witness size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputKind {
+	/// A single Schnorr signature.
+	TaprootKeySpend,
+	/// Any other witness of the given serialized size, e.g. a reveal script path spend.
+	Witness(usize),
+}
+
+fn compact_size_len(n: usize) -> usize {
+	match n {
+		0..=0xFC => 1,
+		0xFD..=0xFFFF => 3,
+		0x10000..=0xFFFFFFFF => 5,
+		_ => 9,
+	}
+}
+
+// The sizes of a transaction spending `inputs` to `outputs`, without and with the witnesses.
+fn sizes_of(inputs: &[InputKind], outputs: &[TxOut]) -> (usize, usize) {
+	// Version, lock time and the counts.
+	let base = 4 + 4 + compact_size_len(inputs.len()) + compact_size_len(outputs.len());
+	// Previous outpoint, empty script sig and sequence.
+	let inputs_base = inputs.len() * (32 + 4 + 1 + 4);
+	let outputs_base = outputs
+		.iter()
+		.map(|o| 8 + compact_size_len(o.script_pubkey.len()) + o.script_pubkey.len())
+		.sum::<usize>();
+	// The segwit marker and flag, then the witnesses.
+	let witness = 2 + inputs
+		.iter()
+		.map(|i| match i {
+			InputKind::TaprootKeySpend => 1 + 1 + 64,
+			InputKind::Witness(size) => *size,
+		})
+		.sum::<usize>();
+	let stripped = base + inputs_base + outputs_base;
+
+	(stripped, stripped + witness)
+}
+
+/// Estimate the `(vsize, fee)` of a transaction spending `inputs` to `outputs` at `fee_rate`.
+///
+/// Signatures are assumed at their maximum size, so this never underestimates.
+pub fn estimate_vsize_and_fee(
+	inputs: &[InputKind],
+	outputs: &[TxOut],
+	fee_rate: FeeRate,
+) -> (u64, u64) {
+	let (stripped, total) = sizes_of(inputs, outputs);
+	let vsize = (stripped * 3 + total).div_ceil(4) as u64;
+
+	(vsize, fee_rate.fee_of(vsize))
+}
+#[test]
+fn estimate_vsize_and_fee_should_work() {
+	let output =
+		|len| TxOut { value: bitcoin::Amount::ZERO, script_pubkey: ScriptBuf::from(vec![0; len]) };
+	let rate = FeeRate::from_sat_per_vb;
+
+	assert_eq!(
+		estimate_vsize_and_fee(&[InputKind::TaprootKeySpend], &[output(34)], rate(2.)),
+		(111, 222)
+	);
+	assert_eq!(
+		estimate_vsize_and_fee(
+			&[InputKind::TaprootKeySpend, InputKind::TaprootKeySpend],
+			&[output(34), output(34)],
+			rate(1.)
+		),
+		(212, 212)
+	);
+	// 334 more witness bytes than a key spend, at a quarter each.
+	assert_eq!(
+		estimate_vsize_and_fee(&[InputKind::Witness(400)], &[output(34)], rate(1.)),
+		(195, 195)
+	);
+}
+
+/// Estimate the serialized size of a transaction spending `inputs` to `outputs`, witnesses
+/// included.
+pub fn estimate_size(inputs: &[InputKind], outputs: &[TxOut]) -> usize {
+	sizes_of(inputs, outputs).1
+}
+#[test]
+fn estimate_size_should_work() {
+	let output =
+		|len| TxOut { value: bitcoin::Amount::ZERO, script_pubkey: ScriptBuf::from(vec![0; len]) };
+
+	assert_eq!(estimate_size(&[InputKind::TaprootKeySpend], &[output(34)]), 162);
+	assert_eq!(estimate_size(&[InputKind::Witness(400)], &[output(34)]), 496);
+}
+
+/// The smallest P2TR output the nodes relay, anything below is dust.
+pub const P2TR_DUST_LIMIT: u64 = 330;
+
+/// The change output worth making out of `leftover` sats, `None` if it would be dust and is
+/// better left to the fee.
+pub fn change_of(leftover: u64) -> Option<u64> {
+	(leftover >= P2TR_DUST_LIMIT).then_some(leftover)
+}
+#[test]
+fn change_of_should_drop_dust() {
+	assert_eq!(change_of(0), None);
+	assert_eq!(change_of(P2TR_DUST_LIMIT - 1), None);
+	assert_eq!(change_of(P2TR_DUST_LIMIT), Some(P2TR_DUST_LIMIT));
+}
+
+pub fn tx_from_hex<S>(hex: S) -> Result<Transaction>
+where
+	S: AsRef<str>,
+{
+	Ok(encode::deserialize(&Vec::<u8>::from_hex(hex.as_ref())?)?)
+}
+
+pub fn psbt_from_hex<S>(hex: S) -> Result<Psbt>
+where
+	S: AsRef<str>,
+{
+	Ok(Psbt::deserialize(&Vec::<u8>::from_hex(hex.as_ref())?)?)
+}
+
+/// The canonical id of the atomical created by the `vout` output of transaction `txid`, e.g. the
+/// first output of a reveal.
+pub fn atomical_id_of(txid: &Txid, vout: u32) -> String {
+	format!("{txid}i{vout}")
+}
+#[test]
+fn atomical_id_of_should_work() {
+	let txid = "1d2f39f54320631d1f8d7b02e9e4eb2b33a7bcfac27ae5c9a5bd5cf4d488e2a1".parse().unwrap();
+
+	assert_eq!(
+		atomical_id_of(&txid, 0),
+		"1d2f39f54320631d1f8d7b02e9e4eb2b33a7bcfac27ae5c9a5bd5cf4d488e2a1i0"
+	);
+}
+
+pub fn address2scripthash(address: &Address) -> Result<ScriptHash> {
 	let mut hasher = Sha256::new();
 
 	hasher.update(address.script_pubkey());
@@ -184,7 +537,7 @@ pub fn address2scripthash(address: &Address) -> Result<String> {
 
 	hash.reverse();
 
-	Ok(array_bytes::bytes2hex("", hash))
+	Ok(ScriptHash(array_bytes::bytes2hex("", hash)))
 }
 #[test]
 fn address2scripthash_should_work() {
@@ -200,7 +553,8 @@ fn address2scripthash_should_work() {
 				.require_network(Network::Bitcoin)
 				.unwrap()
 		)
-		.unwrap(),
+		.unwrap()
+		.as_ref(),
 		"2ae9d6353b5f9b05073e3a4def3b47ab05033d8340ffa6959917c21779f956cf"
 	)
 }